@@ -0,0 +1,77 @@
+//! Compares the tree-walking decoder with compiled plans on packets of the 1.7.2 specification.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use specmc_protocol::{
+    decode::{decode, decode_ref},
+    packets::Packet,
+    parse::{tokenize, Parse, TokenStream},
+    plan::Plan,
+    resolved::ResolvedProtocol,
+    Protocol,
+};
+
+fn packets() -> Vec<(&'static str, Vec<u8>)> {
+    let mut handshake: Vec<u8> = vec![0x04, 0x09];
+    handshake.extend(b"localhost");
+    handshake.extend([0x63, 0xdd, 0x02]);
+
+    let mut position_and_look: Vec<u8> = vec![];
+    for value in [1.5f64, 64.0, 65.62, -3.5] {
+        position_and_look.extend(value.to_be_bytes());
+    }
+    for value in [90.0f32, -12.5] {
+        position_and_look.extend(value.to_be_bytes());
+    }
+    position_and_look.push(0x01);
+
+    let block_placement: Vec<u8> = vec![
+        0x00, 0x00, 0x00, 0x01, 0x40, 0xff, 0xff, 0xff, 0xfe, 0x01, // position and face
+        0x00, 0x01, 0x40, 0x00, 0x00, 0xff, 0xff, // stone without NBT
+        0x08, 0x00, 0x0f,
+    ];
+
+    let mut encryption_response: Vec<u8> = vec![0x00, 0x80];
+    encryption_response.extend([0xaa; 128]);
+    encryption_response.extend([0x00, 0x80]);
+    encryption_response.extend([0xbb; 128]);
+
+    vec![
+        ("Handshake", handshake),
+        ("PlayerPositionAndLook", position_and_look),
+        ("PlayerBlockPlacement", block_placement),
+        ("EncryptionResponse", encryption_response),
+    ]
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut tokens: TokenStream = tokenize(include_str!("../spec/1_7_2.specmc"));
+    let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+    let resolved: ResolvedProtocol = protocol.resolve().unwrap();
+    let plan: Plan = resolved.compile();
+
+    for (name, bytes) in packets() {
+        let packet: &Packet = protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == name)
+            .unwrap();
+        let id = resolved.find_packet(name).unwrap();
+        assert_eq!(
+            plan.decode(id, &bytes),
+            decode_ref(&protocol, packet, &bytes)
+        );
+
+        let mut group = c.benchmark_group(name);
+        group.bench_function("decode", |b| {
+            b.iter(|| decode(&protocol, packet, black_box(&bytes)))
+        });
+        group.bench_function("decode_ref", |b| {
+            b.iter(|| decode_ref(&protocol, packet, black_box(&bytes)))
+        });
+        group.bench_function("plan", |b| b.iter(|| plan.decode(id, black_box(&bytes))));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
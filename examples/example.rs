@@ -1,5 +1,7 @@
-use specmc_base::{parse::Parse, tokenize::tokenize};
-use specmc_protocol::Protocol;
+use specmc_protocol::{
+    parse::{tokenize, Parse, TokenStream},
+    Protocol,
+};
 
 const INPUT: &str = "
 enum TestEnum(i32) {}
@@ -15,7 +17,6 @@ packet TestPacket(serverbound, Play, 0x42) {
 }";
 
 fn main() {
-    let mut tokens: Vec<String> = tokenize(INPUT);
-    tokens.reverse();
+    let mut tokens: TokenStream = tokenize(INPUT);
     println!("{:#?}", Protocol::parse(&mut tokens));
 }
@@ -0,0 +1,510 @@
+//! Analysis of the conditions of conditional fields.
+//!
+//! Conditions comparing fields to integer or boolean literals are checked against
+//! each other and against the values the compared fields can have.
+//! Any other condition is treated as an opaque boolean.
+
+use std::collections::HashMap;
+
+use specmc_base::parse::Literal;
+
+use crate::{
+    condition::{Comparison, Condition, Operand},
+    resolved::{ResolvedCustomType, ResolvedField, ResolvedPacket, ResolvedProtocol, ResolvedType},
+};
+
+/// A condition that can't be split any further.
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    /// The field with the given index compares to the value.
+    Constraint {
+        field: usize,
+        comparison: Comparison,
+        value: i128,
+    },
+    /// A condition that is not understood, identified by its text.
+    Opaque {
+        key: String,
+        negated: bool,
+    },
+    Constant(bool),
+}
+
+/// The values a field can have.
+#[derive(Debug, Clone, PartialEq)]
+enum Domain {
+    Range(i128, i128),
+    Values(Vec<i128>),
+}
+impl Domain {
+    fn of(protocol: &ResolvedProtocol, ty: &ResolvedType) -> Self {
+        match ty {
+            ResolvedType::Bool => Domain::Range(0, 1),
+            ResolvedType::Integer(integer_type) => {
+                let range = integer_type.range();
+                Domain::Range(*range.start() as i128, *range.end() as i128)
+            }
+            ResolvedType::Enum(id) => Domain::Values(
+                protocol[*id]
+                    .variants
+                    .iter()
+                    .map(|(_, value)| *value as i128)
+                    .collect(),
+            ),
+            _ => Domain::Range(i128::MIN, i128::MAX),
+        }
+    }
+
+    /// Whether some value in the domain satisfies all constraints.
+    fn satisfies(&self, constraints: &[(Comparison, i128)]) -> bool {
+        let holds = |value: i128| {
+            constraints
+                .iter()
+                .all(|(comparison, other)| comparison.holds(value.cmp(other)))
+        };
+
+        match self {
+            Domain::Values(values) => values.iter().any(|value| holds(*value)),
+            Domain::Range(min, max) => {
+                let (mut min, mut max) = (*min, *max);
+                let mut excluded: Vec<i128> = vec![];
+                for (comparison, value) in constraints {
+                    match comparison {
+                        Comparison::Eq => {
+                            min = min.max(*value);
+                            max = max.min(*value);
+                        }
+                        Comparison::Ne => excluded.push(*value),
+                        Comparison::Lt => max = max.min(value.saturating_sub(1)),
+                        Comparison::Le => max = max.min(*value),
+                        Comparison::Gt => min = min.max(value.saturating_add(1)),
+                        Comparison::Ge => min = min.max(*value),
+                    }
+                }
+                // Only as many values as there are exclusions have to be checked
+                min <= max
+                    && (0..=excluded.len() as i128)
+                        .map_while(|offset| min.checked_add(offset).filter(|v| *v <= max))
+                        .any(|value| !excluded.contains(&value))
+            }
+        }
+    }
+}
+
+/// A condition in disjunctive normal form.
+type Dnf = Vec<Vec<Atom>>;
+
+fn literal_value(literal: &Literal) -> Option<i128> {
+    match literal {
+        Literal::Boolean(value) => Some(*value as i128),
+        Literal::Integer(value) => Some(*value as i128),
+        _ => None,
+    }
+}
+
+fn opaque(condition: &Condition<usize>, negated: bool) -> Dnf {
+    vec![vec![Atom::Opaque {
+        key: condition.to_string(),
+        negated,
+    }]]
+}
+
+fn dnf(condition: &Condition<usize>, negated: bool) -> Dnf {
+    match condition {
+        Condition::Not(condition) => dnf(condition, !negated),
+        Condition::And(lhs, rhs) | Condition::Or(lhs, rhs) => {
+            let (lhs, rhs) = (dnf(lhs, negated), dnf(rhs, negated));
+            if matches!(condition, Condition::And(..)) != negated {
+                lhs.iter()
+                    .flat_map(|a| rhs.iter().map(move |b| [a.clone(), b.clone()].concat()))
+                    .collect()
+            } else {
+                [lhs, rhs].concat()
+            }
+        }
+        Condition::Truthy(Operand::Field(field)) => vec![vec![Atom::Constraint {
+            field: *field,
+            comparison: if negated {
+                Comparison::Eq
+            } else {
+                Comparison::Ne
+            },
+            value: 0,
+        }]],
+        Condition::Truthy(Operand::Literal(literal)) => match literal_value(literal) {
+            Some(value) => vec![vec![Atom::Constant((value != 0) != negated)]],
+            None => opaque(condition, negated),
+        },
+        Condition::Compare(lhs, comparison, rhs) => {
+            let comparison: Comparison = if negated {
+                comparison.negate()
+            } else {
+                *comparison
+            };
+            match (lhs, rhs) {
+                (Operand::Field(field), Operand::Literal(literal)) => {
+                    match literal_value(literal) {
+                        Some(value) => vec![vec![Atom::Constraint {
+                            field: *field,
+                            comparison,
+                            value,
+                        }]],
+                        None => opaque(condition, negated),
+                    }
+                }
+                (Operand::Literal(literal), Operand::Field(field)) => {
+                    match literal_value(literal) {
+                        Some(value) => vec![vec![Atom::Constraint {
+                            field: *field,
+                            comparison: comparison.flip(),
+                            value,
+                        }]],
+                        None => opaque(condition, negated),
+                    }
+                }
+                (Operand::Literal(lhs), Operand::Literal(rhs)) => {
+                    match (literal_value(lhs), literal_value(rhs)) {
+                        (Some(lhs), Some(rhs)) => {
+                            vec![vec![Atom::Constant(comparison.holds(lhs.cmp(&rhs)))]]
+                        }
+                        _ => opaque(condition, negated),
+                    }
+                }
+                (Operand::Field(_), Operand::Field(_)) => opaque(condition, negated),
+            }
+        }
+    }
+}
+
+/// A reachable combination of conditions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Combination {
+    /// Indices into [`ConditionAnalysis::conditions`] and whether they hold.
+    /// Conditions that don't affect which fields are present are left out.
+    pub assignment: Vec<(usize, bool)>,
+    /// Indices of the fields that are present.
+    pub fields: Vec<usize>,
+}
+
+/// Analysis of the conditions of a list of fields.
+#[derive(Debug, Clone)]
+pub struct ConditionAnalysis {
+    /// The distinct conditions of the fields.
+    pub conditions: Vec<Condition<usize>>,
+    /// For each field, indices into `conditions` that must hold for it to be present.
+    pub field_conditions: Vec<Vec<usize>>,
+    domains: Vec<Domain>,
+}
+impl ConditionAnalysis {
+    pub fn new(protocol: &ResolvedProtocol, fields: &[ResolvedField]) -> Self {
+        let mut conditions: Vec<Condition<usize>> = vec![];
+        let field_conditions: Vec<Vec<usize>> = fields
+            .iter()
+            .map(|field| {
+                field
+                    .conditions
+                    .iter()
+                    .map(|condition| {
+                        conditions
+                            .iter()
+                            .position(|other| other == condition)
+                            .unwrap_or_else(|| {
+                                conditions.push(condition.clone());
+                                conditions.len() - 1
+                            })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        ConditionAnalysis {
+            conditions,
+            field_conditions,
+            domains: fields
+                .iter()
+                .map(|field| Domain::of(protocol, &field.ty))
+                .collect(),
+        }
+    }
+
+    fn is_satisfiable(&self, conjunction: &[Atom]) -> bool {
+        let mut constraints: HashMap<usize, Vec<(Comparison, i128)>> = HashMap::new();
+        for atom in conjunction {
+            match atom {
+                Atom::Constraint {
+                    field,
+                    comparison,
+                    value,
+                } => constraints
+                    .entry(*field)
+                    .or_default()
+                    .push((*comparison, *value)),
+                Atom::Opaque { key, negated } => {
+                    let contradiction = Atom::Opaque {
+                        key: key.clone(),
+                        negated: !negated,
+                    };
+                    if conjunction.contains(&contradiction) {
+                        return false;
+                    }
+                }
+                Atom::Constant(value) => {
+                    if !value {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        constraints
+            .iter()
+            .all(|(field, constraints)| self.domains[*field].satisfies(constraints))
+    }
+
+    /// Whether all given conditions can hold at the same time.
+    fn all_satisfiable(&self, assignment: &[(usize, bool)]) -> bool {
+        let mut terms: Dnf = vec![vec![]];
+        for (condition, value) in assignment {
+            let condition: Dnf = dnf(&self.conditions[*condition], !value);
+            terms = terms
+                .iter()
+                .flat_map(|a| {
+                    condition
+                        .iter()
+                        .map(move |b| [a.clone(), b.clone()].concat())
+                })
+                .filter(|term| self.is_satisfiable(term))
+                .collect();
+            if terms.is_empty() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether the field with the given index can be present.
+    pub fn is_reachable(&self, field: usize) -> bool {
+        let assignment: Vec<(usize, bool)> = self.field_conditions[field]
+            .iter()
+            .map(|condition| (*condition, true))
+            .collect();
+        self.all_satisfiable(&assignment)
+    }
+
+    /// Indices of the fields that can never be present.
+    pub fn unreachable(&self) -> Vec<usize> {
+        (0..self.field_conditions.len())
+            .filter(|field| !self.is_reachable(*field))
+            .collect()
+    }
+
+    /// Whether the two fields can never be present at the same time.
+    pub fn are_exclusive(&self, a: usize, b: usize) -> bool {
+        let assignment: Vec<(usize, bool)> = self.field_conditions[a]
+            .iter()
+            .chain(&self.field_conditions[b])
+            .map(|condition| (*condition, true))
+            .collect();
+        !self.all_satisfiable(&assignment)
+    }
+
+    /// All reachable combinations of conditions with the fields present in each.
+    pub fn combinations(&self) -> Vec<Combination> {
+        let mut combinations: Vec<Combination> = vec![];
+        self.combine(&mut vec![], 0, &mut combinations);
+        combinations
+    }
+
+    fn combine(
+        &self,
+        assignment: &mut Vec<(usize, bool)>,
+        next: usize,
+        combinations: &mut Vec<Combination>,
+    ) {
+        let holds = |condition: &usize, assignment: &[(usize, bool)]| {
+            assignment
+                .iter()
+                .find(|(other, _)| other == condition)
+                .map(|(_, value)| *value)
+        };
+
+        if next == self.conditions.len() {
+            let fields: Vec<usize> = (0..self.field_conditions.len())
+                .filter(|field| {
+                    self.field_conditions[*field]
+                        .iter()
+                        .all(|condition| holds(condition, assignment) == Some(true))
+                })
+                .collect();
+            combinations.push(Combination {
+                assignment: assignment.clone(),
+                fields,
+            });
+            return;
+        }
+
+        // A condition only matters if some field using it may still be present
+        let relevant: bool = self.field_conditions.iter().any(|conditions| {
+            conditions.contains(&next)
+                && conditions
+                    .iter()
+                    .all(|condition| holds(condition, assignment) != Some(false))
+        });
+        if !relevant {
+            self.combine(assignment, next + 1, combinations);
+            return;
+        }
+
+        for value in [true, false] {
+            assignment.push((next, value));
+            if self.all_satisfiable(assignment) {
+                self.combine(assignment, next + 1, combinations);
+            }
+            assignment.pop();
+        }
+    }
+}
+
+impl ResolvedPacket {
+    pub fn analyze_conditions(&self, protocol: &ResolvedProtocol) -> ConditionAnalysis {
+        ConditionAnalysis::new(protocol, &self.fields)
+    }
+}
+impl ResolvedCustomType {
+    pub fn analyze_conditions(&self, protocol: &ResolvedProtocol) -> ConditionAnalysis {
+        ConditionAnalysis::new(protocol, &self.fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use crate::Protocol;
+
+    use super::*;
+
+    fn analyze(fields: &str) -> ConditionAnalysis {
+        let mut tokens: TokenStream = tokenize!(&format!(
+            "
+            enum State(i32) {{
+                Play
+            }}
+            enum Mode(u8) {{
+                Create
+                Remove
+                Update
+            }}
+            type T {{
+                {fields}
+            }}
+            "
+        ));
+        let protocol: ResolvedProtocol = Protocol::parse(&mut tokens).unwrap().resolve().unwrap();
+        protocol.types[0].analyze_conditions(&protocol)
+    }
+
+    #[test]
+    fn test_reachability() {
+        let analysis: ConditionAnalysis = analyze(
+            "
+            i16 id
+            u8 count
+            if (id > 5) {
+                if (id < 3) {
+                    bool never
+                }
+                bool sometimes
+            }
+            if (count > 255) {
+                bool overflow
+            }
+            if (count >= 0) {
+                bool always
+            }
+            if (id != -1 && id == -1) {
+                bool contradiction
+            }
+            ",
+        );
+
+        assert_eq!(analysis.unreachable(), vec![2, 4, 6]);
+        assert!(analysis.is_reachable(3));
+        assert!(analysis.is_reachable(5));
+    }
+
+    #[test]
+    fn test_exclusivity() {
+        let analysis: ConditionAnalysis = analyze(
+            "
+            bool flag
+            Mode mode
+            if (flag) {
+                i32 a
+            }
+            if (!flag) {
+                i32 b
+            }
+            if (mode == Create || mode == Update) {
+                String c
+            }
+            if (mode == Remove) {
+                String d
+            }
+            if (mode != Create) {
+                String e
+            }
+            ",
+        );
+
+        assert!(analysis.are_exclusive(2, 3));
+        assert!(!analysis.are_exclusive(2, 4));
+        assert!(analysis.are_exclusive(4, 5));
+        assert!(!analysis.are_exclusive(4, 6));
+        assert!(!analysis.are_exclusive(5, 6));
+        assert!(analysis.unreachable().is_empty());
+    }
+
+    #[test]
+    fn test_combinations() {
+        let analysis: ConditionAnalysis = analyze(
+            "
+            i16 id
+            if (id != -1) {
+                i8 count
+                i16 data_length
+                if (data_length != -1) {
+                    List[u8; 4] data
+                }
+            }
+            ",
+        );
+        let fields: Vec<Vec<usize>> = analysis
+            .combinations()
+            .into_iter()
+            .map(|combination| combination.fields)
+            .collect();
+        assert_eq!(fields, vec![vec![0, 1, 2, 3], vec![0, 1, 2], vec![0]]);
+
+        let analysis: ConditionAnalysis = analyze(
+            "
+            Mode mode
+            if (mode == Create) {
+                String name
+            }
+            if (mode != Remove) {
+                i32 value
+            }
+            ",
+        );
+        let fields: Vec<Vec<usize>> = analysis
+            .combinations()
+            .into_iter()
+            .map(|combination| combination.fields)
+            .collect();
+        assert_eq!(fields, vec![vec![0, 1, 2], vec![0, 2], vec![0]]);
+    }
+}
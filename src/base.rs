@@ -1,12 +1,17 @@
-use std::{collections::HashSet, ops::RangeInclusive, option, string};
+use std::{collections::HashSet, fmt::Display, ops::RangeInclusive, option};
 
 use specmc_base::{
-    ensure_tokens,
-    parse::{Identifier, Literal, Parse, ParseError},
+    ensure,
+    parse::{Identifier, Literal},
 };
 use strtoint::strtoint;
 
-use crate::types::Type;
+use crate::{
+    ensure_tokens,
+    parse::{Parse, Span, Token, TokenStream, KEYWORDS},
+    types::Type,
+    Error,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum IntegerType {
@@ -34,11 +39,12 @@ impl IntegerType {
             U8 => range!(u8),
             U16 => range!(u16),
             U32 => range!(u32),
-            U64 => range!(u64),
             I8 => range!(i8),
             I16 => range!(i16),
             I32 | VarInt => range!(i32),
             I64 | VarLong => range!(i64),
+            // `u64::MAX` does not fit in an `isize`
+            U64 => 0..=isize::MAX,
         }
     }
 
@@ -46,10 +52,29 @@ impl IntegerType {
         self.range().contains(&value)
     }
 }
+impl Display for IntegerType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use IntegerType::*;
+        let name: &str = match self {
+            U8 => "u8",
+            U16 => "u16",
+            U32 => "u32",
+            U64 => "u64",
+            I8 => "i8",
+            I16 => "i16",
+            I32 => "i32",
+            I64 => "i64",
+            VarInt => "VarInt",
+            VarLong => "VarLong",
+        };
+        write!(f, "{name}")
+    }
+}
 impl Parse for IntegerType {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
         use IntegerType::*;
-        match tokens.pop().ok_or(ParseError::EndOfFile)?.as_str() {
+        let token: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+        match token.as_str() {
             "u8" => Ok(U8),
             "u16" => Ok(U16),
             "u32" => Ok(U32),
@@ -60,12 +85,13 @@ impl Parse for IntegerType {
             "i64" => Ok(I64),
             "VarInt" => Ok(VarInt),
             "VarLong" => Ok(VarLong),
-            token => {
-                tokens.push(token.to_string());
-                Err(ParseError::InvalidToken {
-                    token: token.to_string(),
-                    error: "Invalid integer type".to_string(),
-                })
+            _ => {
+                let error: Error = Error::InvalidIntegerType {
+                    token: token.text.clone(),
+                    span: token.span,
+                };
+                tokens.push(token);
+                Err(error)
             }
         }
     }
@@ -88,10 +114,55 @@ pub enum BaseType {
     Nbt,
     // Option(Box<Type>),
 }
+impl BaseType {
+    /// The maximum length of a string without an explicit length.
+    pub const MAX_STRING_LENGTH: usize = 32767;
+
+    /// Check whether a literal can be assigned to a field of this type.
+    /// String lengths are counted in UTF-16 code units.
+    pub fn accepts(&self, literal: &Literal) -> bool {
+        use BaseType::*;
+        match (self, literal) {
+            (Bool, Literal::Boolean(_)) => true,
+            (Integer(ty), Literal::Integer(value)) => ty.check(*value),
+            (F32, Literal::Integer(_)) | (F64, Literal::Integer(_)) | (F64, Literal::Float(_)) => {
+                true
+            }
+            (F32, Literal::Float(value)) => value.is_nan() || value.abs() <= f32::MAX as f64,
+            (String { length }, Literal::String(value)) => {
+                value.encode_utf16().count() <= length.unwrap_or(Self::MAX_STRING_LENGTH)
+            }
+            _ => false,
+        }
+    }
+}
+/// Types are displayed as they are written in the specification, like `List[u8; 16]`.
+impl Display for BaseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use BaseType::*;
+        match self {
+            Bool => write!(f, "bool"),
+            Integer(ty) => write!(f, "{ty}"),
+            F32 => write!(f, "f32"),
+            F64 => write!(f, "f64"),
+            String { length: None } => write!(f, "String"),
+            String {
+                length: Some(length),
+            } => write!(f, "String[{length}]"),
+            List { ty, length: None } => write!(f, "List[{ty}]"),
+            List {
+                ty,
+                length: Some(length),
+            } => write!(f, "List[{ty}; {length}]"),
+            Nbt => write!(f, "Nbt"),
+        }
+    }
+}
 impl Parse for BaseType {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
         use BaseType::*;
-        match tokens.pop().ok_or(ParseError::EndOfFile)?.as_str() {
+        let token: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+        match token.as_str() {
             "bool" => Ok(Bool),
             "f32" => Ok(F32),
             "f64" => Ok(F64),
@@ -100,11 +171,12 @@ impl Parse for BaseType {
 
                 if !tokens.is_empty() && tokens.last().unwrap() == "[" {
                     tokens.pop();
+                    let span: Span = tokens.next_span();
                     let _length: Literal = Literal::parse(tokens)?;
                     let Literal::Integer(_length) = _length else {
-                        return Err(ParseError::InvalidToken {
+                        return Err(Error::InvalidListLength {
                             token: format!("{_length:?}"),
-                            error: "Invalid list length".to_string(),
+                            span,
                         });
                     };
                     length = Some(_length as usize);
@@ -119,11 +191,14 @@ impl Parse for BaseType {
                 let mut length: option::Option<usize> = None;
                 if !tokens.is_empty() && tokens.last().unwrap() == ";" {
                     tokens.pop();
-                    let _length: string::String = tokens.pop().ok_or(ParseError::EndOfFile)?;
-                    length = Some(strtoint(&_length).map_err(|_| ParseError::InvalidToken {
-                        token: _length,
-                        error: "Invalid list length".to_string(),
-                    })?);
+                    let _length: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+                    length =
+                        Some(
+                            strtoint(_length.as_str()).map_err(|_| Error::InvalidListLength {
+                                token: _length.text.clone(),
+                                span: _length.span,
+                            })?,
+                        );
                 }
                 ensure_tokens!(tokens, "]");
                 Ok(List { ty, length })
@@ -135,14 +210,13 @@ impl Parse for BaseType {
             //     ensure_tokens!(tokens, "]");
             //     Ok(Option(ty))
             // }
-            token => {
-                tokens.push(token.to_string());
-                Ok(Integer(IntegerType::parse(tokens).map_err(|_| {
-                    ParseError::InvalidToken {
-                        token: token.to_string(),
-                        error: "Invalid type".to_string(),
-                    }
-                })?))
+            _ => {
+                let error: Error = Error::InvalidType {
+                    token: token.text.clone(),
+                    span: token.span,
+                };
+                tokens.push(token);
+                Ok(Integer(IntegerType::parse(tokens).map_err(|_| error)?))
             }
         }
     }
@@ -151,24 +225,33 @@ impl Parse for BaseType {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     /// Length of a list or nbt
-    Length(Identifier),
+    Length(Identifier, Span),
     /// A literal value
-    Literal(Literal),
+    Literal(Literal, Span),
     /// Some identifier
-    Identifier(Identifier),
+    Identifier(Identifier, Span),
+}
+impl Value {
+    pub fn span(&self) -> Span {
+        match self {
+            Value::Length(_, span) | Value::Literal(_, span) | Value::Identifier(_, span) => *span,
+        }
+    }
 }
 impl Parse for Value {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
-        if tokens.last().ok_or(ParseError::EndOfFile)? == "len" {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let start: Span = tokens.next_span();
+        if tokens.last().ok_or(Error::EndOfFile)? == "len" {
             tokens.pop();
             ensure_tokens!(tokens, "(");
             let length: Identifier = Identifier::parse(tokens)?;
             ensure_tokens!(tokens, ")");
-            Ok(Value::Length(length))
+            Ok(Value::Length(length, tokens.span_from(start)))
         } else if let Ok(literal) = Literal::parse(tokens) {
-            Ok(Value::Literal(literal))
+            Ok(Value::Literal(literal, tokens.span_from(start)))
         } else {
-            Ok(Value::Identifier(Identifier::parse(tokens)?))
+            let identifier: Identifier = Identifier::parse(tokens)?;
+            Ok(Value::Identifier(identifier, tokens.span_from(start)))
         }
     }
 }
@@ -179,9 +262,11 @@ pub struct Field {
     pub name: Identifier,
     pub value: Option<Value>,
     pub conditions: HashSet<String>,
+    pub span: Span,
 }
 impl Parse for Field {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let start: Span = tokens.next_span();
         let ty: Type = Type::parse(tokens)?;
         let name: Identifier = Identifier::parse(tokens)?;
         let mut value: Option<Value> = None;
@@ -191,58 +276,145 @@ impl Parse for Field {
             value = Some(Value::parse(tokens)?);
         }
 
+        if let (Type::BaseType(base_type), Some(Value::Literal(literal, span))) = (&ty, &value) {
+            ensure!(
+                base_type.accepts(literal),
+                Error::IncompatibleDefault {
+                    value: literal.clone(),
+                    span: *span,
+                }
+            );
+        }
+
         Ok(Field {
             name,
             ty,
             value,
             conditions: HashSet::new(),
+            span: tokens.span_from(start),
         })
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FieldList(pub Vec<Field>);
+impl FieldList {
+    /// Parse the condition and opening bracket of an `if` block.
+    fn condition(tokens: &mut TokenStream) -> Result<String, Error> {
+        ensure_tokens!(tokens, "(");
+
+        let mut condition: String = "".to_string();
+        let mut paren_count: usize = 1;
+        let mut in_string: bool = false;
+        while !tokens.is_empty() && paren_count != 0 {
+            // Brackets can't be part of a condition, so the parenthesis was not closed
+            if let ("{" | "}", false) = (tokens.last().unwrap().as_str(), in_string) {
+                let token: &Token = tokens.last().unwrap();
+                return Err(Error::Expected {
+                    expected: ")".to_string(),
+                    found: token.text.clone(),
+                    span: token.span,
+                });
+            }
+            match (tokens.last().unwrap().as_str(), in_string) {
+                ("(", false) => paren_count += 1,
+                (")", false) => paren_count -= 1,
+                _ => {}
+            }
+            // Keep nested parentheses, they are part of the condition
+            if paren_count != 0 {
+                // Strings are kept as written
+                if !condition.is_empty() && !in_string {
+                    condition += " ";
+                }
+                condition += tokens.last().unwrap().as_str();
+                if tokens.last().unwrap() == "\"" {
+                    in_string = !in_string;
+                }
+            }
+            tokens.pop();
+        }
+
+        ensure_tokens!(tokens, "{");
+        Ok(condition)
+    }
+
+    /// Skip the rest of the line of the last consumed token, and any block opened on it.
+    /// Stops before a `}` that closes the enclosing block.
+    fn skip_line(tokens: &mut TokenStream, start: usize) {
+        let line: usize = tokens.previous_span().map_or(0, |span| span.line);
+        let mut depth: usize = 0;
+        while let Some(token) = tokens.last() {
+            match token.as_str() {
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                "{" => depth += 1,
+                _ if depth == 0 && token.span.line > line && tokens.len() < start => break,
+                _ => {}
+            }
+            tokens.pop();
+        }
+    }
+}
 impl Parse for FieldList {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    /// Stops before the `}` that closes the enclosing block.
+    /// If the stream is recovering, invalid fields are recorded and skipped.
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
         let mut value: Vec<Field> = vec![];
         let mut conditions: Vec<String> = vec![];
-        let mut bracket_count: usize = 0;
-        while !tokens.is_empty() {
-            match tokens.pop().unwrap().as_str() {
+        // Where the `if` blocks that are still open start
+        let mut open: Vec<Span> = vec![];
+        loop {
+            let token: Token = match tokens.pop() {
+                // A new item starts, so the enclosing blocks were never closed
+                Some(token) if !KEYWORDS.contains(&token.as_str()) => token,
+                token => {
+                    if let Some(token) = token {
+                        tokens.push(token);
+                    }
+                    let span: Span = tokens.next_span();
+                    if let Some(open) = open.pop() {
+                        return Err(Error::UnclosedBlock {
+                            kind: "if".to_string(),
+                            open,
+                            span,
+                        });
+                    }
+                    break;
+                }
+            };
+            match token.as_str() {
                 "}" => {
-                    if bracket_count == 0 {
-                        tokens.push("}".to_string());
+                    if open.pop().is_none() {
+                        tokens.push(token);
                         break;
                     }
-                    bracket_count -= 1;
                     conditions.pop();
                 }
                 "if" => {
-                    ensure_tokens!(tokens, "(");
-
-                    let mut condition: String = "".to_string();
-                    let mut paren_count: usize = 1;
-                    while !tokens.is_empty() && paren_count != 0 {
-                        if tokens.last().unwrap() == "(" {
-                            paren_count += 1;
-                        } else if tokens.last().unwrap() == ")" {
-                            paren_count -= 1;
-                        } else {
-                            if !condition.is_empty() {
-                                condition += " ";
-                            }
-                            condition += &tokens.last().unwrap();
+                    let start: usize = tokens.len() + 1;
+                    match Self::condition(tokens) {
+                        Ok(condition) => {
+                            conditions.push(condition);
+                            open.push(token.span);
+                        }
+                        Err(error) => {
+                            tokens.recover(error)?;
+                            Self::skip_line(tokens, start);
                         }
-                        tokens.pop();
                     }
-                    conditions.push(condition);
-
-                    ensure_tokens!(tokens, "{");
-                    bracket_count += 1;
                 }
-                token => {
-                    tokens.push(token.to_string());
-                    let mut field: Field = Field::parse(tokens)?;
+                _ => {
+                    tokens.push(token);
+                    let start: usize = tokens.len();
+                    let mut field: Field = match Field::parse(tokens) {
+                        Ok(field) => field,
+                        Err(error) => {
+                            tokens.recover(error)?;
+                            Self::skip_line(tokens, start);
+                            continue;
+                        }
+                    };
 
                     if !conditions.is_empty() {
                         field.conditions = HashSet::from_iter(conditions.clone());
@@ -259,7 +431,7 @@ impl Parse for FieldList {
 
 #[cfg(test)]
 mod tests {
-    use specmc_base::tokenize;
+    use crate::tokenize;
 
     use crate::test_parse;
 
@@ -267,7 +439,7 @@ mod tests {
 
     #[test]
     fn test_integer_type() {
-        let mut tokens: Vec<String> =
+        let mut tokens: TokenStream =
             tokenize!("u8 u16 u32 u64 i8 i16 i32 i64 VarInt VarLong Unknown");
 
         test_parse!(tokens, IntegerType, Ok(IntegerType::U8));
@@ -284,19 +456,19 @@ mod tests {
         test_parse!(
             tokens,
             IntegerType,
-            Err(ParseError::InvalidToken {
+            Err(Error::InvalidIntegerType {
                 token: "Unknown".to_string(),
-                error: "Invalid integer type".to_string(),
+                span: Span::default(),
             })
         );
         assert_eq!(tokens.pop().unwrap(), "Unknown");
         assert!(tokens.is_empty());
-        test_parse!(tokens, IntegerType, Err(ParseError::EndOfFile));
+        test_parse!(tokens, IntegerType, Err(Error::EndOfFile));
     }
 
     #[test]
     fn test_base_type() {
-        let mut tokens: Vec<String> =
+        let mut tokens: TokenStream =
             tokenize!("bool VarInt f32 f64 String String[42] List[i32] List[u8; 42] Nbt Unknown");
 
         test_parse!(tokens, BaseType, Ok(BaseType::Bool));
@@ -333,42 +505,61 @@ mod tests {
         test_parse!(
             tokens,
             BaseType,
-            Err(ParseError::InvalidToken {
+            Err(Error::InvalidType {
                 token: "Unknown".to_string(),
-                error: "Invalid type".to_string(),
+                span: Span::default(),
             })
         );
         assert_eq!(tokens.pop().unwrap(), "Unknown");
         assert!(tokens.is_empty());
-        test_parse!(tokens, BaseType, Err(ParseError::EndOfFile));
+        test_parse!(tokens, BaseType, Err(Error::EndOfFile));
+
+        // Types are displayed as they are written
+        let input: &str = "bool VarInt f32 f64 String String[42] List[i32] List[u8; 42] Nbt";
+        let mut tokens: TokenStream = tokenize!(input);
+        let mut types: Vec<String> = vec![];
+        while !tokens.is_empty() {
+            types.push(BaseType::parse(&mut tokens).unwrap().to_string());
+        }
+        assert_eq!(types.join(" "), input);
     }
 
     #[test]
     fn test_value() {
-        let mut tokens: Vec<String> = tokenize!("len(iden) 42.0 iden");
+        let mut tokens: TokenStream = tokenize!("len(iden) 42.0 iden");
 
         test_parse!(
             tokens,
             Value,
-            Ok(Value::Length(Identifier("iden".to_string())))
+            Ok(Value::Length(
+                Identifier("iden".to_string()),
+                Span::default()
+            ))
+        );
+        test_parse!(
+            tokens,
+            Value,
+            Ok(Value::Literal(Literal::Float(42.0), Span::default()))
         );
-        test_parse!(tokens, Value, Ok(Value::Literal(Literal::Float(42.0))));
         test_parse!(
             tokens,
             Value,
-            Ok(Value::Identifier(Identifier("iden".to_string())))
+            Ok(Value::Identifier(
+                Identifier("iden".to_string()),
+                Span::default()
+            ))
         );
 
         assert!(tokens.is_empty());
-        test_parse!(tokens, Value, Err(ParseError::EndOfFile));
+        test_parse!(tokens, Value, Err(Error::EndOfFile));
     }
 
     #[test]
     fn test_field() {
-        let mut tokens: Vec<String> = tokenize!(
+        let mut tokens: TokenStream = tokenize!(
             "
             i32 first_field
-            Nbt second_field = 42.0
+            f64 second_field = 42.0
             i64 third_field = len(list)
             List[i32] list
             "
@@ -382,16 +573,18 @@ mod tests {
                 name: Identifier("first_field".to_string()),
                 value: None,
                 conditions: HashSet::new(),
+                span: Span::default(),
             })
         );
         test_parse!(
             tokens,
             Field,
             Ok(Field {
-                ty: Type::BaseType(BaseType::Nbt),
+                ty: Type::BaseType(BaseType::F64),
                 name: Identifier("second_field".to_string()),
-                value: Some(Value::Literal(Literal::Float(42.0))),
+                value: Some(Value::Literal(Literal::Float(42.0), Span::default())),
                 conditions: HashSet::new(),
+                span: Span::default(),
             })
         );
         test_parse!(
@@ -400,8 +593,12 @@ mod tests {
             Ok(Field {
                 ty: Type::BaseType(BaseType::Integer(IntegerType::I64)),
                 name: Identifier("third_field".to_string()),
-                value: Some(Value::Length(Identifier("list".to_string()))),
+                value: Some(Value::Length(
+                    Identifier("list".to_string()),
+                    Span::default()
+                )),
                 conditions: HashSet::new(),
+                span: Span::default(),
             })
         );
         test_parse!(
@@ -415,16 +612,69 @@ mod tests {
                 name: Identifier("list".to_string()),
                 value: None,
                 conditions: HashSet::new(),
+                span: Span::default(),
             })
         );
 
         assert!(tokens.is_empty());
-        test_parse!(tokens, Field, Err(ParseError::EndOfFile));
+        test_parse!(tokens, Field, Err(Error::EndOfFile));
+    }
+
+    #[test]
+    fn test_field_default() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            u8 a = 255
+            f32 b = 1
+            String[2] c = \"ab\"
+            Nbt d = 42.0
+            bool e = \"true\"
+            u8 f = 300
+            String[2] g = \"abc\"
+            "
+        );
+
+        assert!(Field::parse(&mut tokens).is_ok());
+        assert!(Field::parse(&mut tokens).is_ok());
+        assert!(Field::parse(&mut tokens).is_ok());
+        test_parse!(
+            tokens,
+            Field,
+            Err(Error::IncompatibleDefault {
+                value: Literal::Float(42.0),
+                span: Span::default(),
+            })
+        );
+        test_parse!(
+            tokens,
+            Field,
+            Err(Error::IncompatibleDefault {
+                value: Literal::String("true".to_string()),
+                span: Span::default(),
+            })
+        );
+        test_parse!(
+            tokens,
+            Field,
+            Err(Error::IncompatibleDefault {
+                value: Literal::Integer(300),
+                span: Span::default(),
+            })
+        );
+        test_parse!(
+            tokens,
+            Field,
+            Err(Error::IncompatibleDefault {
+                value: Literal::String("abc".to_string()),
+                span: Span::default(),
+            })
+        );
+        assert!(tokens.is_empty());
     }
 
     #[test]
     fn test_field_list() {
-        let mut tokens: Vec<String> = tokenize!(
+        let mut tokens: TokenStream = tokenize!(
             "
             bool cond
             if (cond) {
@@ -433,6 +683,13 @@ mod tests {
             if (!cond) {
                 u64 other
             }
+            if ((!cond) && (number > 0)) {
+                bool both
+            }
+            String mode
+            if (mode == \" a \") {
+                bool spaced
+            }
             "
         );
 
@@ -445,18 +702,42 @@ mod tests {
                     name: Identifier("cond".to_string()),
                     value: None,
                     conditions: HashSet::new(),
+                    span: Span::default(),
                 },
                 Field {
                     ty: Type::BaseType(BaseType::Integer(IntegerType::I32)),
                     name: Identifier("number".to_string()),
                     value: None,
                     conditions: HashSet::from_iter(vec!["cond".to_string()]),
+                    span: Span::default(),
                 },
                 Field {
                     ty: Type::BaseType(BaseType::Integer(IntegerType::U64)),
                     name: Identifier("other".to_string()),
                     value: None,
                     conditions: HashSet::from_iter(vec!["!cond".to_string()]),
+                    span: Span::default(),
+                },
+                Field {
+                    ty: Type::BaseType(BaseType::Bool),
+                    name: Identifier("both".to_string()),
+                    value: None,
+                    conditions: HashSet::from_iter(vec!["( !cond ) && ( number > 0 )".to_string()]),
+                    span: Span::default(),
+                },
+                Field {
+                    ty: Type::BaseType(BaseType::String { length: None }),
+                    name: Identifier("mode".to_string()),
+                    value: None,
+                    conditions: HashSet::new(),
+                    span: Span::default(),
+                },
+                Field {
+                    ty: Type::BaseType(BaseType::Bool),
+                    name: Identifier("spaced".to_string()),
+                    value: None,
+                    conditions: HashSet::from_iter(vec!["mode == \" a \"".to_string()]),
+                    span: Span::default(),
                 },
             ]))
         );
@@ -464,4 +745,59 @@ mod tests {
         assert!(tokens.is_empty());
         test_parse!(tokens, FieldList, Ok(FieldList(vec![])));
     }
+
+    #[test]
+    fn test_field_list_blocks() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            bool a
+            if (a) {
+                if (!a) {
+                    i32 b
+                }
+            "
+        );
+        let error: Error = FieldList::parse(&mut tokens).unwrap_err();
+        assert_eq!(
+            error,
+            Error::UnclosedBlock {
+                kind: "if".to_string(),
+                open: Span {
+                    start: 32,
+                    end: 34,
+                    line: 3,
+                    column: 13,
+                },
+                span: Span {
+                    start: 110,
+                    end: 110,
+                    line: 6,
+                    column: 17,
+                },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Unclosed `if` block at 6:17 (opened at 3:13)"
+        );
+
+        let mut tokens: TokenStream = tokenize!(
+            "
+            if (a) {
+                i32 b
+            packet P(serverbound, Play, 0x00) {}
+            "
+        );
+        let error: Error = FieldList::parse(&mut tokens).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unclosed `if` block at 4:13 (opened at 2:13)"
+        );
+        assert_eq!(error.span().unwrap().to_string(), "4:13");
+        assert_eq!(tokens.last().unwrap(), "packet");
+
+        let mut tokens: TokenStream = tokenize!("i32 a } }");
+        assert_eq!(FieldList::parse(&mut tokens).unwrap().0.len(), 1);
+        assert_eq!(tokens.len(), 2);
+    }
 }
@@ -0,0 +1,308 @@
+//! Analysis of the encoded size of packets and custom types.
+
+use std::ops::Add;
+
+use specmc_base::parse::Identifier;
+
+use crate::{
+    base::{BaseType, FieldList, IntegerType, Value},
+    types::Type,
+    Protocol,
+};
+
+/// The minimum and maximum encoded length of a value in bytes.
+/// A maximum of `None` means that the length is unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SizeBounds {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+impl SizeBounds {
+    /// Bounds of a value nothing is known about.
+    pub const UNBOUNDED: SizeBounds = SizeBounds { min: 0, max: None };
+
+    /// Bounds of a value that always has the same length.
+    pub const fn exact(size: usize) -> Self {
+        SizeBounds {
+            min: size,
+            max: Some(size),
+        }
+    }
+
+    /// Whether the value always has the same length.
+    pub fn is_fixed(&self) -> bool {
+        self.max == Some(self.min)
+    }
+
+    /// Bounds of the value if it may also be absent.
+    pub fn optional(self) -> Self {
+        SizeBounds {
+            min: 0,
+            max: self.max,
+        }
+    }
+
+    /// Bounds of a sequence of `min_count` to `max_count` values.
+    pub fn repeat(self, min_count: usize, max_count: Option<usize>) -> Self {
+        SizeBounds {
+            min: self.min.saturating_mul(min_count),
+            max: match (self.max, max_count) {
+                (Some(0), _) | (_, Some(0)) => Some(0),
+                (Some(max), Some(max_count)) => max.checked_mul(max_count),
+                _ => None,
+            },
+        }
+    }
+}
+impl Add for SizeBounds {
+    type Output = SizeBounds;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        SizeBounds {
+            min: self.min.saturating_add(rhs.min),
+            max: self.max.zip(rhs.max).and_then(|(a, b)| a.checked_add(b)),
+        }
+    }
+}
+
+/// The number of bytes needed to encode a value as VarInt or VarLong.
+pub fn var_int_size(value: u64) -> usize {
+    (64 - value.leading_zeros()).max(1).div_ceil(7) as usize
+}
+
+/// Bounds of an integer type.
+pub fn integer_bounds(ty: &IntegerType) -> SizeBounds {
+    use IntegerType::*;
+    match ty {
+        U8 | I8 => SizeBounds::exact(1),
+        U16 | I16 => SizeBounds::exact(2),
+        U32 | I32 => SizeBounds::exact(4),
+        U64 | I64 => SizeBounds::exact(8),
+        VarInt => SizeBounds {
+            min: 1,
+            max: Some(5),
+        },
+        VarLong => SizeBounds {
+            min: 1,
+            max: Some(10),
+        },
+    }
+}
+
+/// Computes size bounds while keeping track of the custom types being visited,
+/// so that recursive types don't cause infinite recursion.
+pub(crate) struct Analyzer<'a> {
+    protocol: &'a Protocol,
+    stack: Vec<&'a Identifier>,
+}
+impl<'a> Analyzer<'a> {
+    pub(crate) fn new(protocol: &'a Protocol) -> Self {
+        Analyzer {
+            protocol,
+            stack: vec![],
+        }
+    }
+
+    pub(crate) fn fields(&mut self, fields: &'a FieldList) -> SizeBounds {
+        let mut bounds: SizeBounds = SizeBounds::exact(0);
+        for field in &fields.0 {
+            // Unprefixed lists and NBT take their length from a field defined as `len(...)`
+            let count_type: Option<&IntegerType> =
+                fields
+                    .0
+                    .iter()
+                    .find_map(|other| match (&other.value, &other.ty) {
+                        (
+                            Some(Value::Length(name, _)),
+                            Type::BaseType(BaseType::Integer(integer_type)),
+                        ) if name == &field.name => Some(integer_type),
+                        _ => None,
+                    });
+            let max_count = |ty: &IntegerType| Some(*ty.range().end() as usize);
+
+            let field_bounds: SizeBounds = match (&field.ty, count_type) {
+                (Type::BaseType(BaseType::List { ty, length: None }), Some(count_type)) => {
+                    self.ty(ty).repeat(0, max_count(count_type))
+                }
+                (Type::BaseType(BaseType::List { ty, length: None }), None) => {
+                    integer_bounds(&IntegerType::VarInt)
+                        + self.ty(ty).repeat(0, max_count(&IntegerType::VarInt))
+                }
+                (Type::BaseType(BaseType::Nbt), Some(count_type)) => {
+                    SizeBounds::exact(1).repeat(0, max_count(count_type))
+                }
+                (ty, _) => self.ty(ty),
+            };
+
+            bounds = bounds
+                + if field.conditions.is_empty() {
+                    field_bounds
+                } else {
+                    field_bounds.optional()
+                };
+        }
+
+        bounds
+    }
+
+    pub(crate) fn ty(&mut self, ty: &'a Type) -> SizeBounds {
+        match ty {
+            Type::BaseType(base_type) => self.base_type(base_type),
+            Type::CustomType(name) => {
+                if let Some(e) = self.protocol.get_enum(name) {
+                    integer_bounds(&e.ty)
+                } else if let Some(custom_type) = self.protocol.get_type(name) {
+                    if self.stack.contains(&name) {
+                        return SizeBounds::UNBOUNDED;
+                    }
+                    self.stack.push(name);
+                    let bounds: SizeBounds = self.fields(&custom_type.fields);
+                    self.stack.pop();
+                    bounds
+                } else {
+                    SizeBounds::UNBOUNDED
+                }
+            }
+        }
+    }
+
+    fn base_type(&mut self, base_type: &'a BaseType) -> SizeBounds {
+        use BaseType::*;
+        match base_type {
+            Bool => SizeBounds::exact(1),
+            Integer(integer_type) => integer_bounds(integer_type),
+            F32 => SizeBounds::exact(4),
+            F64 => SizeBounds::exact(8),
+            String { length } => {
+                // Every UTF-16 code unit takes at most 3 bytes in UTF-8
+                let max_bytes: usize = length.unwrap_or(BaseType::MAX_STRING_LENGTH) * 3;
+                SizeBounds {
+                    min: 1,
+                    max: Some(var_int_size(max_bytes as u64) + max_bytes),
+                }
+            }
+            List {
+                ty,
+                length: Some(length),
+            } => self.ty(ty).repeat(*length, Some(*length)),
+            // Lists without a length field are prefixed with their length as VarInt
+            List { ty, length: None } => {
+                integer_bounds(&IntegerType::VarInt)
+                    + self.ty(ty).repeat(0, Some(i32::MAX as usize))
+            }
+            // An empty NBT is a single end tag
+            Nbt => SizeBounds { min: 1, max: None },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    fn bounds(protocol: &Protocol, name: &str) -> SizeBounds {
+        protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == name)
+            .unwrap()
+            .size_bounds(protocol)
+    }
+
+    #[test]
+    fn test_var_int_size() {
+        assert_eq!(var_int_size(0), 1);
+        assert_eq!(var_int_size(127), 1);
+        assert_eq!(var_int_size(128), 2);
+        assert_eq!(var_int_size(u32::MAX as u64), 5);
+        assert_eq!(var_int_size(u64::MAX), 10);
+    }
+
+    #[test]
+    fn test_size_bounds() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum Mouse(i8) {
+                LeftClick
+                RightClick
+            }
+            type Slot {
+                i16 id
+                if (id != -1) {
+                    i8 count
+                    i16 damage
+                }
+            }
+            packet KeepAlive(serverbound, Play, 0x00) {
+                i32 keep_alive_id
+            }
+            packet ChatMessage(serverbound, Play, 0x01) {
+                String[100] message
+            }
+            packet UseEntity(serverbound, Play, 0x02) {
+                VarInt target
+                Mouse mouse
+            }
+            packet Player(serverbound, Play, 0x03) {
+                bool on_ground
+            }
+            packet EncryptionResponse(serverbound, Login, 0x01) {
+                u16 shared_secret_length = len(shared_secret)
+                List[u8] shared_secret
+            }
+            packet SetSlot(clientbound, Play, 0x2F) {
+                List[Slot; 2] slots
+            }
+            packet Tags(clientbound, Play, 0x30) {
+                List[Nbt] tags
+            }
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+
+        assert_eq!(bounds(&protocol, "KeepAlive"), SizeBounds::exact(4));
+        assert_eq!(bounds(&protocol, "Player"), SizeBounds::exact(1));
+        assert!(bounds(&protocol, "Player").is_fixed());
+        assert_eq!(
+            bounds(&protocol, "ChatMessage"),
+            SizeBounds {
+                min: 1,
+                max: Some(302),
+            }
+        );
+        assert_eq!(
+            bounds(&protocol, "UseEntity"),
+            SizeBounds {
+                min: 2,
+                max: Some(6),
+            }
+        );
+        assert_eq!(
+            bounds(&protocol, "EncryptionResponse"),
+            SizeBounds {
+                min: 2,
+                max: Some(2 + 65535),
+            }
+        );
+        assert_eq!(
+            bounds(&protocol, "SetSlot"),
+            SizeBounds {
+                min: 4,
+                max: Some(10),
+            }
+        );
+        assert_eq!(bounds(&protocol, "Tags"), SizeBounds { min: 1, max: None });
+        assert_eq!(
+            protocol.types[0].size_bounds(&protocol),
+            SizeBounds {
+                min: 2,
+                max: Some(5),
+            }
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! Readers and writers for the primitive types of the protocol.
+//!
+//! These are shared by the [interpreter](crate::decode) and generated code,
+//! so both agree on the wire format.
+//! Integers are big-endian, and `VarInt` and `VarLong` are LEB128 encoded.
+
+use std::{fmt::Display, ops::RangeInclusive};
+
+use crate::base::IntegerType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodecError {
+    /// The input ended in the middle of a value.
+    UnexpectedEnd,
+    /// A `VarInt` with more than 5 bytes, or a `VarLong` with more than 10.
+    VarIntTooLong,
+    InvalidUtf8,
+    /// A string with more UTF-16 code units than its maximum length.
+    /// Strings with more than 3 bytes per code unit are rejected by their size in bytes.
+    StringTooLong {
+        length: usize,
+        max: usize,
+    },
+    /// A negative length, or one that doesn't fit in memory.
+    InvalidLength(i128),
+    /// An integer that doesn't fit its integer type.
+    OutOfRange {
+        value: i128,
+        ty: IntegerType,
+    },
+    /// An NBT tag id that doesn't exist.
+    InvalidNbtTag(u8),
+    /// NBT with compounds and lists nested deeper than [`MAX_DEPTH`](crate::nbt::MAX_DEPTH).
+    NbtTooDeep,
+    /// An NBT list with tags of different types.
+    MixedNbtList,
+    /// Compressed data that is corrupt or too large.
+    InvalidCompression,
+}
+impl Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use CodecError::*;
+        match self {
+            UnexpectedEnd => write!(f, "Unexpected end of input"),
+            VarIntTooLong => write!(f, "VarInt is too long"),
+            InvalidUtf8 => write!(f, "Invalid UTF-8"),
+            StringTooLong { length, max } => {
+                write!(f, "String of length {length} is longer than {max}")
+            }
+            InvalidLength(length) => write!(f, "Invalid length {length}"),
+            OutOfRange { value, ty } => write!(f, "Value {value} is out of range for {ty:?}"),
+            InvalidNbtTag(id) => write!(f, "Invalid NBT tag {id}"),
+            NbtTooDeep => write!(f, "NBT is nested too deep"),
+            MixedNbtList => write!(f, "NBT list has tags of different types"),
+            InvalidCompression => write!(f, "Invalid compressed data"),
+        }
+    }
+}
+impl std::error::Error for CodecError {}
+
+/// The values an integer type can hold, including all of `u64`.
+pub fn integer_range(ty: &IntegerType) -> RangeInclusive<i128> {
+    match ty {
+        IntegerType::U64 => 0..=u64::MAX as i128,
+        ty => *ty.range().start() as i128..=*ty.range().end() as i128,
+    }
+}
+
+/// Reads primitive values from a byte slice.
+/// Reads that fail don't consume any bytes, so the offset points at the start of the value.
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, offset: 0 }
+    }
+
+    /// The number of bytes read so far.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The bytes that haven't been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.offset..]
+    }
+
+    pub fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], CodecError> {
+        let end: usize = self
+            .offset
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(CodecError::UnexpectedEnd)?;
+        let bytes: &[u8] = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CodecError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    /// Any byte other than zero is `true`.
+    pub fn read_bool(&mut self) -> Result<bool, CodecError> {
+        Ok(self.array::<1>()? != [0])
+    }
+
+    fn read_leb128(&mut self, max_bytes: usize) -> Result<u64, CodecError> {
+        let start: usize = self.offset;
+        let mut value: u64 = 0;
+        for i in 0..max_bytes {
+            let Ok([byte]) = self.array::<1>() else {
+                self.offset = start;
+                return Err(CodecError::UnexpectedEnd);
+            };
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        self.offset = start;
+        Err(CodecError::VarIntTooLong)
+    }
+
+    pub fn read_var_int(&mut self) -> Result<i32, CodecError> {
+        Ok(self.read_leb128(5)? as u32 as i32)
+    }
+
+    pub fn read_var_long(&mut self) -> Result<i64, CodecError> {
+        Ok(self.read_leb128(10)? as i64)
+    }
+
+    pub fn read_integer(&mut self, ty: &IntegerType) -> Result<i128, CodecError> {
+        use IntegerType::*;
+        Ok(match ty {
+            U8 => u8::from_be_bytes(self.array()?) as i128,
+            U16 => u16::from_be_bytes(self.array()?) as i128,
+            U32 => u32::from_be_bytes(self.array()?) as i128,
+            U64 => u64::from_be_bytes(self.array()?) as i128,
+            I8 => i8::from_be_bytes(self.array()?) as i128,
+            I16 => i16::from_be_bytes(self.array()?) as i128,
+            I32 => i32::from_be_bytes(self.array()?) as i128,
+            I64 => i64::from_be_bytes(self.array()?) as i128,
+            VarInt => self.read_var_int()? as i128,
+            VarLong => self.read_var_long()? as i128,
+        })
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, CodecError> {
+        Ok(f32::from_be_bytes(self.array()?))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, CodecError> {
+        Ok(f64::from_be_bytes(self.array()?))
+    }
+
+    /// A length prefix, which is a `VarInt` that must not be negative.
+    pub fn read_length(&mut self) -> Result<usize, CodecError> {
+        let start: usize = self.offset;
+        let length: i32 = self.read_var_int()?;
+        usize::try_from(length).map_err(|_| {
+            self.offset = start;
+            CodecError::InvalidLength(length as i128)
+        })
+    }
+
+    /// A string prefixed with its size in bytes.
+    /// `max_length` is counted in UTF-16 code units.
+    pub fn read_string(&mut self, max_length: usize) -> Result<String, CodecError> {
+        self.read_str(max_length).map(str::to_string)
+    }
+
+    /// Read a string without copying it out of the input.
+    pub fn read_str(&mut self, max_length: usize) -> Result<&'a str, CodecError> {
+        let start: usize = self.offset;
+        let result: Result<&'a str, CodecError> = (|| {
+            let size: usize = self.read_length()?;
+            // Every UTF-16 code unit takes at most 3 bytes in UTF-8
+            if size > max_length.saturating_mul(3) {
+                return Err(CodecError::StringTooLong {
+                    length: size,
+                    max: max_length,
+                });
+            }
+            let string: &'a str =
+                std::str::from_utf8(self.read_bytes(size)?).map_err(|_| CodecError::InvalidUtf8)?;
+            let length: usize = string.encode_utf16().count();
+            if length > max_length {
+                return Err(CodecError::StringTooLong {
+                    length,
+                    max: max_length,
+                });
+            }
+            Ok(string)
+        })();
+        if result.is_err() {
+            self.offset = start;
+        }
+        result
+    }
+}
+
+pub fn write_bool(bytes: &mut Vec<u8>, value: bool) {
+    bytes.push(value as u8);
+}
+
+fn write_leb128(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Write a `VarInt` with as few bytes as possible.
+/// Negative values always take 5 bytes.
+pub fn write_var_int(bytes: &mut Vec<u8>, value: i32) {
+    write_leb128(bytes, value as u32 as u64);
+}
+
+/// Write a `VarLong` with as few bytes as possible.
+/// Negative values always take 10 bytes.
+pub fn write_var_long(bytes: &mut Vec<u8>, value: i64) {
+    write_leb128(bytes, value as u64);
+}
+
+pub fn write_integer(bytes: &mut Vec<u8>, ty: &IntegerType, value: i128) -> Result<(), CodecError> {
+    if !integer_range(ty).contains(&value) {
+        return Err(CodecError::OutOfRange {
+            value,
+            ty: ty.clone(),
+        });
+    }
+
+    use IntegerType::*;
+    match ty {
+        U8 | I8 => bytes.push(value as u8),
+        U16 | I16 => bytes.extend((value as u16).to_be_bytes()),
+        U32 | I32 => bytes.extend((value as u32).to_be_bytes()),
+        U64 | I64 => bytes.extend((value as u64).to_be_bytes()),
+        VarInt => write_var_int(bytes, value as i32),
+        VarLong => write_var_long(bytes, value as i64),
+    }
+    Ok(())
+}
+
+pub fn write_f32(bytes: &mut Vec<u8>, value: f32) {
+    bytes.extend(value.to_be_bytes());
+}
+
+pub fn write_f64(bytes: &mut Vec<u8>, value: f64) {
+    bytes.extend(value.to_be_bytes());
+}
+
+/// A length prefix as `VarInt`.
+pub fn write_length(bytes: &mut Vec<u8>, length: usize) -> Result<(), CodecError> {
+    write_integer(bytes, &IntegerType::VarInt, length as i128)
+        .map_err(|_| CodecError::InvalidLength(length as i128))
+}
+
+/// Write a string prefixed with its size in bytes.
+/// `max_length` is counted in UTF-16 code units.
+pub fn write_string(bytes: &mut Vec<u8>, value: &str, max_length: usize) -> Result<(), CodecError> {
+    let length: usize = value.encode_utf16().count();
+    if length > max_length {
+        return Err(CodecError::StringTooLong {
+            length,
+            max: max_length,
+        });
+    }
+    write_length(bytes, value.len())?;
+    bytes.extend(value.as_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_int() {
+        let cases: [(i32, &[u8]); 7] = [
+            (0, &[0x00]),
+            (1, &[0x01]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (25565, &[0xdd, 0xc7, 0x01]),
+            (i32::MAX, &[0xff, 0xff, 0xff, 0xff, 0x07]),
+            (-1, &[0xff, 0xff, 0xff, 0xff, 0x0f]),
+        ];
+        for (value, expected) in cases {
+            let mut bytes: Vec<u8> = vec![];
+            write_var_int(&mut bytes, value);
+            assert_eq!(bytes, expected);
+            assert_eq!(Reader::new(expected).read_var_int(), Ok(value));
+        }
+
+        let mut bytes: Vec<u8> = vec![];
+        write_var_long(&mut bytes, i64::MIN);
+        assert_eq!(
+            bytes,
+            [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01]
+        );
+        assert_eq!(Reader::new(&bytes).read_var_long(), Ok(i64::MIN));
+
+        let mut reader: Reader = Reader::new(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert_eq!(reader.read_var_int(), Err(CodecError::VarIntTooLong));
+        assert_eq!(reader.offset(), 0);
+        assert_eq!(
+            Reader::new(&[0xff; 11]).read_var_long(),
+            Err(CodecError::VarIntTooLong)
+        );
+        let mut reader: Reader = Reader::new(&[0x80, 0x80]);
+        assert_eq!(reader.read_var_int(), Err(CodecError::UnexpectedEnd));
+        assert_eq!(reader.offset(), 0);
+    }
+
+    #[test]
+    fn test_primitives() {
+        let mut bytes: Vec<u8> = vec![];
+        write_bool(&mut bytes, true);
+        write_integer(&mut bytes, &IntegerType::U16, 0xabcd).unwrap();
+        write_integer(&mut bytes, &IntegerType::I32, -2).unwrap();
+        write_integer(&mut bytes, &IntegerType::U64, u64::MAX as i128).unwrap();
+        write_integer(&mut bytes, &IntegerType::VarLong, 300).unwrap();
+        write_f32(&mut bytes, 1.5);
+        write_f64(&mut bytes, -0.25);
+        write_string(&mut bytes, "héllo", 5).unwrap();
+
+        let mut reader: Reader = Reader::new(&bytes);
+        assert_eq!(reader.read_bool(), Ok(true));
+        assert_eq!(reader.read_integer(&IntegerType::U16), Ok(0xabcd));
+        assert_eq!(reader.read_integer(&IntegerType::I32), Ok(-2));
+        assert_eq!(reader.read_integer(&IntegerType::U64), Ok(u64::MAX as i128));
+        assert_eq!(reader.read_integer(&IntegerType::VarLong), Ok(300));
+        assert_eq!(reader.read_f32(), Ok(1.5));
+        assert_eq!(reader.read_f64(), Ok(-0.25));
+        assert_eq!(reader.read_string(5), Ok("héllo".to_string()));
+        assert!(reader.remaining().is_empty());
+        assert_eq!(reader.read_bool(), Err(CodecError::UnexpectedEnd));
+
+        assert_eq!(
+            write_integer(&mut bytes, &IntegerType::I8, 128),
+            Err(CodecError::OutOfRange {
+                value: 128,
+                ty: IntegerType::I8,
+            })
+        );
+        assert_eq!(
+            write_integer(&mut bytes, &IntegerType::U32, -1),
+            Err(CodecError::OutOfRange {
+                value: -1,
+                ty: IntegerType::U32,
+            })
+        );
+    }
+
+    #[test]
+    fn test_string() {
+        assert_eq!(
+            write_string(&mut vec![], "héllo", 4),
+            Err(CodecError::StringTooLong { length: 5, max: 4 })
+        );
+
+        let mut reader: Reader = Reader::new(&[0x06, b'h', 0xc3, 0xa9, b'l', b'l', b'o']);
+        assert_eq!(
+            reader.read_string(4),
+            Err(CodecError::StringTooLong { length: 5, max: 4 })
+        );
+        assert_eq!(reader.offset(), 0);
+        // The size is checked before the string is read
+        assert_eq!(
+            Reader::new(&[0x0d]).read_string(4),
+            Err(CodecError::StringTooLong { length: 13, max: 4 })
+        );
+        assert_eq!(
+            Reader::new(&[0x02, 0xc3, 0x28]).read_string(4),
+            Err(CodecError::InvalidUtf8)
+        );
+        assert_eq!(
+            Reader::new(&[0xff, 0xff, 0xff, 0xff, 0x0f]).read_string(4),
+            Err(CodecError::InvalidLength(-1))
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! Conditions of conditional fields.
+//!
+//! Conditions are stored as strings in [`Field::conditions`](crate::base::Field::conditions)
+//! and can be parsed into a [`Condition`] tree.
+
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use specmc_base::{
+    ensure,
+    parse::{Identifier, Literal},
+};
+
+use crate::{parse::Span, Error};
+
+/// A value in a condition.
+/// `F` is the way fields are referred to, by default by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand<F = Identifier> {
+    Field(F),
+    Literal(Literal),
+}
+impl<F: Display> Display for Operand<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Field(field) => write!(f, "{field}"),
+            Operand::Literal(literal) => write!(f, "{literal}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+impl Comparison {
+    pub fn symbol(&self) -> &'static str {
+        use Comparison::*;
+        match self {
+            Eq => "==",
+            Ne => "!=",
+            Lt => "<",
+            Le => "<=",
+            Gt => ">",
+            Ge => ">=",
+        }
+    }
+
+    /// The comparison that holds exactly when this one doesn't.
+    pub fn negate(self) -> Self {
+        use Comparison::*;
+        match self {
+            Eq => Ne,
+            Ne => Eq,
+            Lt => Ge,
+            Le => Gt,
+            Gt => Le,
+            Ge => Lt,
+        }
+    }
+
+    /// The comparison with its operands swapped, i.e. `a < b` becomes `b > a`.
+    pub fn flip(self) -> Self {
+        use Comparison::*;
+        match self {
+            Eq => Eq,
+            Ne => Ne,
+            Lt => Gt,
+            Le => Ge,
+            Gt => Lt,
+            Ge => Le,
+        }
+    }
+
+    /// Whether the comparison holds for operands with the given ordering.
+    pub fn holds(&self, ordering: Ordering) -> bool {
+        use Comparison::*;
+        match self {
+            Eq => ordering.is_eq(),
+            Ne => ordering.is_ne(),
+            Lt => ordering.is_lt(),
+            Le => ordering.is_le(),
+            Gt => ordering.is_gt(),
+            Ge => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition<F = Identifier> {
+    /// The operand is `true` or a non-zero number.
+    Truthy(Operand<F>),
+    Not(Box<Condition<F>>),
+    And(Box<Condition<F>>, Box<Condition<F>>),
+    Or(Box<Condition<F>>, Box<Condition<F>>),
+    Compare(Operand<F>, Comparison, Operand<F>),
+}
+impl<F> Condition<F> {
+    /// Replace the field references in the condition.
+    pub fn try_map<G, E>(
+        &self,
+        map: &mut impl FnMut(&F) -> Result<Operand<G>, E>,
+    ) -> Result<Condition<G>, E> {
+        let mut operand = |operand: &Operand<F>| match operand {
+            Operand::Field(field) => map(field),
+            Operand::Literal(literal) => Ok(Operand::Literal(literal.clone())),
+        };
+
+        use Condition::*;
+        Ok(match self {
+            Truthy(value) => Truthy(operand(value)?),
+            Not(condition) => Not(Box::new(condition.try_map(map)?)),
+            And(lhs, rhs) => And(Box::new(lhs.try_map(map)?), Box::new(rhs.try_map(map)?)),
+            Or(lhs, rhs) => Or(Box::new(lhs.try_map(map)?), Box::new(rhs.try_map(map)?)),
+            Compare(lhs, comparison, rhs) => Compare(operand(lhs)?, *comparison, operand(rhs)?),
+        })
+    }
+
+    /// All field references in the condition.
+    pub fn fields(&self) -> Vec<&F> {
+        fn operand<F>(operand: &Operand<F>) -> Option<&F> {
+            match operand {
+                Operand::Field(field) => Some(field),
+                Operand::Literal(_) => None,
+            }
+        }
+
+        use Condition::*;
+        match self {
+            Truthy(value) => operand(value).into_iter().collect(),
+            Not(condition) => condition.fields(),
+            And(lhs, rhs) | Or(lhs, rhs) => {
+                let mut fields: Vec<&F> = lhs.fields();
+                fields.extend(rhs.fields());
+                fields
+            }
+            Compare(lhs, _, rhs) => operand(lhs).into_iter().chain(operand(rhs)).collect(),
+        }
+    }
+
+    /// Evaluate the condition, given the values of the fields it refers to.
+    /// Returns `None` if a field has no value or operands can't be compared.
+    pub fn evaluate(&self, value: &mut impl FnMut(&F) -> Option<Literal>) -> Option<bool> {
+        let mut operand = |operand: &Operand<F>| match operand {
+            Operand::Field(field) => value(field),
+            Operand::Literal(literal) => Some(literal.clone()),
+        };
+
+        use Condition::*;
+        match self {
+            Truthy(operand_value) => match operand(operand_value)? {
+                Literal::Boolean(boolean) => Some(boolean),
+                Literal::Integer(int) => Some(int != 0),
+                Literal::Float(float) => Some(float != 0.0),
+                Literal::String(_) => None,
+            },
+            Not(condition) => Some(!condition.evaluate(value)?),
+            And(lhs, rhs) => Some(lhs.evaluate(value)? && rhs.evaluate(value)?),
+            Or(lhs, rhs) => Some(lhs.evaluate(value)? || rhs.evaluate(value)?),
+            Compare(lhs, comparison, rhs) => {
+                let ordering: Ordering = match (operand(lhs)?, operand(rhs)?) {
+                    (Literal::Boolean(lhs), Literal::Boolean(rhs)) => lhs.cmp(&rhs),
+                    (Literal::Integer(lhs), Literal::Integer(rhs)) => lhs.cmp(&rhs),
+                    (Literal::Float(lhs), Literal::Float(rhs)) => lhs.partial_cmp(&rhs)?,
+                    (Literal::Integer(lhs), Literal::Float(rhs)) => {
+                        (lhs as f64).partial_cmp(&rhs)?
+                    }
+                    (Literal::Float(lhs), Literal::Integer(rhs)) => {
+                        lhs.partial_cmp(&(rhs as f64))?
+                    }
+                    (Literal::String(lhs), Literal::String(rhs)) => lhs.cmp(&rhs),
+                    _ => return None,
+                };
+                Some(comparison.holds(ordering))
+            }
+        }
+    }
+}
+impl<F: Display> Display for Condition<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Condition::*;
+        match self {
+            Truthy(value) => write!(f, "{value}"),
+            Not(condition) => write!(f, "!({condition})"),
+            And(lhs, rhs) => write!(f, "({lhs}) && ({rhs})"),
+            Or(lhs, rhs) => write!(f, "({lhs}) || ({rhs})"),
+            Compare(lhs, comparison, rhs) => write!(f, "{lhs} {} {rhs}", comparison.symbol()),
+        }
+    }
+}
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser: Parser = Parser {
+            tokens: lex(s)?,
+            position: 0,
+        };
+        let condition: Condition = parser.or()?;
+        ensure!(
+            parser.position == parser.tokens.len(),
+            invalid(&parser.tokens[parser.position].to_string())
+        );
+        Ok(condition)
+    }
+}
+
+/// Conditions are parsed from strings, so errors are reported without a span.
+/// Callers can move them to the field the condition belongs to with [`Error::at`].
+fn invalid(token: &str) -> Error {
+    Error::InvalidCondition {
+        token: token.to_string(),
+        span: Span::default(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Literal(Literal),
+    Symbol(&'static str),
+}
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Identifier(identifier) => write!(f, "{identifier}"),
+            Token::Literal(literal) => write!(f, "{literal}"),
+            Token::Symbol(symbol) => write!(f, "{symbol}"),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "-", "+",
+];
+
+fn lex(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut rest: &str = input.trim_start();
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            rest = &rest[symbol.len()..];
+            // The tokenizer splits `<=` and `>=` into two tokens
+            let after: &str = rest.trim_start();
+            match *symbol {
+                "<" | ">" if after.starts_with('=') && !after.starts_with("==") => {
+                    tokens.push(Token::Symbol(if *symbol == "<" { "<=" } else { ">=" }));
+                    rest = &after[1..];
+                }
+                _ => tokens.push(Token::Symbol(symbol)),
+            }
+        } else if let Some(string) = rest.strip_prefix('"') {
+            let end: usize = string.find('"').ok_or_else(|| invalid(rest))?;
+            tokens.push(Token::Literal(Literal::String(string[..end].to_string())));
+            rest = &string[end + 1..];
+        } else {
+            let end: usize = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            ensure!(end != 0, invalid(rest));
+            let word: &str = &rest[..end];
+            tokens.push(match word {
+                "true" => Token::Literal(Literal::Boolean(true)),
+                "false" => Token::Literal(Literal::Boolean(false)),
+                _ if word.starts_with(|c: char| c.is_ascii_digit()) => {
+                    if let Ok(int) = strtoint::strtoint(word) {
+                        Token::Literal(Literal::Integer(int))
+                    } else {
+                        Token::Literal(Literal::Float(word.parse().map_err(|_| invalid(word))?))
+                    }
+                }
+                _ => Token::Identifier(word.to_string()),
+            });
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+impl Parser {
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        if self.tokens.get(self.position) == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, Error> {
+        let mut condition: Condition = self.and()?;
+        while self.eat("||") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, Error> {
+        let mut condition: Condition = self.not()?;
+        while self.eat("&&") {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, Error> {
+        if self.eat("!") {
+            Ok(Condition::Not(Box::new(self.not()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Condition, Error> {
+        if self.eat("(") {
+            let condition: Condition = self.or()?;
+            ensure!(self.eat(")"), self.unexpected());
+            return Ok(condition);
+        }
+
+        let lhs: Operand = self.operand()?;
+        for comparison in [
+            Comparison::Eq,
+            Comparison::Ne,
+            Comparison::Le,
+            Comparison::Ge,
+            Comparison::Lt,
+            Comparison::Gt,
+        ] {
+            if self.eat(comparison.symbol()) {
+                return Ok(Condition::Compare(lhs, comparison, self.operand()?));
+            }
+        }
+        Ok(Condition::Truthy(lhs))
+    }
+
+    fn operand(&mut self) -> Result<Operand, Error> {
+        let negative: bool = self.eat("-");
+        if !negative {
+            self.eat("+");
+        }
+
+        let token: Token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or(Error::EndOfFile)?;
+        self.position += 1;
+        match token {
+            Token::Literal(Literal::Integer(int)) if negative => {
+                Ok(Operand::Literal(Literal::Integer(-int)))
+            }
+            Token::Literal(Literal::Float(float)) if negative => {
+                Ok(Operand::Literal(Literal::Float(-float)))
+            }
+            Token::Literal(literal) if !negative => Ok(Operand::Literal(literal)),
+            Token::Identifier(identifier) if !negative => {
+                Ok(Operand::Field(Identifier(identifier)))
+            }
+            token => Err(invalid(&token.to_string())),
+        }
+    }
+
+    fn unexpected(&self) -> Error {
+        match self.tokens.get(self.position) {
+            Some(token) => invalid(&token.to_string()),
+            None => Error::EndOfFile,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str) -> Operand {
+        Operand::Field(Identifier(name.to_string()))
+    }
+
+    #[test]
+    fn test_condition() {
+        assert_eq!("flag".parse(), Ok(Condition::Truthy(field("flag"))));
+        assert_eq!(
+            "!flag".parse(),
+            Ok(Condition::Not(Box::new(Condition::Truthy(field("flag")))))
+        );
+        assert_eq!(
+            "id != - 1".parse(),
+            Ok(Condition::Compare(
+                field("id"),
+                Comparison::Ne,
+                Operand::Literal(Literal::Integer(-1))
+            ))
+        );
+        assert_eq!(
+            "a >= 0x10 || b && ( !c )".parse(),
+            Ok(Condition::Or(
+                Box::new(Condition::Compare(
+                    field("a"),
+                    Comparison::Ge,
+                    Operand::Literal(Literal::Integer(16))
+                )),
+                Box::new(Condition::And(
+                    Box::new(Condition::Truthy(field("b"))),
+                    Box::new(Condition::Not(Box::new(Condition::Truthy(field("c")))))
+                ))
+            ))
+        );
+        assert_eq!(
+            "mode == \" a \"".parse(),
+            Ok(Condition::Compare(
+                field("mode"),
+                Comparison::Eq,
+                Operand::Literal(Literal::String(" a ".to_string()))
+            ))
+        );
+
+        assert_eq!(
+            "a < = 1".parse(),
+            Ok(Condition::Compare(
+                field("a"),
+                Comparison::Le,
+                Operand::Literal(Literal::Integer(1))
+            ))
+        );
+
+        assert_eq!("a ==".parse::<Condition>(), Err(Error::EndOfFile));
+        assert_eq!("a b".parse::<Condition>(), Err(invalid("b")));
+        assert_eq!("( a".parse::<Condition>(), Err(Error::EndOfFile));
+        assert_eq!("- a".parse::<Condition>(), Err(invalid("a")));
+
+        let condition: Condition = "( !cond ) && ( number > 0 )".parse().unwrap();
+        assert_eq!(condition.to_string(), "(!(cond)) && (number > 0)");
+        assert_eq!(
+            condition.fields(),
+            vec![
+                &Identifier("cond".to_string()),
+                &Identifier("number".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut value = |name: &Identifier| match name.0.as_str() {
+            "flag" => Some(Literal::Boolean(true)),
+            "id" => Some(Literal::Integer(-1)),
+            "speed" => Some(Literal::Float(0.5)),
+            "name" => Some(Literal::String("a".to_string())),
+            _ => None,
+        };
+        let mut evaluate =
+            |condition: &str| condition.parse::<Condition>().unwrap().evaluate(&mut value);
+
+        assert_eq!(evaluate("flag"), Some(true));
+        assert_eq!(evaluate("!flag || id"), Some(true));
+        assert_eq!(evaluate("id != - 1"), Some(false));
+        assert_eq!(evaluate("id < 0 && speed >= 0.5"), Some(true));
+        assert_eq!(evaluate("speed > 1"), Some(false));
+        assert_eq!(evaluate("name == \"a\""), Some(true));
+        assert_eq!(evaluate("name"), None);
+        assert_eq!(evaluate("flag == 1"), None);
+        assert_eq!(evaluate("missing"), None);
+    }
+}
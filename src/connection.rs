@@ -0,0 +1,278 @@
+//! Tracking the state of a connection.
+//!
+//! Which packet an id refers to depends on the state of the connection.
+//! Packets marked with `@transition(...)` switch the state once they are sent or received:
+//!
+//! ```text
+//! @transition(next_state)
+//! packet Handshake(serverbound, Handshake, 0x00) {
+//!     ...
+//!     NextState next_state
+//! }
+//! @transition(Play)
+//! packet LoginSuccess(clientbound, Login, 0x02) { ... }
+//! ```
+//!
+//! The argument is either the name of a state, or an enum field whose variant names the state.
+//! [`Protocol::resolve`] checks that all transitions lead to states.
+//!
+//! Packets marked with `@compression(...)` set the compression threshold of frames
+//! to the value of an integer field, like Set Compression from 1.8 on.
+//! The connection only records it, since frames are read by a [`Framer`](crate::frame::Framer).
+
+use specmc_base::parse::Identifier;
+
+use crate::{
+    decode::{decode, DecodeError},
+    encode::{encode, EncodeError},
+    frame::Frame,
+    packets::{Direction, Packet},
+    types::Type,
+    value::PacketValue,
+    Protocol,
+};
+
+/// The state of a connection, which is shared by both directions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection<'a> {
+    protocol: &'a Protocol,
+    state: Identifier,
+    compression: Option<usize>,
+}
+impl<'a> Connection<'a> {
+    pub fn new(protocol: &'a Protocol, state: Identifier) -> Self {
+        Connection {
+            protocol,
+            state,
+            compression: None,
+        }
+    }
+
+    pub fn protocol(&self) -> &'a Protocol {
+        self.protocol
+    }
+
+    pub fn state(&self) -> &Identifier {
+        &self.state
+    }
+
+    /// The compression threshold from the last packet marked with `@compression(...)`.
+    pub fn compression(&self) -> Option<usize> {
+        self.compression
+    }
+
+    /// Switch to another state, for transitions that aren't part of the specification.
+    pub fn set_state(&mut self, state: Identifier) {
+        self.state = state;
+    }
+
+    /// Find the packet with the given id in the current state.
+    pub fn packet(&self, direction: &Direction, id: u32) -> Option<&'a Packet> {
+        self.protocol.get_packet(&self.state, direction, id)
+    }
+
+    /// Decode a received frame in the current state, and follow its transition.
+    /// Returns `None` for frames with ids that aren't part of the current state.
+    pub fn decode(
+        &mut self,
+        direction: &Direction,
+        frame: &Frame,
+    ) -> Result<Option<(&'a Packet, PacketValue)>, DecodeError> {
+        let Some(packet) = u32::try_from(frame.id)
+            .ok()
+            .and_then(|id| self.packet(direction, id))
+        else {
+            return Ok(None);
+        };
+        let value: PacketValue = decode(self.protocol, packet, &frame.body)?;
+        self.advance(packet, &value);
+        Ok(Some((packet, value)))
+    }
+
+    /// Encode a packet into a frame, and follow its transition.
+    pub fn encode(&mut self, packet: &Packet, value: &PacketValue) -> Result<Frame, EncodeError> {
+        let body: Vec<u8> = encode(self.protocol, packet, value)?;
+        self.advance(packet, value);
+        Ok(Frame {
+            id: packet.id as i32,
+            body,
+        })
+    }
+
+    /// Follow the transition and compression of a packet that was sent or received.
+    /// The state is kept if the packet has no transition, or the state can't be determined.
+    pub fn advance(&mut self, packet: &Packet, value: &PacketValue) {
+        if let Some(threshold) = packet
+            .compression
+            .as_ref()
+            .and_then(|field| value.get(&field.0))
+            .and_then(PacketValue::as_integer)
+        {
+            // Negative thresholds switch compression off
+            self.compression = usize::try_from(threshold).ok();
+        }
+
+        let Some(transition) = &packet.transition else {
+            return;
+        };
+        let Some(field) = packet
+            .fields
+            .0
+            .iter()
+            .find(|field| &field.name == transition)
+        else {
+            self.state = transition.clone();
+            return;
+        };
+
+        let Some(value) = value.get(&field.name.0) else {
+            return;
+        };
+        // Enum fields may be given as the name of the variant or as plain integers when encoding
+        let variant: Option<Identifier> = match value.as_variant().or(value.as_str()) {
+            Some(variant) => Some(Identifier(variant.to_string())),
+            None => match (&field.ty, value.as_integer()) {
+                (Type::CustomType(name), Some(value)) => self
+                    .protocol
+                    .get_enum(name)
+                    .and_then(|e| {
+                        e.variants
+                            .iter()
+                            .find(|variant| variant.value.map(|v| v as i128) == Some(value))
+                    })
+                    .map(|variant| variant.name.clone()),
+                _ => None,
+            },
+        };
+        if let Some(variant) = variant {
+            self.state = variant;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_connection() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let packet = |name: &str| {
+            protocol
+                .packets
+                .iter()
+                .find(|packet| packet.name.0 == name)
+                .unwrap()
+        };
+        let state = |name: &str| Identifier(name.to_string());
+        let handshake = |next_state: PacketValue| {
+            PacketValue::Struct(vec![
+                ("server_address".to_string(), "localhost".into()),
+                ("server_port".to_string(), 25565u16.into()),
+                ("next_state".to_string(), next_state),
+            ])
+        };
+
+        let mut client: Connection = Connection::new(&protocol, state("Handshake"));
+        let mut server: Connection = Connection::new(&protocol, state("Handshake"));
+        let login: PacketValue = PacketValue::Enum {
+            variant: "Login".to_string(),
+            value: 2,
+        };
+        let frame: Frame = client
+            .encode(packet("Handshake"), &handshake(login))
+            .unwrap();
+        assert_eq!(client.state(), &state("Login"));
+        let (received, value) = server
+            .decode(&Direction::Serverbound, &frame)
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.name.0, "Handshake");
+        assert_eq!(value.get("next_state").unwrap().as_variant(), Some("Login"));
+        assert_eq!(server.state(), &state("Login"));
+
+        // The same id refers to a different packet in each state
+        let frame: Frame = client
+            .encode(
+                packet("LoginStart"),
+                &PacketValue::Struct(vec![("name".to_string(), "Steve".into())]),
+            )
+            .unwrap();
+        assert_eq!(frame.id, 0x00);
+        let (received, _) = server
+            .decode(&Direction::Serverbound, &frame)
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.name.0, "LoginStart");
+        assert_eq!(server.state(), &state("Login"));
+
+        let frame: Frame = server
+            .encode(
+                packet("LoginSuccess"),
+                &PacketValue::Struct(vec![
+                    ("uuid".to_string(), "".into()),
+                    ("username".to_string(), "Steve".into()),
+                ]),
+            )
+            .unwrap();
+        assert_eq!(server.state(), &state("Play"));
+        client.decode(&Direction::Clientbound, &frame).unwrap();
+        assert_eq!(client.state(), &state("Play"));
+        assert_eq!(
+            client.packet(&Direction::Serverbound, 0x00).unwrap().name.0,
+            "KeepAlive"
+        );
+
+        // Enum fields given as names or integers are matched to their variant
+        let mut client: Connection = Connection::new(&protocol, state("Handshake"));
+        client
+            .encode(packet("Handshake"), &handshake("Login".into()))
+            .unwrap();
+        assert_eq!(client.state(), &state("Login"));
+        let mut client: Connection = Connection::new(&protocol, state("Handshake"));
+        client
+            .encode(packet("Handshake"), &handshake(1.into()))
+            .unwrap();
+        assert_eq!(client.state(), &state("Status"));
+        assert_eq!(
+            client.decode(
+                &Direction::Clientbound,
+                &Frame {
+                    id: 0x7f,
+                    body: vec![],
+                }
+            ),
+            Ok(None)
+        );
+        assert_eq!(client.state(), &state("Status"));
+
+        // Packets marked with `@compression` set the threshold, and negative ones unset it
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum State(VarInt) {
+                Login
+            }
+            @compression(threshold)
+            packet SetCompression(clientbound, Login, 0x03) {
+                VarInt threshold
+            }
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let set_compression: &Packet = &protocol.packets[0];
+        let threshold =
+            |threshold: i32| PacketValue::Struct(vec![("threshold".to_string(), threshold.into())]);
+        let mut client: Connection = Connection::new(&protocol, state("Login"));
+        assert_eq!(client.compression(), None);
+        client.advance(set_compression, &threshold(256));
+        assert_eq!(client.compression(), Some(256));
+        client.advance(set_compression, &threshold(-1));
+        assert_eq!(client.compression(), None);
+    }
+}
@@ -0,0 +1,69 @@
+use specmc_base::parse::{Identifier, Literal};
+
+use crate::{
+    ensure_tokens,
+    parse::{Parse, Span, TokenStream},
+    Error,
+};
+
+/// A named literal that fields can be set to, like `PVN`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub name: Identifier,
+    pub value: Literal,
+    pub span: Span,
+}
+impl Parse for Constant {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let start: Span = tokens.next_span();
+        ensure_tokens!(tokens, "const");
+        let name: Identifier = Identifier::parse(tokens)?;
+        ensure_tokens!(tokens, "=");
+        let value: Literal = Literal::parse(tokens)?;
+
+        Ok(Constant {
+            name,
+            value,
+            span: tokens.span_from(start),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_parse, tokenize};
+
+    use super::*;
+
+    #[test]
+    fn test_constant() {
+        let mut tokens: TokenStream = tokenize!("const PVN = 4 const NAME = \"a\" const X = Y");
+
+        test_parse!(
+            tokens,
+            Constant,
+            Ok(Constant {
+                name: Identifier("PVN".to_string()),
+                value: Literal::Integer(4),
+                span: Span::default(),
+            })
+        );
+        test_parse!(
+            tokens,
+            Constant,
+            Ok(Constant {
+                name: Identifier("NAME".to_string()),
+                value: Literal::String("a".to_string()),
+                span: Span::default(),
+            })
+        );
+        test_parse!(
+            tokens,
+            Constant,
+            Err(Error::InvalidLiteral {
+                token: "Y".to_string(),
+                span: Span::default(),
+            })
+        );
+    }
+}
@@ -0,0 +1,702 @@
+//! Decoding packets by interpreting their specification.
+//!
+//! [`decode`] walks the fields of a [`Packet`] and reads them from raw bytes
+//! into a [`PacketValue`], so any protocol with a specification can be inspected
+//! without generated code.
+//! [`decode_ref`] does the same without copying strings and bytes out of the input.
+//! [`decode_traced`] also records where each value was read, for debugging.
+
+use std::fmt::Display;
+
+use specmc_base::parse::{Identifier, Literal};
+
+use crate::{
+    base::{BaseType, Field, FieldList, IntegerType, Value},
+    codec::{CodecError, Reader},
+    condition::Condition,
+    enums::Enum,
+    nbt,
+    packets::Packet,
+    trace::{Trace, TraceEntry},
+    types::Type,
+    value::{PacketValue, PacketValueRef},
+    Protocol,
+};
+
+/// The maximum nesting of custom types, which may contain themselves through conditional fields.
+pub const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeErrorKind {
+    /// A primitive value that can't be read.
+    Codec(CodecError),
+    /// A value that isn't the value of any variant of the enum.
+    UnknownVariant {
+        name: Identifier,
+        value: i128,
+    },
+    UnknownType(Identifier),
+    /// A condition that can't be parsed, or refers to fields without a comparable value.
+    InvalidCondition(String),
+    /// The length field of a list or NBT was not decoded before it.
+    MissingLength(Identifier),
+    /// Bytes left over after the last field.
+    TrailingBytes(usize),
+    /// Custom types nested deeper than [`MAX_DEPTH`].
+    TooDeep,
+}
+impl Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use DecodeErrorKind::*;
+        match self {
+            Codec(error) => write!(f, "{error}"),
+            UnknownVariant { name, value } => write!(f, "Unknown variant of `{name}`: {value}"),
+            UnknownType(name) => write!(f, "Unknown type `{name}`"),
+            InvalidCondition(condition) => write!(f, "Invalid condition `{condition}`"),
+            MissingLength(name) => write!(f, "Length field `{name}` is missing"),
+            TrailingBytes(count) => write!(f, "Unexpected bytes after the packet: {count}"),
+            TooDeep => write!(f, "Custom types are nested too deeply"),
+        }
+    }
+}
+
+/// An error while decoding, with the path of the field that failed,
+/// like `slots.0.count`, and the byte offset of the value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    pub kind: DecodeErrorKind,
+    pub path: String,
+    pub offset: usize,
+}
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.path.is_empty() {
+            write!(f, " in `{}`", self.path)?;
+        }
+        write!(f, " at byte {}", self.offset)
+    }
+}
+impl std::error::Error for DecodeError {}
+
+/// Decode the body of a packet, without its id.
+/// All bytes must belong to the packet.
+pub fn decode(
+    protocol: &Protocol,
+    packet: &Packet,
+    bytes: &[u8],
+) -> Result<PacketValue, DecodeError> {
+    decode_ref(protocol, packet, bytes).map(PacketValueRef::into_owned)
+}
+
+/// Decode the body of a packet, borrowing strings and bytes from the input.
+/// Only compressed NBT is copied out of the input.
+pub fn decode_ref<'a>(
+    protocol: &'a Protocol,
+    packet: &'a Packet,
+    bytes: &'a [u8],
+) -> Result<PacketValueRef<'a>, DecodeError> {
+    Decoder::new(protocol, bytes, None).packet(packet)
+}
+
+/// Decode the body of a packet like [`decode_ref`], and record the byte range of every value.
+/// The trace is also returned when decoding fails, up to the value that failed.
+pub fn decode_traced<'a>(
+    protocol: &'a Protocol,
+    packet: &'a Packet,
+    bytes: &'a [u8],
+) -> (Result<PacketValueRef<'a>, DecodeError>, Trace) {
+    let mut decoder: Decoder = Decoder::new(protocol, bytes, Some(vec![]));
+    let result: Result<PacketValueRef, DecodeError> = decoder.packet(packet);
+    let entries: Vec<TraceEntry> = decoder.trace.unwrap_or_default();
+    (result, Trace { entries })
+}
+
+/// A part of the path of the value being decoded.
+pub(crate) enum PathPart<'a> {
+    Field(&'a str),
+    Index(usize),
+}
+
+/// Join a path with dots, like `slots.0.count`.
+pub(crate) fn join_path(path: &[PathPart]) -> String {
+    path.iter()
+        .map(|part| match part {
+            PathPart::Field(name) => name.to_string(),
+            PathPart::Index(index) => index.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+struct Decoder<'a> {
+    protocol: &'a Protocol,
+    reader: Reader<'a>,
+    /// Names of the fields and indices of the list elements being decoded.
+    path: Vec<PathPart<'a>>,
+    /// The values decoded so far, if they are traced.
+    trace: Option<Vec<TraceEntry>>,
+    /// The number of custom types being decoded.
+    depth: usize,
+}
+impl<'a> Decoder<'a> {
+    fn new(protocol: &'a Protocol, bytes: &'a [u8], trace: Option<Vec<TraceEntry>>) -> Self {
+        Decoder {
+            protocol,
+            reader: Reader::new(bytes),
+            path: vec![],
+            trace,
+            depth: 0,
+        }
+    }
+
+    fn packet(&mut self, packet: &'a Packet) -> Result<PacketValueRef<'a>, DecodeError> {
+        let value: PacketValueRef = self.fields(&packet.fields)?;
+        let remaining: usize = self.reader.remaining().len();
+        if remaining != 0 {
+            return Err(self.error(DecodeErrorKind::TrailingBytes(remaining)));
+        }
+        Ok(value)
+    }
+
+    /// Start tracing the value at the end of the path, returning the index of its entry.
+    fn begin(&mut self, ty: &Type) -> Option<usize> {
+        let trace: &mut Vec<TraceEntry> = self.trace.as_mut()?;
+        trace.push(TraceEntry {
+            depth: self.path.len() - 1,
+            name: match self.path.last() {
+                Some(PathPart::Field(name)) => name.to_string(),
+                Some(PathPart::Index(index)) => format!("[{index}]"),
+                None => String::new(),
+            },
+            ty: ty.to_string(),
+            start: self.reader.offset(),
+            end: None,
+            value: None,
+        });
+        Some(trace.len() - 1)
+    }
+
+    /// Finish tracing a value that was decoded successfully.
+    fn finish(&mut self, entry: Option<usize>, value: &PacketValueRef) {
+        let (Some(entry), Some(trace)) = (entry, &mut self.trace) else {
+            return;
+        };
+        trace[entry].end = Some(self.reader.offset());
+        // Structs and lists are shown through their elements
+        trace[entry].value = match value {
+            PacketValueRef::Struct(_) | PacketValueRef::List(_) => None,
+            value => Some(value.clone().into_owned().to_string()),
+        };
+    }
+    fn error(&self, kind: DecodeErrorKind) -> DecodeError {
+        DecodeError {
+            kind,
+            path: join_path(&self.path),
+            offset: self.reader.offset(),
+        }
+    }
+
+    fn read<T>(
+        &mut self,
+        read: impl FnOnce(&mut Reader<'a>) -> Result<T, CodecError>,
+    ) -> Result<T, DecodeError> {
+        read(&mut self.reader).map_err(|error| self.error(DecodeErrorKind::Codec(error)))
+    }
+
+    fn fields(&mut self, fields: &'a FieldList) -> Result<PacketValueRef<'a>, DecodeError> {
+        let mut decoded: Vec<(&'a str, PacketValueRef<'a>)> = vec![];
+        for field in &fields.0 {
+            self.path.push(PathPart::Field(&field.name.0));
+            let find = |name: &Identifier| {
+                decoded
+                    .iter()
+                    .find(|(field, _)| *field == name.0)
+                    .and_then(|(_, value)| value.literal())
+            };
+            let present: bool =
+                present(self.protocol, field, fields, find).map_err(|condition| {
+                    self.error(DecodeErrorKind::InvalidCondition(condition.to_string()))
+                })?;
+            if present {
+                let entry: Option<usize> = self.begin(&field.ty);
+                let length: Option<usize> = self.linked_length(field, fields, &decoded)?;
+                let value: PacketValueRef = self.value(&field.ty, length)?;
+                self.finish(entry, &value);
+                decoded.push((&field.name.0, value));
+            }
+            self.path.pop();
+        }
+        Ok(PacketValueRef::Struct(decoded))
+    }
+
+    /// The length of an unprefixed list or NBT, from a field defined as `len(...)`.
+    fn linked_length(
+        &self,
+        field: &Field,
+        fields: &FieldList,
+        decoded: &[(&str, PacketValueRef)],
+    ) -> Result<Option<usize>, DecodeError> {
+        let Some(length_field) = fields.0.iter().find(
+            |other| matches!(&other.value, Some(Value::Length(name, _)) if name == &field.name),
+        ) else {
+            return Ok(None);
+        };
+        let length: i128 = decoded
+            .iter()
+            .find(|(name, _)| *name == length_field.name.0)
+            .and_then(|(_, value)| value.as_integer())
+            .ok_or_else(|| self.error(DecodeErrorKind::MissingLength(length_field.name.clone())))?;
+        usize::try_from(length)
+            .map(Some)
+            .map_err(|_| self.error(DecodeErrorKind::Codec(CodecError::InvalidLength(length))))
+    }
+
+    fn value(
+        &mut self,
+        ty: &'a Type,
+        length: Option<usize>,
+    ) -> Result<PacketValueRef<'a>, DecodeError> {
+        match ty {
+            Type::BaseType(base_type) => self.base_type(base_type, length),
+            Type::CustomType(name) => {
+                if let Some(e) = self.protocol.get_enum(name) {
+                    let start: Reader = self.reader.clone();
+                    let value: i128 = self.read(|reader| reader.read_integer(&e.ty))?;
+                    let Some(variant) = e
+                        .variants
+                        .iter()
+                        .find(|variant| variant.value.map(|v| v as i128) == Some(value))
+                    else {
+                        self.reader = start;
+                        return Err(self.error(DecodeErrorKind::UnknownVariant {
+                            name: name.clone(),
+                            value,
+                        }));
+                    };
+                    Ok(PacketValueRef::Enum {
+                        variant: &variant.name.0,
+                        value,
+                    })
+                } else if let Some(custom_type) = self.protocol.get_type(name) {
+                    if self.depth >= MAX_DEPTH {
+                        return Err(self.error(DecodeErrorKind::TooDeep));
+                    }
+                    self.depth += 1;
+                    let value: PacketValueRef = self.fields(&custom_type.fields)?;
+                    self.depth -= 1;
+                    Ok(value)
+                } else {
+                    Err(self.error(DecodeErrorKind::UnknownType(name.clone())))
+                }
+            }
+        }
+    }
+
+    fn base_type(
+        &mut self,
+        base_type: &'a BaseType,
+        length: Option<usize>,
+    ) -> Result<PacketValueRef<'a>, DecodeError> {
+        use BaseType::*;
+        Ok(match base_type {
+            Bool => PacketValueRef::Bool(self.read(Reader::read_bool)?),
+            Integer(integer_type) => {
+                PacketValueRef::Integer(self.read(|reader| reader.read_integer(integer_type))?)
+            }
+            F32 => PacketValueRef::Float(self.read(Reader::read_f32)? as f64),
+            F64 => PacketValueRef::Float(self.read(Reader::read_f64)?),
+            String { length: max } => PacketValueRef::String(
+                self.read(|reader| reader.read_str(max.unwrap_or(BaseType::MAX_STRING_LENGTH)))?,
+            ),
+            List { ty, length: fixed } => {
+                let count: usize = match fixed.or(length) {
+                    Some(count) => count,
+                    None => self.read(Reader::read_length)?,
+                };
+
+                if let Type::BaseType(Integer(IntegerType::U8)) = **ty {
+                    return Ok(PacketValueRef::Bytes(
+                        self.read(|reader| reader.read_bytes(count))?,
+                    ));
+                }
+
+                // The count comes from the input, so it isn't used to preallocate
+                let mut values: Vec<PacketValueRef> = vec![];
+                for i in 0..count {
+                    self.path.push(PathPart::Index(i));
+                    let entry: Option<usize> = self.begin(ty);
+                    let value: PacketValueRef = self.value(ty, None)?;
+                    self.finish(entry, &value);
+                    values.push(value);
+                    self.path.pop();
+                }
+                PacketValueRef::List(values)
+            }
+            Nbt => match length {
+                // NBT with a length field is gzip compressed, like item data in 1.7
+                Some(length) => {
+                    let start: Reader = self.reader.clone();
+                    let bytes: &[u8] = self.read(|reader| reader.read_bytes(length))?;
+                    PacketValueRef::Nbt(nbt::read_compressed(bytes).map_err(|error| {
+                        self.reader = start;
+                        self.error(DecodeErrorKind::Codec(error))
+                    })?)
+                }
+                None => PacketValueRef::RawNbt(self.read(nbt::read_raw_nbt)?),
+            },
+        })
+    }
+}
+
+/// Whether all conditions of a field hold for the values of the fields before it,
+/// which are looked up by `value`.
+/// Names that are not fields may be variants of an enum field in the same condition.
+/// Conditions of nested `if` blocks may refer to fields that are only present when
+/// the outer conditions hold, so the field is absent if any condition doesn't hold.
+/// Otherwise, a condition that can't be evaluated is returned as the error.
+pub(crate) fn present<'a>(
+    protocol: &Protocol,
+    field: &'a Field,
+    fields: &FieldList,
+    value: impl Fn(&Identifier) -> Option<Literal>,
+) -> Result<bool, &'a str> {
+    let mut invalid: Option<&str> = None;
+    for text in &field.conditions {
+        let Ok(condition) = text.parse::<Condition>() else {
+            invalid = Some(text);
+            continue;
+        };
+        let enums: Vec<&Enum> = condition
+            .fields()
+            .into_iter()
+            .filter_map(|name| {
+                let field: &Field = fields.0.iter().find(|field| &field.name == name)?;
+                match &field.ty {
+                    Type::CustomType(ty) => protocol.get_enum(ty),
+                    Type::BaseType(_) => None,
+                }
+            })
+            .collect();
+
+        let holds: Option<bool> = condition.evaluate(&mut |name: &Identifier| {
+            if let Some(value) = value(name) {
+                return Some(value);
+            }
+            enums
+                .iter()
+                .find_map(|e| e.variant(name)?.value)
+                .map(Literal::Integer)
+        });
+        match holds {
+            Some(true) => {}
+            Some(false) => return Ok(false),
+            None => invalid = Some(text),
+        }
+    }
+
+    match invalid {
+        Some(condition) => Err(condition),
+        None => Ok(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        nbt::{NamedTag, Nbt},
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    fn protocol() -> Protocol {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum NextState(VarInt) {
+                Status = 1
+                Login
+            }
+            enum Mode(u8) {
+                Survival
+                Creative
+            }
+            type Slot {
+                i16 id
+                if (id != -1) {
+                    i8 count
+                }
+            }
+            type Node {
+                bool more
+                if (more) {
+                    Node next
+                }
+            }
+            packet Handshake(serverbound, Handshake, 0x00) {
+                VarInt protocol_version
+                String[255] server_address
+                u16 server_port
+                NextState next_state
+            }
+            packet Window(clientbound, Play, 0x30) {
+                Mode mode
+                if (mode == Creative) {
+                    f32 speed
+                }
+                List[Slot] slots
+                i8 length = len(data)
+                List[i8] data
+                List[bool; 2] flags
+            }
+            packet Tag(clientbound, Play, 0x31) {
+                i16 length = len(tag)
+                if (length != -1) {
+                    Nbt tag
+                }
+                Nbt extra
+            }
+            packet Chain(clientbound, Play, 0x32) {
+                Node node
+            }
+            "
+        );
+        Protocol::parse(&mut tokens).unwrap()
+    }
+
+    fn packet<'a>(protocol: &'a Protocol, name: &str) -> &'a Packet {
+        protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_decode() {
+        let protocol: Protocol = protocol();
+
+        let mut bytes: Vec<u8> = vec![0x04, 0x09];
+        bytes.extend(b"localhost");
+        bytes.extend([0x63, 0xdd, 0x02]);
+        let value: PacketValue = decode(&protocol, packet(&protocol, "Handshake"), &bytes).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "{ protocol_version: 4, server_address: \"localhost\", server_port: 25565, next_state: Login(2) }"
+        );
+
+        let bytes: Vec<u8> = vec![
+            0x01, 0x3f, 0x80, 0x00, 0x00, // Creative, 1.0
+            0x02, 0xff, 0xff, 0x00, 0x01, 0x40, // slots
+            0x02, 0xfe, 0x01, // data
+            0x00, 0x01, // flags
+        ];
+        let value: PacketValue = decode(&protocol, packet(&protocol, "Window"), &bytes).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "{ mode: Creative(1), speed: 1.0, slots: [{ id: -1 }, { id: 1, count: 64 }], length: 2, data: [-2, 1], flags: [false, true] }"
+        );
+
+        let value: PacketValue = decode(
+            &protocol,
+            packet(&protocol, "Window"),
+            &[0x00, 0x00, 0x00, 0x00, 0x00],
+        )
+        .unwrap();
+        assert_eq!(value.get("speed"), None);
+
+        let tag: NamedTag = NamedTag {
+            name: "".to_string(),
+            tag: Nbt::Compound(vec![("id".to_string(), Nbt::Short(1))]),
+        };
+        let compressed: Vec<u8> = nbt::write_compressed(Some(&tag)).unwrap();
+        let mut bytes: Vec<u8> = (compressed.len() as i16).to_be_bytes().to_vec();
+        bytes.extend(compressed);
+        bytes.extend([0x0a, 0x00, 0x01, b'a', 0x01, 0x00, 0x01, b'b', 0x05, 0x00]);
+        let value: PacketValue = decode(&protocol, packet(&protocol, "Tag"), &bytes).unwrap();
+        assert_eq!(value.get("tag"), Some(&PacketValue::Nbt(Some(tag))));
+        assert_eq!(value.get("extra").unwrap().to_string(), "\"a\": {b: 5b}");
+
+        let value: PacketValue =
+            decode(&protocol, packet(&protocol, "Tag"), &[0xff, 0xff, 0x00]).unwrap();
+        assert_eq!(value.get("tag"), None);
+        assert_eq!(value.get("extra"), Some(&PacketValue::Nbt(None)));
+    }
+
+    #[test]
+    fn test_decode_ref() {
+        let protocol: Protocol = protocol();
+        let within = |bytes: &[u8], slice: &[u8]| bytes.as_ptr_range().contains(&slice.as_ptr());
+
+        let mut bytes: Vec<u8> = vec![0x04, 0x09];
+        bytes.extend(b"localhost");
+        bytes.extend([0x63, 0xdd, 0x02]);
+        let value: PacketValueRef =
+            decode_ref(&protocol, packet(&protocol, "Handshake"), &bytes).unwrap();
+        let address: &str = value.get("server_address").unwrap().as_str().unwrap();
+        assert_eq!(address, "localhost");
+        assert!(within(&bytes, address.as_bytes()));
+        assert_eq!(value.get("next_state").unwrap().as_variant(), Some("Login"));
+        assert_eq!(
+            value.into_owned(),
+            decode(&protocol, packet(&protocol, "Handshake"), &bytes).unwrap()
+        );
+
+        // Uncompressed NBT is borrowed, compressed NBT is decompressed
+        let tag: NamedTag = NamedTag {
+            name: "".to_string(),
+            tag: Nbt::Compound(vec![("id".to_string(), Nbt::Short(1))]),
+        };
+        let compressed: Vec<u8> = nbt::write_compressed(Some(&tag)).unwrap();
+        let mut bytes: Vec<u8> = (compressed.len() as i16).to_be_bytes().to_vec();
+        bytes.extend(compressed);
+        bytes.extend([0x0a, 0x00, 0x01, b'a', 0x01, 0x00, 0x01, b'b', 0x05, 0x00]);
+        let value: PacketValueRef =
+            decode_ref(&protocol, packet(&protocol, "Tag"), &bytes).unwrap();
+        assert_eq!(value.get("tag"), Some(&PacketValueRef::Nbt(Some(tag))));
+        let extra: &[u8] = value.get("extra").unwrap().as_bytes().unwrap();
+        assert_eq!(extra, &bytes[bytes.len() - 10..]);
+        assert!(within(&bytes, extra));
+        assert_eq!(
+            value.into_owned(),
+            decode(&protocol, packet(&protocol, "Tag"), &bytes).unwrap()
+        );
+
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let bytes: Vec<u8> = vec![0x00, 0x02, 0xaa, 0xbb, 0x00, 0x01, 0xcc];
+        let value: PacketValueRef =
+            decode_ref(&protocol, packet(&protocol, "EncryptionResponse"), &bytes).unwrap();
+        let secret: &[u8] = value.path("shared_secret").unwrap().as_bytes().unwrap();
+        assert_eq!(secret, [0xaa, 0xbb]);
+        assert!(within(&bytes, secret));
+        assert_eq!(
+            decode_ref(
+                &protocol,
+                packet(&protocol, "EncryptionResponse"),
+                &bytes[..3]
+            )
+            .unwrap_err(),
+            DecodeError {
+                kind: DecodeErrorKind::Codec(CodecError::UnexpectedEnd),
+                path: "shared_secret".to_string(),
+                offset: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let protocol: Protocol = protocol();
+        let error = |name: &str, bytes: &[u8]| {
+            decode(&protocol, packet(&protocol, name), bytes).unwrap_err()
+        };
+
+        let error_slot: DecodeError = error("Window", &[0x00, 0x02, 0x00, 0x01]);
+        assert_eq!(
+            error_slot,
+            DecodeError {
+                kind: DecodeErrorKind::Codec(CodecError::UnexpectedEnd),
+                path: "slots.0.count".to_string(),
+                offset: 4,
+            }
+        );
+        assert_eq!(
+            error_slot.to_string(),
+            "Unexpected end of input in `slots.0.count` at byte 4"
+        );
+
+        assert_eq!(
+            error("Window", &[0x02]).kind,
+            DecodeErrorKind::UnknownVariant {
+                name: Identifier("Mode".to_string()),
+                value: 2,
+            }
+        );
+        assert_eq!(
+            error("Window", &[0x00, 0x00, 0xff]),
+            DecodeError {
+                kind: DecodeErrorKind::Codec(CodecError::InvalidLength(-1)),
+                path: "data".to_string(),
+                offset: 3,
+            }
+        );
+        assert_eq!(
+            error("Handshake", &[0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+            DecodeError {
+                kind: DecodeErrorKind::Codec(CodecError::VarIntTooLong),
+                path: "protocol_version".to_string(),
+                offset: 0,
+            }
+        );
+        assert_eq!(
+            error("Handshake", &[0x04, 0x02, 0xc3, 0x28]),
+            DecodeError {
+                kind: DecodeErrorKind::Codec(CodecError::InvalidUtf8),
+                path: "server_address".to_string(),
+                offset: 1,
+            }
+        );
+        assert_eq!(
+            error("Tag", &[0x00, 0x02, 0x1f, 0x8b, 0x00]),
+            DecodeError {
+                kind: DecodeErrorKind::Codec(CodecError::InvalidCompression),
+                path: "tag".to_string(),
+                offset: 2,
+            }
+        );
+        assert_eq!(
+            error("Tag", &[0xff, 0xff, 0x0d]),
+            DecodeError {
+                kind: DecodeErrorKind::Codec(CodecError::InvalidNbtTag(13)),
+                path: "extra".to_string(),
+                offset: 2,
+            }
+        );
+        assert_eq!(
+            error("Tag", &[0x00, 0x00, 0x00, 0x00]),
+            DecodeError {
+                kind: DecodeErrorKind::TrailingBytes(1),
+                path: "".to_string(),
+                offset: 3,
+            }
+        );
+        assert_eq!(
+            error("Tag", &[0x00, 0x00, 0x00, 0x00]).to_string(),
+            "Unexpected bytes after the packet: 1 at byte 3"
+        );
+
+        // Types that contain themselves can't nest without limit
+        assert!(decode(&protocol, packet(&protocol, "Chain"), &[0x01, 0x01, 0x00]).is_ok());
+        let error_chain: DecodeError = error("Chain", &vec![0x01; 2_000_000]);
+        assert_eq!(error_chain.kind, DecodeErrorKind::TooDeep);
+        assert_eq!(error_chain.offset, MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_decode_spec() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+
+        let bytes: Vec<u8> = vec![
+            0x00, 0x00, 0x00, 0x01, 0x40, 0xff, 0xff, 0xff, 0xfe, 0x01, // position and face
+            0xff, 0xff, // empty slot
+            0x08, 0x00, 0x0f,
+        ];
+        let value: PacketValue =
+            decode(&protocol, packet(&protocol, "PlayerBlockPlacement"), &bytes).unwrap();
+        assert_eq!(value.path("z"), Some(&PacketValue::Integer(-2)));
+        assert_eq!(value.path("held_item.id"), Some(&PacketValue::Integer(-1)));
+        assert_eq!(value.path("held_item.count"), None);
+        assert_eq!(value.path("cursor_z"), Some(&PacketValue::Integer(15)));
+
+        let bytes: Vec<u8> = vec![0x00, 0x02, 0xaa, 0xbb, 0x00, 0x01, 0xcc];
+        let value: PacketValue =
+            decode(&protocol, packet(&protocol, "EncryptionResponse"), &bytes).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "{ shared_secret_length: 2, shared_secret: [170, 187], verify_token_length: 1, verify_token: [204] }"
+        );
+    }
+}
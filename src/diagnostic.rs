@@ -0,0 +1,464 @@
+//! Problems found in a specification, and rendering them like compiler errors.
+
+use std::fmt::Display;
+
+use crate::{
+    base::{FieldList, Value},
+    condition::Condition,
+    enums::Enum,
+    lint::{Level, Warning, BASE_TYPES},
+    parse::Span,
+    Error, Protocol,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+impl Severity {
+    fn colour(&self) -> &'static str {
+        match self {
+            Severity::Note => "\x1b[1;36m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Error => "\x1b[1;31m",
+        }
+    }
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A message attached to a part of the specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A short code identifying the kind of problem, like a lint code.
+    pub code: Option<String>,
+    pub message: String,
+    /// Where the problem is.
+    pub primary: Label,
+    /// Other places related to the problem.
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    /// A suggested replacement for the offending name.
+    pub suggestion: Option<String>,
+}
+impl Diagnostic {
+    pub fn error(message: impl ToString, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: message.to_string(),
+            primary: Label {
+                span,
+                message: String::new(),
+            },
+            secondary: vec![],
+            notes: vec![],
+            suggestion: None,
+        }
+    }
+
+    /// Convert an error. Errors without a span are reported at the given span.
+    pub fn new(error: Error, span: Span) -> Self {
+        let span: Span = error.span().unwrap_or(span);
+        let mut diagnostic: Diagnostic = Diagnostic::error(error.message(), span);
+        if let Some(token) = error.token() {
+            diagnostic = diagnostic.with_label(format!("found `{token}`"));
+        }
+        if let Error::UnclosedBlock { kind, open, .. } = &error {
+            diagnostic = diagnostic
+                .with_label("expected `}`")
+                .with_secondary(*open, format!("`{kind}` block opened here"));
+        }
+        diagnostic
+    }
+
+    pub fn with_code(mut self, code: impl ToString) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// Set the message of the primary label.
+    pub fn with_label(mut self, message: impl ToString) -> Self {
+        self.primary.message = message.to_string();
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl ToString) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.to_string(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl ToString) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Option<impl ToString>) -> Self {
+        self.suggestion = suggestion.map(|suggestion| suggestion.to_string());
+        self
+    }
+
+    /// Render the diagnostic with the lines of the source it refers to.
+    /// If `colour` is set, ANSI escape codes are used to highlight it.
+    pub fn render(&self, source: &str, colour: bool) -> String {
+        let paint = |code: &'static str, text: &str| -> String {
+            if colour {
+                format!("{code}{text}\x1b[0m")
+            } else {
+                text.to_string()
+            }
+        };
+        const BLUE: &str = "\x1b[1;34m";
+        const BOLD: &str = "\x1b[1m";
+
+        let mut labels: Vec<(&Label, bool)> = vec![(&self.primary, true)];
+        labels.extend(self.secondary.iter().map(|label| (label, false)));
+        labels.sort_by_key(|(label, _)| (label.span.line, label.span.column));
+        let width: usize = labels
+            .iter()
+            .map(|(label, _)| label.span.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter: String = paint(BLUE, &format!("{} |", " ".repeat(width)));
+
+        let mut output: String = paint(self.severity.colour(), &self.severity.to_string());
+        if let Some(code) = &self.code {
+            output += &paint(self.severity.colour(), &format!("[{code}]"));
+        }
+        output += &paint(BOLD, &format!(": {}", self.message));
+        output += &format!(
+            "\n{}{} {}\n{gutter}\n",
+            " ".repeat(width),
+            paint(BLUE, "-->"),
+            self.primary.span
+        );
+
+        let lines: Vec<&str> = source.lines().collect();
+        for (i, (label, primary)) in labels.iter().enumerate() {
+            let Some(line) = label.span.line.checked_sub(1).and_then(|i| lines.get(i)) else {
+                continue;
+            };
+            if i == 0 || labels[i - 1].0.span.line != label.span.line {
+                output += &paint(BLUE, &format!("{:>width$} |", label.span.line));
+                output += &format!(" {line}\n");
+            }
+
+            // Spans of multiple lines are underlined up to the end of the first line
+            let rest: usize = line.chars().count().saturating_sub(label.span.column - 1);
+            let length: usize = source
+                .get(label.span.start..label.span.end)
+                .map_or(1, |text| text.chars().count().min(rest).max(1));
+            let (marker, code): (&str, &str) = if *primary {
+                ("^", self.severity.colour())
+            } else {
+                ("-", BLUE)
+            };
+            let mut underline: String = marker.repeat(length);
+            if !label.message.is_empty() {
+                underline += &format!(" {}", label.message);
+            }
+            output += &format!(
+                "{gutter} {}{}\n",
+                " ".repeat(label.span.column.saturating_sub(1)),
+                paint(code, &underline)
+            );
+        }
+
+        for note in &self.notes {
+            output += &format!("{} {} note: {note}\n", " ".repeat(width), paint(BLUE, "="));
+        }
+        if let Some(suggestion) = &self.suggestion {
+            output += &format!(
+                "{} {} help: did you mean `{suggestion}`?\n",
+                " ".repeat(width),
+                paint(BLUE, "=")
+            );
+        }
+
+        output
+    }
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.severity)?;
+        if let Some(code) = &self.code {
+            write!(f, "[{code}]")?;
+        }
+        write!(f, ": {} at {}", self.message, self.primary.span)
+    }
+}
+impl From<Warning> for Diagnostic {
+    fn from(warning: Warning) -> Self {
+        let severity: Severity = match warning.level {
+            Level::Deny => Severity::Error,
+            _ => Severity::Warning,
+        };
+        Diagnostic {
+            severity,
+            ..Diagnostic::error(warning.message, warning.span).with_code(warning.lint)
+        }
+    }
+}
+
+/// The number of single character insertions, deletions and substitutions
+/// needed to turn one string into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution: usize = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `name`, if it is close enough to be a likely typo.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit: usize = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= limit || candidate.eq_ignore_ascii_case(name))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+impl Protocol {
+    /// Find names that don't refer to anything: unknown types, enum variants,
+    /// and fields in conditions and lengths. Similar names are suggested.
+    pub fn check_names(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = vec![];
+        for fields in self
+            .types
+            .iter()
+            .map(|ty| &ty.fields)
+            .chain(self.packets.iter().map(|packet| &packet.fields))
+        {
+            self.check_field_names(fields, &mut diagnostics);
+        }
+        diagnostics
+    }
+
+    fn check_field_names(&self, fields: &FieldList, diagnostics: &mut Vec<Diagnostic>) {
+        let types: Vec<&str> = BASE_TYPES
+            .iter()
+            .copied()
+            .chain(self.enums.iter().map(|e| e.name.0.as_str()))
+            .chain(self.types.iter().map(|ty| ty.name.0.as_str()))
+            .collect();
+        let field_names: Vec<&str> = fields.0.iter().map(|field| field.name.0.as_str()).collect();
+
+        for field in &fields.0 {
+            if let Some(name) = field.ty.referenced() {
+                if self.get_enum(name).is_none() && self.get_type(name).is_none() {
+                    diagnostics.push(
+                        Diagnostic::error(format!("Unknown type `{name}`"), field.span)
+                            .with_code("unknown_type")
+                            .with_label("not defined")
+                            .with_suggestion(suggest(&name.0, types.iter().copied())),
+                    );
+                }
+            }
+
+            match &field.value {
+                Some(Value::Length(name, span)) if !field_names.contains(&name.0.as_str()) => {
+                    diagnostics.push(
+                        Diagnostic::error(format!("Unknown field `{name}`"), *span)
+                            .with_code("unknown_field")
+                            .with_label("not a field of this type or packet")
+                            .with_suggestion(suggest(&name.0, field_names.iter().copied())),
+                    );
+                }
+                Some(Value::Identifier(name, span)) if !field_names.contains(&name.0.as_str()) => {
+                    let e: Option<&Enum> = field.ty.referenced().and_then(|ty| self.get_enum(ty));
+                    if let Some(e) = e.filter(|e| e.variant(name).is_none()) {
+                        diagnostics.push(
+                            Diagnostic::error(
+                                format!("Unknown variant `{name}` of `{}`", e.name),
+                                *span,
+                            )
+                            .with_code("unknown_variant")
+                            .with_label(format!("not a variant of `{}`", e.name))
+                            .with_secondary(e.span, "enum defined here")
+                            .with_suggestion(suggest(
+                                &name.0,
+                                e.variants.iter().map(|variant| variant.name.0.as_str()),
+                            )),
+                        );
+                    }
+                }
+                _ => {}
+            }
+
+            let mut conditions: Vec<&String> = field.conditions.iter().collect();
+            conditions.sort();
+            for condition in conditions {
+                let Ok(condition) = condition.parse::<Condition>() else {
+                    continue;
+                };
+                // Enum variants of fields in the same condition can be used as values
+                let variants: Vec<&str> = condition
+                    .fields()
+                    .into_iter()
+                    .filter_map(|name| fields.0.iter().find(|field| &field.name == name))
+                    .filter_map(|field| self.get_enum(field.ty.referenced()?))
+                    .flat_map(|e| e.variants.iter().map(|variant| variant.name.0.as_str()))
+                    .collect();
+                for name in condition.fields() {
+                    if field_names.contains(&name.0.as_str()) || variants.contains(&name.0.as_str())
+                    {
+                        continue;
+                    }
+                    let diagnostic: Diagnostic = Diagnostic::error(
+                        format!("Unknown field `{name}` in condition"),
+                        field.span,
+                    )
+                    .with_code("unknown_field")
+                    .with_label(format!("used in the condition of `{}`", field.name))
+                    .with_note(
+                        "conditions can refer to earlier fields and to variants of their enums",
+                    )
+                    .with_suggestion(suggest(
+                        &name.0,
+                        field_names.iter().chain(variants.iter()).copied(),
+                    ));
+                    if !diagnostics.contains(&diagnostic) {
+                        diagnostics.push(diagnostic);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("VarInt", "VarInt"), 0);
+
+        assert_eq!(suggest("Strng", ["String", "bool"]), Some("String"));
+        assert_eq!(suggest("varint", ["VarInt", "VarLong"]), Some("VarInt"));
+        assert_eq!(suggest("abc", ["String", "bool"]), None);
+    }
+
+    #[test]
+    fn test_check_names() {
+        const INPUT: &str = "
+enum Mode(u8) {
+    Survival
+    Creative
+}
+type Item {
+    Strng name
+}
+packet P(serverbound, Play, 0x00) {
+    Mode mode = Creatve
+    bool has_item
+    if (mode == Survival && has_itme) {
+        Item item
+    }
+}
+";
+        let mut tokens: TokenStream = tokenize!(INPUT);
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let diagnostics: Vec<Diagnostic> = protocol.check_names();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(Diagnostic::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "error[unknown_type]: Unknown type `Strng` at 7:5",
+                "error[unknown_variant]: Unknown variant `Creatve` of `Mode` at 10:17",
+                "error[unknown_field]: Unknown field `has_itme` in condition at 13:9",
+            ]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.suggestion.as_deref())
+                .collect::<Vec<Option<&str>>>(),
+            vec![Some("String"), Some("Creative"), Some("has_item")]
+        );
+
+        assert_eq!(
+            diagnostics[1].render(INPUT, false),
+            "error[unknown_variant]: Unknown variant `Creatve` of `Mode`
+  --> 10:17
+   |
+ 2 | enum Mode(u8) {
+   | --------------- enum defined here
+10 |     Mode mode = Creatve
+   |                 ^^^^^^^ not a variant of `Mode`
+   = help: did you mean `Creative`?
+"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let source: &str = "type T {\n    Unknown<> b\n}\n";
+        let mut tokens: TokenStream = tokenize!(source);
+        let (_, diagnostics) = Protocol::parse_recovering(std::mem::take(&mut tokens));
+        assert_eq!(diagnostics.len(), 1);
+
+        let rendered: String = diagnostics[0].render(source, false);
+        assert_eq!(
+            rendered,
+            "error: Identifiers must not start with a number and can only contain letters, numbers, and underscores
+ --> 2:12
+  |
+2 |     Unknown<> b
+  |            ^ found `<`
+"
+        );
+
+        let coloured: String = diagnostics[0].render(source, true);
+        assert!(coloured.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(coloured.contains("\x1b[1;31m^ found `<`\x1b[0m"));
+
+        let diagnostic: Diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error("Something", diagnostics[0].primary.span)
+        }
+        .with_code("code")
+        .with_note("a note");
+        assert_eq!(diagnostic.to_string(), "warning[code]: Something at 2:12");
+        assert!(diagnostic
+            .render(source, false)
+            .ends_with("  = note: a note\n"));
+    }
+}
@@ -0,0 +1,705 @@
+//! Encoding packets by interpreting their specification.
+//!
+//! [`encode`] is the counterpart of [`decode`](crate::decode::decode).
+//! Fields that are set to `len(...)`, a constant or another field are computed,
+//! so they don't have to be supplied.
+
+use std::fmt::Display;
+
+use specmc_base::parse::{Identifier, Literal};
+
+use crate::{
+    base::{BaseType, Field, FieldList, IntegerType, Value},
+    codec::{self, CodecError, Reader},
+    decode::{present, MAX_DEPTH},
+    enums::{Enum, Variant},
+    nbt::{self, NamedTag},
+    packets::Packet,
+    types::Type,
+    value::PacketValue,
+    Protocol,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeErrorKind {
+    /// A field that is present but wasn't supplied.
+    MissingField,
+    /// A value of the wrong kind for the type of the field.
+    InvalidValue {
+        expected: String,
+    },
+    /// A primitive value that can't be written.
+    Codec(CodecError),
+    /// A list with a different number of elements than its fixed length.
+    InvalidListLength {
+        length: usize,
+        expected: usize,
+    },
+    /// A variant that isn't part of the enum.
+    UnknownVariant {
+        name: Identifier,
+        variant: String,
+    },
+    UnknownType(Identifier),
+    UnknownConstant(Identifier),
+    /// A condition that can't be parsed, or refers to fields without a comparable value.
+    InvalidCondition(String),
+    /// Custom types nested deeper than [`MAX_DEPTH`].
+    TooDeep,
+}
+impl Display for EncodeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use EncodeErrorKind::*;
+        match self {
+            MissingField => write!(f, "Missing field"),
+            InvalidValue { expected } => write!(f, "Expected {expected}"),
+            Codec(error) => write!(f, "{error}"),
+            InvalidListLength { length, expected } => {
+                write!(f, "List of length {length} should have length {expected}")
+            }
+            UnknownVariant { name, variant } => write!(f, "Unknown variant of `{name}`: {variant}"),
+            UnknownType(name) => write!(f, "Unknown type `{name}`"),
+            UnknownConstant(name) => write!(f, "Unknown constant `{name}`"),
+            InvalidCondition(condition) => write!(f, "Invalid condition `{condition}`"),
+            TooDeep => write!(f, "Custom types are nested too deeply"),
+        }
+    }
+}
+
+/// An error while encoding, with the path of the field that failed, like `slots.0.count`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeError {
+    pub kind: EncodeErrorKind,
+    pub path: String,
+}
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.path.is_empty() {
+            write!(f, " in `{}`", self.path)?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for EncodeError {}
+
+/// Encode the body of a packet, without its id.
+/// `value` must be a struct with the fields of the packet.
+/// Fields with a value in the specification are computed, and supplied values for them are ignored.
+/// A `len(...)` field is `-1` if the field it refers to is absent.
+pub fn encode(
+    protocol: &Protocol,
+    packet: &Packet,
+    value: &PacketValue,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut encoder: Encoder = Encoder {
+        protocol,
+        bytes: vec![],
+        path: vec![],
+        depth: 0,
+    };
+    encoder.fields(&packet.fields, value)?;
+    Ok(encoder.bytes)
+}
+
+pub(crate) fn literal_value(literal: &Literal) -> PacketValue {
+    match literal {
+        Literal::Boolean(value) => PacketValue::Bool(*value),
+        Literal::Integer(value) => PacketValue::Integer(*value as i128),
+        Literal::Float(value) => PacketValue::Float(*value),
+        Literal::String(value) => PacketValue::String(value.clone()),
+    }
+}
+
+struct Encoder<'a> {
+    protocol: &'a Protocol,
+    bytes: Vec<u8>,
+    /// Names of the fields and indices of the list elements being encoded.
+    path: Vec<String>,
+    /// The number of custom types being encoded.
+    depth: usize,
+}
+impl Encoder<'_> {
+    fn error(&self, kind: EncodeErrorKind) -> EncodeError {
+        EncodeError {
+            kind,
+            path: self.path.join("."),
+        }
+    }
+
+    fn invalid(&self, expected: &str) -> EncodeError {
+        self.error(EncodeErrorKind::InvalidValue {
+            expected: expected.to_string(),
+        })
+    }
+
+    fn write<T>(
+        &mut self,
+        write: impl FnOnce(&mut Vec<u8>) -> Result<T, CodecError>,
+    ) -> Result<T, EncodeError> {
+        write(&mut self.bytes).map_err(|error| self.error(EncodeErrorKind::Codec(error)))
+    }
+
+    fn fields(&mut self, fields: &FieldList, value: &PacketValue) -> Result<(), EncodeError> {
+        let supplied: &[(String, PacketValue)] =
+            value.as_struct().ok_or_else(|| self.invalid("a struct"))?;
+        let find = |name: &str| {
+            supplied
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value)
+        };
+
+        let mut encoded: Vec<(String, PacketValue)> = vec![];
+        for field in &fields.0 {
+            self.path.push(field.name.0.clone());
+            let find_encoded = |name: &Identifier| {
+                encoded
+                    .iter()
+                    .find(|(field, _)| field == &name.0)
+                    .and_then(|(_, value)| value.literal())
+            };
+            let present: bool =
+                present(self.protocol, field, fields, find_encoded).map_err(|condition| {
+                    self.error(EncodeErrorKind::InvalidCondition(condition.to_string()))
+                })?;
+            if present {
+                let value: PacketValue = match &field.value {
+                    Some(value) => self.computed(field, value, fields, &encoded, supplied)?,
+                    None => find(&field.name.0)
+                        .cloned()
+                        .ok_or_else(|| self.error(EncodeErrorKind::MissingField))?,
+                };
+                // Enums given by name or value are kept as their variant, so conditions compare
+                // the values of the specification
+                let value: PacketValue = match field
+                    .ty
+                    .referenced()
+                    .and_then(|name| self.protocol.get_enum(name))
+                    .and_then(|e| variant(e, &value))
+                {
+                    Some(variant) => PacketValue::Enum {
+                        variant: variant.name.0.clone(),
+                        value: variant.value.unwrap_or_default() as i128,
+                    },
+                    None => value,
+                };
+                self.value(&field.ty, &value, linked(field, fields))?;
+                encoded.push((field.name.0.clone(), value));
+            }
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    /// The value of a field that is set to a value in the specification.
+    fn computed(
+        &self,
+        field: &Field,
+        value: &Value,
+        fields: &FieldList,
+        encoded: &[(String, PacketValue)],
+        supplied: &[(String, PacketValue)],
+    ) -> Result<PacketValue, EncodeError> {
+        let find = |name: &Identifier| {
+            encoded
+                .iter()
+                .chain(supplied)
+                .find(|(field, _)| field == &name.0)
+                .map(|(_, value)| value)
+        };
+
+        match value {
+            Value::Length(name, _) => {
+                let Some(target) = find(name) else {
+                    // Lengths of fields that are absent are -1, but required fields must be supplied
+                    return match fields.0.iter().find(|field| &field.name == name) {
+                        Some(target) if target.conditions.is_empty() => Err(EncodeError {
+                            kind: EncodeErrorKind::MissingField,
+                            path: self.path[..self.path.len() - 1]
+                                .iter()
+                                .chain([&name.0])
+                                .cloned()
+                                .collect::<Vec<String>>()
+                                .join("."),
+                        }),
+                        _ => Ok(PacketValue::Integer(-1)),
+                    };
+                };
+                let nbt: bool = fields
+                    .0
+                    .iter()
+                    .any(|field| &field.name == name && field.ty == Type::BaseType(BaseType::Nbt));
+                match target {
+                    // The NBT is compressed again when it is encoded, which gives the same bytes
+                    target if nbt => nbt::write_compressed(self.nbt(target)?.as_ref())
+                        .map(|bytes| PacketValue::Integer(bytes.len() as i128))
+                        .map_err(|error| self.error(EncodeErrorKind::Codec(error))),
+                    PacketValue::List(values) => Ok(PacketValue::Integer(values.len() as i128)),
+                    PacketValue::Bytes(bytes) => Ok(PacketValue::Integer(bytes.len() as i128)),
+                    _ => Err(self.invalid("a list, bytes or NBT for the length")),
+                }
+            }
+            Value::Literal(literal, _) => Ok(literal_value(literal)),
+            Value::Identifier(name, _) => {
+                if let Some(value) = find(name) {
+                    return Ok(value.clone());
+                }
+                if let Some(e) = field
+                    .ty
+                    .referenced()
+                    .and_then(|ty| self.protocol.get_enum(ty))
+                {
+                    if let Some(variant) = e.variant(name) {
+                        return Ok(PacketValue::Enum {
+                            variant: variant.name.0.clone(),
+                            value: variant.value.unwrap_or_default() as i128,
+                        });
+                    }
+                }
+                self.protocol
+                    .get_constant(name)
+                    .map(|constant| literal_value(&constant.value))
+                    .ok_or_else(|| self.error(EncodeErrorKind::UnknownConstant(name.clone())))
+            }
+        }
+    }
+
+    fn value(&mut self, ty: &Type, value: &PacketValue, linked: bool) -> Result<(), EncodeError> {
+        match ty {
+            Type::BaseType(base_type) => self.base_type(base_type, value, linked),
+            Type::CustomType(name) => {
+                if let Some(e) = self.protocol.get_enum(name) {
+                    if !matches!(
+                        value,
+                        PacketValue::Enum { .. } | PacketValue::String(_) | PacketValue::Integer(_)
+                    ) {
+                        return Err(self.invalid("an enum"));
+                    }
+                    let Some(variant) = variant(e, value) else {
+                        return Err(self.error(EncodeErrorKind::UnknownVariant {
+                            name: name.clone(),
+                            variant: value.to_string(),
+                        }));
+                    };
+                    self.integer(&e.ty, variant.value.unwrap_or_default() as i128)
+                } else if let Some(custom_type) = self.protocol.get_type(name) {
+                    if self.depth >= MAX_DEPTH {
+                        return Err(self.error(EncodeErrorKind::TooDeep));
+                    }
+                    self.depth += 1;
+                    self.fields(&custom_type.fields, value)?;
+                    self.depth -= 1;
+                    Ok(())
+                } else {
+                    Err(self.error(EncodeErrorKind::UnknownType(name.clone())))
+                }
+            }
+        }
+    }
+
+    fn integer(&mut self, ty: &IntegerType, value: i128) -> Result<(), EncodeError> {
+        self.write(|bytes| codec::write_integer(bytes, ty, value))
+    }
+
+    fn base_type(
+        &mut self,
+        base_type: &BaseType,
+        value: &PacketValue,
+        linked: bool,
+    ) -> Result<(), EncodeError> {
+        use BaseType::*;
+        match (base_type, value) {
+            (Bool, PacketValue::Bool(value)) => codec::write_bool(&mut self.bytes, *value),
+            (Integer(ty), value) => {
+                let value: i128 = value
+                    .as_integer()
+                    .ok_or_else(|| self.invalid("an integer"))?;
+                self.integer(ty, value)?;
+            }
+            (F32, value) => {
+                let value: f64 = float(value).ok_or_else(|| self.invalid("a number"))?;
+                codec::write_f32(&mut self.bytes, value as f32);
+            }
+            (F64, value) => {
+                let value: f64 = float(value).ok_or_else(|| self.invalid("a number"))?;
+                codec::write_f64(&mut self.bytes, value);
+            }
+            (String { length }, PacketValue::String(value)) => {
+                let max: usize = length.unwrap_or(BaseType::MAX_STRING_LENGTH);
+                self.write(|bytes| codec::write_string(bytes, value, max))?;
+            }
+            (List { ty, length }, PacketValue::Bytes(bytes))
+                if **ty == Type::BaseType(Integer(IntegerType::U8)) =>
+            {
+                self.list_length(*length, bytes.len(), linked)?;
+                self.bytes.extend(bytes);
+            }
+            (List { ty, length }, PacketValue::List(values)) => {
+                self.list_length(*length, values.len(), linked)?;
+                for (i, value) in values.iter().enumerate() {
+                    self.path.push(i.to_string());
+                    self.value(ty, value, false)?;
+                    self.path.pop();
+                }
+            }
+            // NBT with a length field is gzip compressed, like item data in 1.7
+            (Nbt, value) if linked => {
+                let tag: Option<NamedTag> = self.nbt(value)?;
+                let compressed: Vec<u8> = self.write(|_| nbt::write_compressed(tag.as_ref()))?;
+                self.bytes.extend(compressed);
+            }
+            // Bytes are NBT that is already encoded
+            (Nbt, PacketValue::Bytes(bytes)) => self.bytes.extend(bytes),
+            (Nbt, value) => {
+                let tag: Option<NamedTag> = self.nbt(value)?;
+                self.write(|bytes| nbt::write_nbt(bytes, tag.as_ref()))?
+            }
+            (Bool, _) => return Err(self.invalid("a boolean")),
+            (String { .. }, _) => return Err(self.invalid("a string")),
+            (List { .. }, _) => return Err(self.invalid("a list")),
+        }
+        Ok(())
+    }
+
+    /// Write the length of a list, unless it is fixed or given by another field.
+    fn list_length(
+        &mut self,
+        length: Option<usize>,
+        count: usize,
+        linked: bool,
+    ) -> Result<(), EncodeError> {
+        match length {
+            Some(expected) if count != expected => {
+                Err(self.error(EncodeErrorKind::InvalidListLength {
+                    length: count,
+                    expected,
+                }))
+            }
+            None if !linked => self.write(|bytes| codec::write_length(bytes, count)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The tag of an NBT value, which can also be given as its uncompressed bytes,
+    /// like NBT fields deserialize with serde.
+    fn nbt(&self, value: &PacketValue) -> Result<Option<NamedTag>, EncodeError> {
+        let bytes: Vec<u8> = match value {
+            PacketValue::Nbt(tag) => return Ok(tag.clone()),
+            PacketValue::Bytes(bytes) => bytes.clone(),
+            PacketValue::List(values) => values
+                .iter()
+                .map(|value| value.as_integer().and_then(|byte| u8::try_from(byte).ok()))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| self.invalid("NBT or bytes"))?,
+            _ => return Err(self.invalid("NBT or bytes")),
+        };
+        let mut reader: Reader = Reader::new(&bytes);
+        let tag: Option<NamedTag> = nbt::read_nbt(&mut reader)
+            .map_err(|error| self.error(EncodeErrorKind::Codec(error)))?;
+        if !reader.remaining().is_empty() {
+            return Err(self.invalid("a single NBT tag"));
+        }
+        Ok(tag)
+    }
+}
+
+/// The variant of an enum that a value refers to by name or by value.
+fn variant<'a>(e: &'a Enum, value: &PacketValue) -> Option<&'a Variant> {
+    match value {
+        PacketValue::Enum { variant, .. } | PacketValue::String(variant) => {
+            e.variant(&Identifier(variant.clone()))
+        }
+        PacketValue::Integer(value) => e
+            .variants
+            .iter()
+            .find(|variant| variant.value.map(|v| v as i128) == Some(*value)),
+        _ => None,
+    }
+}
+
+/// Whether the length of a field is given by a field defined as `len(...)`.
+fn linked(field: &Field, fields: &FieldList) -> bool {
+    fields
+        .0
+        .iter()
+        .any(|other| matches!(&other.value, Some(Value::Length(name, _)) if name == &field.name))
+}
+
+fn float(value: &PacketValue) -> Option<f64> {
+    match value {
+        PacketValue::Float(value) => Some(*value),
+        PacketValue::Integer(value) => Some(*value as f64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode::decode,
+        nbt::{NamedTag, Nbt},
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    fn packet<'a>(protocol: &'a Protocol, name: &str) -> &'a Packet {
+        protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == name)
+            .unwrap()
+    }
+
+    fn fields(fields: Vec<(&str, PacketValue)>) -> PacketValue {
+        PacketValue::Struct(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            const PVN = 4
+            enum NextState(VarInt) {
+                Status = 1
+                Login
+            }
+            packet Handshake(serverbound, Handshake, 0x00) {
+                VarInt protocol_version = PVN
+                String[9] server_address
+                u16 server_port
+                NextState next_state
+            }
+            packet Tag(clientbound, Play, 0x31) {
+                i16 length = len(tag)
+                if (length != -1) {
+                    Nbt tag
+                }
+                u8 count = len(values)
+                List[i8] values
+            }
+            packet Next(serverbound, Handshake, 0x01) {
+                NextState next_state
+                if (next_state == Login) {
+                    String[9] name
+                }
+            }
+            type Node {
+                bool more
+                if (more) {
+                    Node next
+                }
+            }
+            packet Chain(clientbound, Play, 0x32) {
+                Node node
+            }
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let handshake: &Packet = packet(&protocol, "Handshake");
+        let tag: &Packet = packet(&protocol, "Tag");
+
+        let value: PacketValue = fields(vec![
+            ("server_address", "localhost".into()),
+            ("server_port", 25565.into()),
+            (
+                "next_state",
+                PacketValue::Enum {
+                    variant: "Login".to_string(),
+                    value: 2,
+                },
+            ),
+        ]);
+        let mut bytes: Vec<u8> = vec![0x04, 0x09];
+        bytes.extend(b"localhost");
+        bytes.extend([0x63, 0xdd, 0x02]);
+        assert_eq!(encode(&protocol, handshake, &value), Ok(bytes));
+
+        // Enums can be given by name, and conditions compare their values
+        let value: PacketValue = fields(vec![("next_state", "Login".into()), ("name", "a".into())]);
+        assert_eq!(
+            encode(&protocol, packet(&protocol, "Next"), &value),
+            Ok(vec![0x02, 0x01, b'a'])
+        );
+
+        // Bytes are uncompressed NBT, which is compressed for a length field
+        let value: PacketValue = fields(vec![
+            ("tag", PacketValue::Bytes(vec![0x00])),
+            ("values", vec![PacketValue::Integer(-1)].into()),
+        ]);
+        assert_eq!(
+            encode(&protocol, tag, &value),
+            Ok(vec![0x00, 0x00, 0x01, 0xff])
+        );
+        let value: PacketValue = fields(vec![("values", vec![].into())]);
+        assert_eq!(encode(&protocol, tag, &value), Ok(vec![0xff, 0xff, 0x00]));
+
+        let error = |packet: &Packet, value: Vec<(&str, PacketValue)>| {
+            encode(&protocol, packet, &fields(value)).unwrap_err()
+        };
+        assert_eq!(
+            error(tag, vec![]),
+            EncodeError {
+                kind: EncodeErrorKind::MissingField,
+                path: "values".to_string(),
+            }
+        );
+        assert_eq!(
+            error(handshake, vec![("server_address", "localhost".into())]).to_string(),
+            "Missing field in `server_port`"
+        );
+        assert_eq!(
+            error(
+                handshake,
+                vec![
+                    ("server_address", "localhost".into()),
+                    ("server_port", 65536.into()),
+                ]
+            ),
+            EncodeError {
+                kind: EncodeErrorKind::Codec(CodecError::OutOfRange {
+                    value: 65536,
+                    ty: IntegerType::U16,
+                }),
+                path: "server_port".to_string(),
+            }
+        );
+        assert_eq!(
+            error(
+                handshake,
+                vec![("server_address", "localhost:25565".into())]
+            )
+            .kind,
+            EncodeErrorKind::Codec(CodecError::StringTooLong { length: 15, max: 9 })
+        );
+        assert_eq!(
+            error(
+                tag,
+                vec![("values", vec![PacketValue::Integer(0); 256].into())]
+            ),
+            EncodeError {
+                kind: EncodeErrorKind::Codec(CodecError::OutOfRange {
+                    value: 256,
+                    ty: IntegerType::U8,
+                }),
+                path: "count".to_string(),
+            }
+        );
+        assert_eq!(
+            error(tag, vec![("values", vec![true.into()].into())]),
+            EncodeError {
+                kind: EncodeErrorKind::InvalidValue {
+                    expected: "an integer".to_string(),
+                },
+                path: "values.0".to_string(),
+            }
+        );
+
+        // Types that contain themselves can't nest without limit
+        let mut node: PacketValue = fields(vec![("more", false.into())]);
+        for _ in 0..MAX_DEPTH {
+            node = fields(vec![("more", true.into()), ("next", node)]);
+        }
+        let chain: &Packet = packet(&protocol, "Chain");
+        assert_eq!(
+            error(chain, vec![("node", node.clone())]).kind,
+            EncodeErrorKind::TooDeep
+        );
+        let next: PacketValue = node.get("next").unwrap().clone();
+        let bytes: Vec<u8> = encode(&protocol, chain, &fields(vec![("node", next)])).unwrap();
+        assert_eq!(bytes.len(), MAX_DEPTH);
+    }
+
+    /// A value for every field of a type that isn't computed, with conditional fields present when possible.
+    fn sample(protocol: &Protocol, ty: &Type) -> PacketValue {
+        match ty {
+            Type::BaseType(BaseType::Bool) => true.into(),
+            Type::BaseType(BaseType::Integer(ty)) => (*ty.range().end() as i128 / 2).into(),
+            Type::BaseType(BaseType::F32) | Type::BaseType(BaseType::F64) => 1.5.into(),
+            Type::BaseType(BaseType::String { .. }) => "sample".into(),
+            Type::BaseType(BaseType::List { ty, length }) => {
+                vec![sample(protocol, ty); length.unwrap_or(2)].into()
+            }
+            Type::BaseType(BaseType::Nbt) => PacketValue::Nbt(Some(NamedTag {
+                name: "".to_string(),
+                tag: Nbt::Compound(vec![("id".to_string(), Nbt::Short(1))]),
+            })),
+            Type::CustomType(name) => {
+                if let Some(e) = protocol.get_enum(name) {
+                    let variant = e.variants.last().unwrap();
+                    PacketValue::Enum {
+                        variant: variant.name.0.clone(),
+                        value: variant.value.unwrap() as i128,
+                    }
+                } else {
+                    let fields: &FieldList = &protocol.get_type(name).unwrap().fields;
+                    struct_sample(protocol, fields)
+                }
+            }
+        }
+    }
+
+    fn struct_sample(protocol: &Protocol, fields: &FieldList) -> PacketValue {
+        PacketValue::Struct(
+            fields
+                .0
+                .iter()
+                .filter(|field| field.value.is_none())
+                .map(|field| (field.name.0.clone(), sample(protocol, &field.ty)))
+                .collect(),
+        )
+    }
+
+    /// Check that a decoded value has the supplied values, besides the computed ones.
+    fn assert_contains(decoded: &PacketValue, value: &PacketValue, path: &str) {
+        match (decoded, value) {
+            (PacketValue::Struct(_), PacketValue::Struct(fields)) => {
+                for (name, value) in fields {
+                    let path: String = format!("{path}.{name}");
+                    assert_contains(decoded.get(name).expect(&path), value, &path);
+                }
+            }
+            (PacketValue::List(decoded), PacketValue::List(values)) => {
+                assert_eq!(decoded.len(), values.len(), "{path}");
+                for (i, (decoded, value)) in decoded.iter().zip(values).enumerate() {
+                    assert_contains(decoded, value, &format!("{path}.{i}"));
+                }
+            }
+            _ => assert_eq!(decoded, value, "{path}"),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+
+        for packet in &protocol.packets {
+            let value: PacketValue = struct_sample(&protocol, &packet.fields);
+            let bytes: Vec<u8> = encode(&protocol, packet, &value).unwrap();
+            let decoded: PacketValue = decode(&protocol, packet, &bytes).unwrap();
+            assert_eq!(encode(&protocol, packet, &decoded), Ok(bytes));
+            assert_contains(&decoded, &value, &packet.name.0);
+        }
+
+        let handshake: PacketValue = decode(
+            &protocol,
+            &protocol.packets[0],
+            &encode(
+                &protocol,
+                &protocol.packets[0],
+                &struct_sample(&protocol, &protocol.packets[0].fields),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(handshake.get("protocol_version"), Some(&4.into()));
+    }
+}
@@ -0,0 +1,96 @@
+//! AES-128 in CFB8 mode, which encrypts the connection after the login encryption handshake.
+//!
+//! Both the key and the initial vector are the shared secret from Encryption Response.
+//! The cipher is a stream cipher, so each direction keeps its own state across frames.
+
+use aes::{
+    cipher::{generic_array::GenericArray, BlockDecryptMut, BlockEncryptMut, KeyIvInit},
+    Aes128,
+};
+
+/// The length of the shared secret in bytes.
+pub const SECRET_SIZE: usize = 16;
+
+/// Encrypts outgoing bytes and decrypts incoming bytes of one end of a connection.
+#[derive(Debug, Clone)]
+pub struct Cipher {
+    encryptor: cfb8::Encryptor<Aes128>,
+    decryptor: cfb8::Decryptor<Aes128>,
+}
+impl Cipher {
+    /// A cipher keyed by the shared secret, as used by Minecraft.
+    pub fn new(secret: &[u8; SECRET_SIZE]) -> Self {
+        Cipher::with_iv(secret, secret)
+    }
+
+    /// A cipher with a separate initial vector.
+    pub fn with_iv(key: &[u8; SECRET_SIZE], iv: &[u8; SECRET_SIZE]) -> Self {
+        Cipher {
+            encryptor: cfb8::Encryptor::new(key.into(), iv.into()),
+            decryptor: cfb8::Decryptor::new(key.into(), iv.into()),
+        }
+    }
+
+    /// Encrypt bytes in place, continuing from the previously encrypted bytes.
+    pub fn encrypt(&mut self, bytes: &mut [u8]) {
+        for byte in bytes {
+            self.encryptor
+                .encrypt_block_mut(GenericArray::from_mut_slice(std::slice::from_mut(byte)));
+        }
+    }
+
+    /// Decrypt bytes in place, continuing from the previously decrypted bytes.
+    pub fn decrypt(&mut self, bytes: &mut [u8]) {
+        for byte in bytes {
+            self.decryptor
+                .decrypt_block_mut(GenericArray::from_mut_slice(std::slice::from_mut(byte)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cipher() {
+        // NIST SP 800-38A, F.3.7
+        let key: [u8; SECRET_SIZE] = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let iv: [u8; SECRET_SIZE] = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ];
+        let plaintext: [u8; 18] = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a, 0xae, 0x2d,
+        ];
+        let ciphertext: [u8; 18] = [
+            0x3b, 0x79, 0x42, 0x4c, 0x9c, 0x0d, 0xd4, 0x36, 0xba, 0xce, 0x9e, 0x0e, 0xd4, 0x58,
+            0x6a, 0x4f, 0x32, 0xb9,
+        ];
+
+        // The state carries over between calls
+        let mut cipher: Cipher = Cipher::with_iv(&key, &iv);
+        let mut bytes: [u8; 18] = plaintext;
+        cipher.encrypt(&mut bytes[..5]);
+        cipher.encrypt(&mut bytes[5..]);
+        assert_eq!(bytes, ciphertext);
+        cipher.decrypt(&mut bytes[..1]);
+        cipher.decrypt(&mut bytes[1..]);
+        assert_eq!(bytes, plaintext);
+
+        // Minecraft uses the secret as the initial vector, recorded with OpenSSL
+        let mut bytes: [u8; 18] = plaintext;
+        Cipher::new(&key).encrypt(&mut bytes);
+        assert_eq!(
+            bytes,
+            [
+                0x14, 0x11, 0x8e, 0x07, 0x10, 0xeb, 0x1b, 0x4c, 0xa3, 0x0a, 0xe2, 0xde, 0x24, 0x74,
+                0x7e, 0xfb, 0x61, 0x73,
+            ]
+        );
+    }
+}
@@ -1,33 +1,54 @@
 use specmc_base::{
-    ensure, ensure_tokens,
-    parse::{Identifier, Literal, Parse, ParseError},
+    ensure,
+    parse::{Identifier, Literal},
 };
 
-use crate::base::IntegerType;
+use crate::{
+    base::IntegerType,
+    ensure_tokens,
+    parse::{Parse, Span, TokenStream, KEYWORDS},
+    Error,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Variant {
     pub name: Identifier,
     pub value: Option<isize>,
+    /// Whether the value was assigned explicitly in the specification.
+    pub(crate) explicit: bool,
+    pub span: Span,
+}
+impl Variant {
+    /// A variant whose value, if any, is assigned explicitly.
+    pub fn new(name: Identifier, value: Option<isize>, span: Span) -> Self {
+        Variant {
+            name,
+            value,
+            explicit: value.is_some(),
+            span,
+        }
+    }
 }
 impl Parse for Variant {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let start: Span = tokens.next_span();
         let name: Identifier = Identifier::parse(tokens)?;
         let mut value: Option<isize> = None;
 
         if !tokens.is_empty() && tokens.last().unwrap() == "=" {
             tokens.pop();
+            let span: Span = tokens.next_span();
             let _value: Literal = Literal::parse(tokens)?;
             let Literal::Integer(_value) = _value else {
-                return Err(ParseError::InvalidToken {
-                    token: format!("{_value:?}"),
-                    error: "Invalid variant value".to_string(),
+                return Err(Error::InvalidVariantValue {
+                    value: _value,
+                    span,
                 });
             };
             value = Some(_value);
         }
 
-        Ok(Variant { name, value })
+        Ok(Variant::new(name, value, tokens.span_from(start)))
     }
 }
 
@@ -36,19 +57,42 @@ pub struct Enum {
     pub name: Identifier,
     pub ty: IntegerType,
     pub variants: Vec<Variant>,
+    pub span: Span,
+}
+impl Enum {
+    /// Find a variant by name.
+    pub fn variant(&self, name: &Identifier) -> Option<&Variant> {
+        self.variants.iter().find(|variant| &variant.name == name)
+    }
+
+    /// Check whether a literal is the value of one of the variants.
+    pub fn accepts(&self, literal: &Literal) -> bool {
+        let Literal::Integer(value) = literal else {
+            return false;
+        };
+        self.variants
+            .iter()
+            .any(|variant| variant.value == Some(*value))
+    }
 }
 impl Parse for Enum {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let start: Span = tokens.next_span();
         ensure_tokens!(tokens, "enum");
         let name: Identifier = Identifier::parse(tokens)?;
         ensure_tokens!(tokens, "(");
         let ty: IntegerType = IntegerType::parse(tokens)?;
-        ensure_tokens!(tokens, ")", "{");
+        ensure_tokens!(tokens, ")");
+        let open: Span = tokens.next_span();
+        ensure_tokens!(tokens, "{");
 
         let mut variants: Vec<Variant> = vec![];
         let mut values: Vec<isize> = vec![];
         let mut i: isize = 0;
-        while !tokens.is_empty() && tokens.last().unwrap() != "}" {
+        while tokens
+            .last()
+            .is_some_and(|token| token != "}" && !KEYWORDS.contains(&token.as_str()))
+        {
             let mut variant: Variant = Variant::parse(tokens)?;
 
             if let Some(value) = variant.value {
@@ -59,17 +103,18 @@ impl Parse for Enum {
 
             ensure!(
                 ty.check(i),
-                ParseError::InvalidToken {
-                    token: i.to_string(),
-                    error: "Enum has incompatible type".to_string(),
+                Error::EnumValueOutOfRange {
+                    value: i,
+                    ty,
+                    span: variant.span,
                 }
             );
 
             ensure!(
                 !values.contains(&i),
-                ParseError::InvalidToken {
-                    token: i.to_string(),
-                    error: "Enum has duplicate value".to_string(),
+                Error::DuplicateEnumValue {
+                    value: i,
+                    span: variant.span,
                 }
             );
 
@@ -78,15 +123,20 @@ impl Parse for Enum {
             i += 1;
         }
 
-        ensure_tokens!(tokens, "}");
+        tokens.close_block("enum", open)?;
 
-        Ok(Enum { name, ty, variants })
+        Ok(Enum {
+            name,
+            ty,
+            variants,
+            span: tokens.span_from(start),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use specmc_base::tokenize;
+    use crate::tokenize;
 
     use crate::test_parse;
 
@@ -94,48 +144,51 @@ mod tests {
 
     #[test]
     fn test_variant() {
-        let mut tokens: Vec<String> = tokenize!("A = 42 B C = -123 D = 1.5");
+        let mut tokens: TokenStream = tokenize!("A = 42 B C = -123 D = 1.5");
 
         test_parse!(
             tokens,
             Variant,
-            Ok(Variant {
-                name: Identifier("A".to_string()),
-                value: Some(42),
-            })
+            Ok(Variant::new(
+                Identifier("A".to_string()),
+                Some(42),
+                Span::default()
+            ))
         );
         test_parse!(
             tokens,
             Variant,
-            Ok(Variant {
-                name: Identifier("B".to_string()),
-                value: None,
-            })
+            Ok(Variant::new(
+                Identifier("B".to_string()),
+                None,
+                Span::default()
+            ))
         );
         test_parse!(
             tokens,
             Variant,
-            Ok(Variant {
-                name: Identifier("C".to_string()),
-                value: Some(-123),
-            })
+            Ok(Variant::new(
+                Identifier("C".to_string()),
+                Some(-123),
+                Span::default()
+            ))
         );
 
         test_parse!(
             tokens,
             Variant,
-            Err(ParseError::InvalidToken {
-                token: "Float(1.5)".to_string(),
-                error: "Invalid variant value".to_string(),
+            Err(Error::InvalidVariantValue {
+                value: Literal::Float(1.5),
+                span: Span::default(),
             })
         );
         assert!(tokens.is_empty());
-        test_parse!(tokens, Variant, Err(ParseError::EndOfFile));
+        test_parse!(tokens, Variant, Err(Error::EndOfFile));
     }
 
     #[test]
     fn test_enum() {
-        let mut tokens: Vec<String> = tokenize!(
+        let mut tokens: TokenStream = tokenize!(
             "
             enum A(VarInt) {
                 A = 42
@@ -170,51 +223,61 @@ mod tests {
                     Variant {
                         name: Identifier("A".to_string()),
                         value: Some(42),
+                        explicit: true,
+                        span: Span::default(),
                     },
                     Variant {
                         name: Identifier("B".to_string()),
                         value: Some(43),
+                        explicit: false,
+                        span: Span::default(),
                     },
                     Variant {
                         name: Identifier("C".to_string()),
                         value: Some(-123),
+                        explicit: true,
+                        span: Span::default(),
                     },
                     Variant {
                         name: Identifier("D".to_string()),
                         value: Some(1),
+                        explicit: true,
+                        span: Span::default(),
                     },
-                ]
+                ],
+                span: Span::default(),
             })
         );
 
         test_parse!(
             tokens,
             Enum,
-            Err(ParseError::InvalidToken {
-                token: "256".to_string(),
-                error: "Enum has incompatible type".to_string(),
+            Err(Error::EnumValueOutOfRange {
+                value: 256,
+                ty: IntegerType::U8,
+                span: Span::default(),
             })
         );
         assert!(tokens.pop().unwrap() == "}");
         test_parse!(
             tokens,
             Enum,
-            Err(ParseError::InvalidToken {
-                token: "42".to_string(),
-                error: "Enum has duplicate value".to_string(),
+            Err(Error::DuplicateEnumValue {
+                value: 42,
+                span: Span::default(),
             })
         );
         assert!(tokens.pop().unwrap() == "}");
         test_parse!(
             tokens,
             Enum,
-            Err(ParseError::InvalidToken {
-                token: "Float(42.0)".to_string(),
-                error: "Invalid variant value".to_string(),
+            Err(Error::InvalidVariantValue {
+                value: Literal::Float(42.0),
+                span: Span::default(),
             })
         );
         assert!(tokens.pop().unwrap() == "}");
         assert!(tokens.is_empty());
-        test_parse!(tokens, Enum, Err(ParseError::EndOfFile));
+        test_parse!(tokens, Enum, Err(Error::EndOfFile));
     }
 }
@@ -0,0 +1,433 @@
+//! Errors found while parsing and resolving specifications.
+
+use std::fmt::Display;
+
+use specmc_base::parse::{Identifier, Literal, ParseError};
+
+use crate::{base::IntegerType, parse::Span};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The specification ended in the middle of an item.
+    EndOfFile,
+    /// A different token was expected.
+    Expected {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    /// A block was opened but never closed.
+    /// `kind` is the keyword of the block, and `span` is where it should have been closed.
+    UnclosedBlock {
+        kind: String,
+        open: Span,
+        span: Span,
+    },
+    /// A `}` that doesn't close any block.
+    UnexpectedClose {
+        span: Span,
+    },
+    InvalidIdentifier {
+        name: String,
+        span: Span,
+    },
+    InvalidLiteral {
+        token: String,
+        span: Span,
+    },
+    InvalidIntegerType {
+        token: String,
+        span: Span,
+    },
+    InvalidType {
+        token: String,
+        span: Span,
+    },
+    InvalidListLength {
+        token: String,
+        span: Span,
+    },
+    InvalidDirection {
+        token: String,
+        span: Span,
+    },
+    InvalidPacketId {
+        token: String,
+        span: Span,
+    },
+    InvalidVariantValue {
+        value: Literal,
+        span: Span,
+    },
+    InvalidCondition {
+        token: String,
+        span: Span,
+    },
+    /// A default value that doesn't fit the type of its field.
+    IncompatibleDefault {
+        value: Literal,
+        span: Span,
+    },
+    /// An enum value that doesn't fit the type of its enum.
+    EnumValueOutOfRange {
+        value: isize,
+        ty: IntegerType,
+        span: Span,
+    },
+    DuplicateEnumValue {
+        value: isize,
+        span: Span,
+    },
+    /// A custom type that always contains itself, with the chain of types that leads back to it.
+    RecursiveType {
+        cycle: Vec<Identifier>,
+        span: Span,
+    },
+    DuplicateTypeName {
+        name: Identifier,
+        span: Span,
+    },
+    /// No enum has the state of a packet as a variant.
+    MissingStateEnum,
+    UnknownState {
+        name: Identifier,
+        span: Span,
+    },
+    DuplicatePacketName {
+        name: Identifier,
+        span: Span,
+    },
+    DuplicatePacketId {
+        id: u32,
+        span: Span,
+    },
+    UnknownType {
+        name: Identifier,
+        span: Span,
+    },
+    DuplicateField {
+        name: Identifier,
+        span: Span,
+    },
+    UnknownLengthField {
+        name: Identifier,
+        span: Span,
+    },
+    /// A field set to a name that is neither a field, a variant nor a constant.
+    UnknownConstant {
+        name: Identifier,
+        span: Span,
+    },
+    /// A `len()` value that refers to a field which is not a list or NBT.
+    InvalidLengthField {
+        name: Identifier,
+        span: Span,
+    },
+    LaterFieldInCondition {
+        name: Identifier,
+        span: Span,
+    },
+    UnknownFieldInCondition {
+        name: Identifier,
+        span: Span,
+    },
+    UnknownAttribute {
+        name: String,
+        span: Span,
+    },
+    /// A `@transition` field that is not an enum.
+    InvalidTransitionField {
+        name: Identifier,
+        span: Span,
+    },
+    /// A `@compression` field that is not an integer field of the packet.
+    InvalidCompressionField {
+        name: Identifier,
+        span: Span,
+    },
+    /// An error from a parser outside of this crate.
+    Other {
+        token: String,
+        error: String,
+    },
+}
+impl Error {
+    /// Where the error is, if known.
+    pub fn span(&self) -> Option<Span> {
+        use Error::*;
+        match self {
+            EndOfFile | MissingStateEnum | Other { .. } => None,
+            Expected { span, .. }
+            | UnclosedBlock { span, .. }
+            | UnexpectedClose { span }
+            | InvalidIdentifier { span, .. }
+            | InvalidLiteral { span, .. }
+            | InvalidIntegerType { span, .. }
+            | InvalidType { span, .. }
+            | InvalidListLength { span, .. }
+            | InvalidDirection { span, .. }
+            | InvalidPacketId { span, .. }
+            | InvalidVariantValue { span, .. }
+            | InvalidCondition { span, .. }
+            | IncompatibleDefault { span, .. }
+            | EnumValueOutOfRange { span, .. }
+            | DuplicateEnumValue { span, .. }
+            | RecursiveType { span, .. }
+            | DuplicateTypeName { span, .. }
+            | UnknownState { span, .. }
+            | DuplicatePacketName { span, .. }
+            | DuplicatePacketId { span, .. }
+            | UnknownType { span, .. }
+            | DuplicateField { span, .. }
+            | UnknownLengthField { span, .. }
+            | UnknownConstant { span, .. }
+            | InvalidLengthField { span, .. }
+            | LaterFieldInCondition { span, .. }
+            | UnknownFieldInCondition { span, .. }
+            | UnknownAttribute { span, .. }
+            | InvalidTransitionField { span, .. }
+            | InvalidCompressionField { span, .. } => Some(*span),
+        }
+    }
+
+    /// Move the error to another span, if it has one.
+    pub fn at(mut self, new: Span) -> Self {
+        use Error::*;
+        match &mut self {
+            EndOfFile | MissingStateEnum | Other { .. } => {}
+            Expected { span, .. }
+            | UnclosedBlock { span, .. }
+            | UnexpectedClose { span }
+            | InvalidIdentifier { span, .. }
+            | InvalidLiteral { span, .. }
+            | InvalidIntegerType { span, .. }
+            | InvalidType { span, .. }
+            | InvalidListLength { span, .. }
+            | InvalidDirection { span, .. }
+            | InvalidPacketId { span, .. }
+            | InvalidVariantValue { span, .. }
+            | InvalidCondition { span, .. }
+            | IncompatibleDefault { span, .. }
+            | EnumValueOutOfRange { span, .. }
+            | DuplicateEnumValue { span, .. }
+            | RecursiveType { span, .. }
+            | DuplicateTypeName { span, .. }
+            | UnknownState { span, .. }
+            | DuplicatePacketName { span, .. }
+            | DuplicatePacketId { span, .. }
+            | UnknownType { span, .. }
+            | DuplicateField { span, .. }
+            | UnknownLengthField { span, .. }
+            | UnknownConstant { span, .. }
+            | InvalidLengthField { span, .. }
+            | LaterFieldInCondition { span, .. }
+            | UnknownFieldInCondition { span, .. }
+            | UnknownAttribute { span, .. }
+            | InvalidTransitionField { span, .. }
+            | InvalidCompressionField { span, .. } => *span = new,
+        }
+        self
+    }
+
+    /// The offending token, as reported by [`ParseError::InvalidToken`].
+    pub fn token(&self) -> Option<String> {
+        use Error::*;
+        Some(match self {
+            EndOfFile => return None,
+            Expected { found: token, .. }
+            | InvalidLiteral { token, .. }
+            | InvalidIntegerType { token, .. }
+            | InvalidType { token, .. }
+            | InvalidListLength { token, .. }
+            | InvalidDirection { token, .. }
+            | InvalidPacketId { token, .. }
+            | InvalidCondition { token, .. }
+            | Other { token, .. }
+            | InvalidIdentifier { name: token, .. }
+            | UnknownAttribute { name: token, .. } => token.clone(),
+            UnclosedBlock { .. } => "{".to_string(),
+            UnexpectedClose { .. } => "}".to_string(),
+            InvalidVariantValue { value, .. } | IncompatibleDefault { value, .. } => {
+                format!("{value:?}")
+            }
+            EnumValueOutOfRange { value, .. } | DuplicateEnumValue { value, .. } => {
+                value.to_string()
+            }
+            RecursiveType { cycle, .. } => cycle
+                .iter()
+                .map(Identifier::to_string)
+                .collect::<Vec<String>>()
+                .join(" -> "),
+            MissingStateEnum => "State".to_string(),
+            DuplicatePacketId { id, .. } => format!("{id:#04X}"),
+            DuplicateTypeName { name, .. }
+            | UnknownState { name, .. }
+            | DuplicatePacketName { name, .. }
+            | UnknownType { name, .. }
+            | DuplicateField { name, .. }
+            | UnknownLengthField { name, .. }
+            | UnknownConstant { name, .. }
+            | InvalidLengthField { name, .. }
+            | LaterFieldInCondition { name, .. }
+            | UnknownFieldInCondition { name, .. }
+            | InvalidTransitionField { name, .. }
+            | InvalidCompressionField { name, .. } => name.to_string(),
+        })
+    }
+
+    /// A description of the error, without its position.
+    pub fn message(&self) -> String {
+        use Error::*;
+        match self {
+            EndOfFile => "Unexpected end of file".to_string(),
+            Expected { expected, .. } => format!("Expected {expected}"),
+            UnclosedBlock { kind, .. } => format!("Unclosed `{kind}` block"),
+            UnexpectedClose { .. } => "Unexpected `}`".to_string(),
+            InvalidIdentifier { name, .. } => {
+                if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                    "Identifiers can only contain letters, numbers, and underscores".to_string()
+                } else {
+                    "Identifiers must not start with a number and can only contain letters, numbers, and underscores".to_string()
+                }
+            }
+            InvalidLiteral { .. } => "Invalid literal".to_string(),
+            InvalidIntegerType { .. } => "Invalid integer type".to_string(),
+            InvalidType { .. } => "Invalid type".to_string(),
+            InvalidListLength { .. } => "Invalid list length".to_string(),
+            InvalidDirection { .. } => "Invalid direction".to_string(),
+            InvalidPacketId { .. } => "Invalid packet id".to_string(),
+            InvalidVariantValue { .. } => "Invalid variant value".to_string(),
+            InvalidCondition { .. } => "Invalid condition".to_string(),
+            IncompatibleDefault { .. } => "Incompatible default value".to_string(),
+            EnumValueOutOfRange { .. } => "Enum has incompatible type".to_string(),
+            DuplicateEnumValue { .. } => "Enum has duplicate value".to_string(),
+            RecursiveType { .. } => "Recursive type".to_string(),
+            DuplicateTypeName { .. } => "Duplicate type name".to_string(),
+            MissingStateEnum => "Missing enum of packet states".to_string(),
+            UnknownState { .. } => "Unknown state".to_string(),
+            DuplicatePacketName { .. } => "Duplicate packet name".to_string(),
+            DuplicatePacketId { .. } => "Duplicate packet id".to_string(),
+            UnknownType { .. } => "Unknown type".to_string(),
+            DuplicateField { .. } => "Duplicate field".to_string(),
+            UnknownLengthField { .. } => "Unknown length field".to_string(),
+            UnknownConstant { .. } => "Unknown constant".to_string(),
+            InvalidLengthField { .. } => "Invalid length field".to_string(),
+            LaterFieldInCondition { .. } => "Condition refers to a later field".to_string(),
+            UnknownFieldInCondition { .. } => "Unknown field in condition".to_string(),
+            UnknownAttribute { .. } => "Unknown attribute".to_string(),
+            InvalidTransitionField { .. } => "Transition field is not an enum".to_string(),
+            InvalidCompressionField { .. } => {
+                "Compression field is not an integer field".to_string()
+            }
+            Other { error, .. } => error.clone(),
+        }
+    }
+
+    /// Where the error is, like ` at 4:13 (opened at 2:24)`, or nothing if it has no position.
+    /// Tokens from `specmc_base::tokenize!` have no position.
+    fn location(&self) -> String {
+        let Some(span) = self.span().filter(|span| span.line > 0) else {
+            return String::new();
+        };
+        match self {
+            Error::UnclosedBlock { open, .. } => format!(" at {span} (opened at {open})"),
+            _ => format!(" at {span}"),
+        }
+    }
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())?;
+        // The token is already clear from these messages
+        let named: bool = matches!(
+            self,
+            Error::UnclosedBlock { .. } | Error::UnexpectedClose { .. } | Error::MissingStateEnum
+        );
+        if let Some(token) = self.token().filter(|_| !named) {
+            write!(f, ": {token}")?;
+        }
+        write!(f, "{}", self.location())
+    }
+}
+impl std::error::Error for Error {}
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        match error {
+            ParseError::EndOfFile => Error::EndOfFile,
+            ParseError::InvalidToken { token, error } => Error::Other { token, error },
+        }
+    }
+}
+impl From<Error> for ParseError {
+    fn from(error: Error) -> Self {
+        let Some(token) = error.token() else {
+            return ParseError::EndOfFile;
+        };
+        ParseError::InvalidToken {
+            token,
+            error: error.message() + &error.location(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error() {
+        let span: Span = Span {
+            start: 10,
+            end: 14,
+            line: 2,
+            column: 5,
+        };
+        let error: Error = Error::DuplicatePacketId { id: 3, span };
+        assert_eq!(error.span().unwrap().to_string(), "2:5");
+        assert_eq!(error.to_string(), "Duplicate packet id: 0x03 at 2:5");
+        assert_eq!(
+            ParseError::from(error),
+            ParseError::InvalidToken {
+                token: "0x03".to_string(),
+                error: "Duplicate packet id at 2:5".to_string(),
+            }
+        );
+
+        let error: Error = Error::UnclosedBlock {
+            kind: "enum".to_string(),
+            open: Span {
+                start: 2,
+                end: 3,
+                line: 1,
+                column: 3,
+            },
+            span,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Unclosed `enum` block at 2:5 (opened at 1:3)"
+        );
+        assert_eq!(
+            ParseError::from(error),
+            ParseError::InvalidToken {
+                token: "{".to_string(),
+                error: "Unclosed `enum` block at 2:5 (opened at 1:3)".to_string(),
+            }
+        );
+
+        assert_eq!(ParseError::from(Error::EndOfFile), ParseError::EndOfFile);
+        assert_eq!(Error::from(ParseError::EndOfFile), Error::EndOfFile);
+        let error: Error = Error::from(ParseError::InvalidToken {
+            token: "x".to_string(),
+            error: "Bad".to_string(),
+        });
+        assert_eq!(error.to_string(), "Bad: x");
+        assert_eq!(
+            ParseError::from(error),
+            ParseError::InvalidToken {
+                token: "x".to_string(),
+                error: "Bad".to_string(),
+            }
+        );
+    }
+}
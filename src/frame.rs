@@ -0,0 +1,563 @@
+//! Splitting a byte stream into packet frames, without doing any IO.
+//!
+//! Each frame is prefixed with its length as `VarInt`, and starts with the packet id as `VarInt`.
+//! Bytes are fed to a [`Framer`] as they arrive, and complete frames are pulled from it,
+//! so it works with any IO runtime.
+//!
+//! From 1.8 on, frames can be compressed once the server sends Set Compression.
+//! Then the length prefix is followed by the length of the uncompressed packet as `VarInt`,
+//! and the zlib compressed packet. Packets smaller than the threshold are not compressed,
+//! which is signaled with an uncompressed length of 0.
+//!
+//! With the `encryption` feature, the stream can be encrypted after the login encryption handshake.
+//! Bytes are decrypted as they are fed and frames are encrypted as they are written.
+
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use specmc_base::parse::Identifier;
+
+#[cfg(feature = "encryption")]
+use crate::encryption::{Cipher, SECRET_SIZE};
+use crate::{
+    codec::{self, CodecError, Reader},
+    packets::{Direction, Packet},
+    Protocol,
+};
+
+/// The maximum length of a frame, which is the largest length that fits in a 3 byte `VarInt`.
+pub const MAX_FRAME_SIZE: usize = (1 << 21) - 1;
+/// The maximum length of a compressed packet after decompressing it.
+pub const MAX_DECOMPRESSED_SIZE: usize = 1 << 23;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    /// A frame longer than the maximum frame size.
+    TooLarge { size: usize, max: usize },
+    /// A frame without a packet id.
+    MissingId,
+    /// A compressed packet smaller than the compression threshold.
+    BelowThreshold { size: usize, threshold: usize },
+    /// An invalid length prefix or packet id.
+    Codec(CodecError),
+}
+impl Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::TooLarge { size, max } => {
+                write!(f, "Frame of {size} bytes is larger than {max}")
+            }
+            FrameError::MissingId => write!(f, "Frame has no packet id"),
+            FrameError::BelowThreshold { size, threshold } => write!(
+                f,
+                "Compressed packet of {size} bytes is smaller than the threshold {threshold}"
+            ),
+            FrameError::Codec(error) => write!(f, "{error}"),
+        }
+    }
+}
+impl std::error::Error for FrameError {}
+impl From<CodecError> for FrameError {
+    fn from(error: CodecError) -> Self {
+        FrameError::Codec(error)
+    }
+}
+
+/// A packet id with the body of the packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub id: i32,
+    pub body: Vec<u8>,
+}
+impl Frame {
+    /// Find the packet of the frame in a state and direction.
+    pub fn dispatch<'a>(
+        self,
+        protocol: &'a Protocol,
+        state: &Identifier,
+        direction: &Direction,
+    ) -> Dispatched<'a> {
+        let packet: Option<&Packet> = u32::try_from(self.id)
+            .ok()
+            .and_then(|id| protocol.get_packet(state, direction, id));
+        match packet {
+            Some(packet) => Dispatched::Packet {
+                packet,
+                body: self.body,
+            },
+            None => Dispatched::Unknown(self),
+        }
+    }
+
+    /// The packet id followed by the body.
+    fn packet(&self) -> Vec<u8> {
+        let mut packet: Vec<u8> = vec![];
+        codec::write_var_int(&mut packet, self.id);
+        packet.extend(&self.body);
+        packet
+    }
+
+    /// Write the frame with its length prefix.
+    pub fn write(&self, bytes: &mut Vec<u8>) -> Result<(), FrameError> {
+        write_prefixed(bytes, &self.packet())
+    }
+
+    /// Write the frame in the compressed format.
+    /// Packets of at least `threshold` bytes are compressed.
+    pub fn write_compressed(
+        &self,
+        bytes: &mut Vec<u8>,
+        threshold: usize,
+    ) -> Result<(), FrameError> {
+        let packet: Vec<u8> = self.packet();
+        let mut frame: Vec<u8> = vec![];
+        if packet.len() < threshold {
+            codec::write_length(&mut frame, 0)?;
+            frame.extend(packet);
+        } else {
+            codec::write_length(&mut frame, packet.len())?;
+            let mut encoder: ZlibEncoder<Vec<u8>> = ZlibEncoder::new(frame, Compression::default());
+            frame = encoder
+                .write_all(&packet)
+                .and_then(|_| encoder.finish())
+                .map_err(|_| CodecError::InvalidCompression)?;
+        }
+        write_prefixed(bytes, &frame)
+    }
+}
+
+fn write_prefixed(bytes: &mut Vec<u8>, frame: &[u8]) -> Result<(), FrameError> {
+    if frame.len() > MAX_FRAME_SIZE {
+        return Err(FrameError::TooLarge {
+            size: frame.len(),
+            max: MAX_FRAME_SIZE,
+        });
+    }
+    codec::write_length(bytes, frame.len())?;
+    bytes.extend(frame);
+    Ok(())
+}
+
+/// A frame matched to the packet it belongs to.
+/// Frames with ids that aren't part of the protocol are kept as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dispatched<'a> {
+    Packet { packet: &'a Packet, body: Vec<u8> },
+    Unknown(Frame),
+}
+
+/// Collects bytes until they form complete frames.
+/// After an error, the stream can't be split into frames anymore.
+#[derive(Debug, Clone)]
+pub struct Framer {
+    buffer: Vec<u8>,
+    max_size: usize,
+    /// The compression threshold, if frames are compressed.
+    compression: Option<usize>,
+    #[cfg(feature = "encryption")]
+    cipher: Option<Cipher>,
+}
+impl Default for Framer {
+    fn default() -> Self {
+        Framer::new(MAX_FRAME_SIZE)
+    }
+}
+impl Framer {
+    pub fn new(max_size: usize) -> Self {
+        Framer {
+            buffer: vec![],
+            max_size,
+            compression: None,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+
+    /// Switch to compressed frames with the given threshold, or back to uncompressed frames.
+    /// Frames that are already buffered are read in the new format.
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compression = threshold;
+    }
+
+    pub fn compression(&self) -> Option<usize> {
+        self.compression
+    }
+
+    /// Encrypt the stream in both directions with the shared secret.
+    /// Bytes that are already buffered were received after the switch, so they are decrypted too.
+    #[cfg(feature = "encryption")]
+    pub fn enable_encryption(&mut self, secret: &[u8; SECRET_SIZE]) {
+        let mut cipher: Cipher = Cipher::new(secret);
+        cipher.decrypt(&mut self.buffer);
+        self.cipher = Some(cipher);
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Write a frame in the current format.
+    pub fn write(&mut self, frame: &Frame, bytes: &mut Vec<u8>) -> Result<(), FrameError> {
+        #[cfg(feature = "encryption")]
+        let start: usize = bytes.len();
+        match self.compression {
+            Some(threshold) => frame.write_compressed(bytes, threshold)?,
+            None => frame.write(bytes)?,
+        }
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.cipher {
+            cipher.encrypt(&mut bytes[start..]);
+        }
+        Ok(())
+    }
+
+    /// Add bytes received from the stream.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        #[cfg(feature = "encryption")]
+        let start: usize = self.buffer.len();
+        self.buffer.extend(bytes);
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.cipher {
+            cipher.decrypt(&mut self.buffer[start..]);
+        }
+    }
+
+    /// The number of bytes that don't form a complete frame yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Take the next complete frame, if there is one.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, FrameError> {
+        let mut reader: Reader = Reader::new(&self.buffer);
+        let size: usize = match reader.read_length() {
+            Ok(size) => size,
+            Err(CodecError::UnexpectedEnd) => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        if size > self.max_size {
+            return Err(FrameError::TooLarge {
+                size,
+                max: self.max_size,
+            });
+        }
+        let Ok(frame) = reader.read_bytes(size) else {
+            return Ok(None);
+        };
+
+        let frame: Frame = self.parse(frame)?;
+        let end: usize = reader.offset();
+        self.buffer.drain(..end);
+        Ok(Some(frame))
+    }
+
+    fn parse(&self, frame: &[u8]) -> Result<Frame, FrameError> {
+        let mut decompressed: Vec<u8> = vec![];
+        let mut packet: Reader = Reader::new(frame);
+        if let Some(threshold) = self.compression {
+            let size: usize = packet.read_length()?;
+            if size != 0 {
+                if size < threshold {
+                    return Err(FrameError::BelowThreshold { size, threshold });
+                }
+                if size > MAX_DECOMPRESSED_SIZE {
+                    return Err(FrameError::TooLarge {
+                        size,
+                        max: MAX_DECOMPRESSED_SIZE,
+                    });
+                }
+                // Reading one more byte than expected shows if the packet is too long
+                ZlibDecoder::new(packet.remaining())
+                    .take(size as u64 + 1)
+                    .read_to_end(&mut decompressed)
+                    .map_err(|_| CodecError::InvalidCompression)?;
+                if decompressed.len() != size {
+                    return Err(CodecError::InvalidCompression.into());
+                }
+                packet = Reader::new(&decompressed);
+            }
+        }
+
+        let id: i32 = match packet.read_var_int() {
+            Ok(id) => id,
+            Err(CodecError::UnexpectedEnd) => return Err(FrameError::MissingId),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Frame {
+            id,
+            body: packet.remaining().to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_framer() {
+        let mut bytes: Vec<u8> = vec![];
+        Frame {
+            id: 0x00,
+            body: vec![0x01, 0x02],
+        }
+        .write(&mut bytes)
+        .unwrap();
+        Frame {
+            id: 0x80,
+            body: vec![0x03; 200],
+        }
+        .write(&mut bytes)
+        .unwrap();
+        assert_eq!(bytes[..4], [0x03, 0x00, 0x01, 0x02]);
+        assert_eq!(bytes[4..8], [0xca, 0x01, 0x80, 0x01]);
+
+        // Frames are only returned once they are complete
+        let mut framer: Framer = Framer::default();
+        let mut frames: Vec<Frame> = vec![];
+        for byte in &bytes {
+            framer.feed(&[*byte]);
+            while let Some(frame) = framer.next_frame().unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(
+            frames,
+            vec![
+                Frame {
+                    id: 0x00,
+                    body: vec![0x01, 0x02],
+                },
+                Frame {
+                    id: 0x80,
+                    body: vec![0x03; 200],
+                },
+            ]
+        );
+        assert_eq!(framer.buffered(), 0);
+
+        let mut framer: Framer = Framer::default();
+        framer.feed(&bytes);
+        framer.feed(&[0x05, 0x00]);
+        assert_eq!(framer.next_frame().unwrap().unwrap().id, 0x00);
+        assert_eq!(framer.next_frame().unwrap().unwrap().id, 0x80);
+        assert_eq!(framer.next_frame(), Ok(None));
+        assert_eq!(framer.buffered(), 2);
+    }
+
+    #[test]
+    fn test_framer_errors() {
+        let mut framer: Framer = Framer::new(100);
+        framer.feed(&[0xc8, 0x01]);
+        assert_eq!(
+            framer.next_frame(),
+            Err(FrameError::TooLarge {
+                size: 200,
+                max: 100
+            })
+        );
+
+        let mut framer: Framer = Framer::default();
+        framer.feed(&[0x00]);
+        assert_eq!(framer.next_frame(), Err(FrameError::MissingId));
+
+        let mut framer: Framer = Framer::default();
+        framer.feed(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert_eq!(
+            framer.next_frame(),
+            Err(FrameError::Codec(CodecError::VarIntTooLong))
+        );
+
+        assert_eq!(
+            Frame {
+                id: 0,
+                body: vec![0; MAX_FRAME_SIZE],
+            }
+            .write(&mut vec![]),
+            Err(FrameError::TooLarge {
+                size: MAX_FRAME_SIZE + 1,
+                max: MAX_FRAME_SIZE,
+            })
+        );
+    }
+
+    #[test]
+    fn test_compression() {
+        let small: Frame = Frame {
+            id: 0x03,
+            body: vec![0x01],
+        };
+        let large: Frame = Frame {
+            id: 0x04,
+            body: vec![0x05; 300],
+        };
+
+        let mut bytes: Vec<u8> = vec![];
+        small.write(&mut bytes).unwrap();
+        let mut framer: Framer = Framer::default();
+        framer.set_compression(Some(64));
+        framer.write(&small, &mut bytes).unwrap();
+        framer.write(&large, &mut bytes).unwrap();
+        // Packets below the threshold have an uncompressed length of 0
+        assert_eq!(bytes[3..7], [0x03, 0x00, 0x03, 0x01]);
+        assert!(bytes.len() < 7 + 300);
+
+        // Compression is switched on after the first frame, like after Set Compression
+        let mut framer: Framer = Framer::default();
+        framer.feed(&bytes);
+        assert_eq!(framer.next_frame(), Ok(Some(small.clone())));
+        framer.set_compression(Some(64));
+        assert_eq!(framer.compression(), Some(64));
+        assert_eq!(framer.next_frame(), Ok(Some(small)));
+        assert_eq!(framer.next_frame(), Ok(Some(large.clone())));
+        assert_eq!(framer.next_frame(), Ok(None));
+
+        let compressed = |threshold: usize| {
+            let mut bytes: Vec<u8> = vec![];
+            large.write_compressed(&mut bytes, threshold).unwrap();
+            bytes
+        };
+        let mut framer: Framer = Framer::default();
+        framer.set_compression(Some(1000));
+        framer.feed(&compressed(64));
+        assert_eq!(
+            framer.next_frame(),
+            Err(FrameError::BelowThreshold {
+                size: 301,
+                threshold: 1000,
+            })
+        );
+
+        // The uncompressed length must match the compressed packet
+        let mut bytes: Vec<u8> = compressed(64);
+        assert_eq!(bytes[1..3], [0xad, 0x02]);
+        bytes[1] = 0xac;
+        let mut framer: Framer = Framer::default();
+        framer.set_compression(Some(64));
+        framer.feed(&bytes);
+        assert_eq!(
+            framer.next_frame(),
+            Err(FrameError::Codec(CodecError::InvalidCompression))
+        );
+
+        let mut framer: Framer = Framer::default();
+        framer.set_compression(Some(64));
+        framer.feed(&[0x06, 0x80, 0x80, 0x80, 0x08, 0x78, 0x9c]);
+        assert_eq!(
+            framer.next_frame(),
+            Err(FrameError::TooLarge {
+                size: 1 << 24,
+                max: MAX_DECOMPRESSED_SIZE,
+            })
+        );
+
+        let mut framer: Framer = Framer::default();
+        framer.set_compression(Some(64));
+        framer.feed(&[0x04, 0x80, 0x01, 0x00, 0x00]);
+        assert_eq!(
+            framer.next_frame(),
+            Err(FrameError::Codec(CodecError::InvalidCompression))
+        );
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encryption() {
+        let secret: [u8; SECRET_SIZE] = [0x2a; SECRET_SIZE];
+        let login: Frame = Frame {
+            id: 0x02,
+            body: vec![0x00, 0x01],
+        };
+        let play: Frame = Frame {
+            id: 0x01,
+            body: vec![0x07; 100],
+        };
+
+        // Encryption is switched on after the first frame, like after Encryption Response
+        let mut server: Framer = Framer::default();
+        let mut bytes: Vec<u8> = vec![];
+        server.write(&login, &mut bytes).unwrap();
+        server.enable_encryption(&secret);
+        assert!(server.is_encrypted());
+        server.write(&play, &mut bytes).unwrap();
+        server.write(&play, &mut bytes).unwrap();
+        assert_eq!(bytes[..4], [0x03, 0x02, 0x00, 0x01]);
+        assert_ne!(bytes[4..6], [0x65, 0x01]);
+
+        // Bytes of encrypted frames may arrive before encryption is enabled
+        let mut client: Framer = Framer::default();
+        client.feed(&bytes[..10]);
+        assert_eq!(client.next_frame(), Ok(Some(login.clone())));
+        client.enable_encryption(&secret);
+        for byte in &bytes[10..] {
+            client.feed(&[*byte]);
+        }
+        assert_eq!(client.next_frame(), Ok(Some(play.clone())));
+        assert_eq!(client.next_frame(), Ok(Some(play.clone())));
+        assert_eq!(client.next_frame(), Ok(None));
+
+        // Compression is applied before encryption
+        server.set_compression(Some(64));
+        client.set_compression(Some(64));
+        let mut bytes: Vec<u8> = vec![];
+        server.write(&play, &mut bytes).unwrap();
+        server.write(&login, &mut bytes).unwrap();
+        client.feed(&bytes);
+        assert_eq!(client.next_frame(), Ok(Some(play)));
+        assert_eq!(client.next_frame(), Ok(Some(login)));
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            packet KeepAlive(clientbound, Play, 0x00) {
+                i32 keep_alive_id
+            }
+            packet KeepAlive(serverbound, Play, 0x00) {
+                i32 keep_alive_id
+            }
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let play: Identifier = Identifier("Play".to_string());
+        let frame = |id: i32| Frame {
+            id,
+            body: vec![0x00, 0x00, 0x00, 0x2a],
+        };
+
+        assert_eq!(
+            frame(0x00).dispatch(&protocol, &play, &Direction::Serverbound),
+            Dispatched::Packet {
+                packet: &protocol.packets[1],
+                body: vec![0x00, 0x00, 0x00, 0x2a],
+            }
+        );
+        assert_eq!(
+            frame(0x01).dispatch(&protocol, &play, &Direction::Serverbound),
+            Dispatched::Unknown(frame(0x01))
+        );
+        assert_eq!(
+            frame(0x00).dispatch(
+                &protocol,
+                &Identifier("Login".to_string()),
+                &Direction::Serverbound
+            ),
+            Dispatched::Unknown(frame(0x00))
+        );
+        assert_eq!(
+            frame(-1).dispatch(&protocol, &play, &Direction::Serverbound),
+            Dispatched::Unknown(frame(-1))
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! Generating random packets, behind the `rand` feature.
+//!
+//! [`generate`] builds a [`PacketValue`] that can be encoded with [`encode`](crate::encode::encode):
+//! integers stay within their type, strings within their maximum length, enums use their variants,
+//! and conditional and computed fields follow the specification.
+//! The value only depends on the state of the random number generator,
+//! so a seeded generator reproduces it.
+
+use std::ops::RangeInclusive;
+
+use rand::Rng;
+use specmc_base::parse::Identifier;
+
+use crate::{
+    base::{BaseType, Field, FieldList, IntegerType, Value},
+    codec::integer_range,
+    decode::present,
+    encode::literal_value,
+    nbt::{self, NamedTag, Nbt},
+    packets::Packet,
+    types::Type,
+    value::PacketValue,
+    Protocol,
+};
+
+/// Limits of generated values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerateOptions {
+    /// The maximum number of elements of lists without a fixed length.
+    pub max_list_length: usize,
+    /// The maximum length of strings, if the specification allows longer ones.
+    pub max_string_length: usize,
+    /// How deeply custom types and NBT are nested before lists are left empty.
+    pub max_depth: usize,
+    /// The probability of picking an edge case, like the minimum or maximum of an integer
+    /// or an empty list, instead of a uniformly random value.
+    pub edge_case_probability: f64,
+}
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            max_list_length: 8,
+            max_string_length: 16,
+            max_depth: 4,
+            edge_case_probability: 0.1,
+        }
+    }
+}
+
+/// Generate a random value of a packet with the default options.
+pub fn generate(protocol: &Protocol, packet: &Packet, rng: &mut impl Rng) -> PacketValue {
+    generate_with(protocol, packet, rng, &GenerateOptions::default())
+}
+
+/// Generate a random value of a packet.
+pub fn generate_with(
+    protocol: &Protocol,
+    packet: &Packet,
+    rng: &mut impl Rng,
+    options: &GenerateOptions,
+) -> PacketValue {
+    Generator {
+        protocol,
+        options,
+        rng,
+        depth: 0,
+    }
+    .fields(&packet.fields)
+}
+
+struct Generator<'a, R> {
+    protocol: &'a Protocol,
+    options: &'a GenerateOptions,
+    rng: &'a mut R,
+    depth: usize,
+}
+impl<R: Rng> Generator<'_, R> {
+    fn edge_case(&mut self) -> bool {
+        self.rng.random_bool(self.options.edge_case_probability)
+    }
+
+    fn fields(&mut self, fields: &FieldList) -> PacketValue {
+        let mut generated: Vec<(String, PacketValue)> = vec![];
+        // Lengths chosen by `len(...)` fields for the lists after them
+        let mut lengths: Vec<(&Identifier, usize)> = vec![];
+        for field in &fields.0 {
+            let find = |name: &Identifier| {
+                generated
+                    .iter()
+                    .find(|(field, _)| field == &name.0)
+                    .and_then(|(_, value)| value.literal())
+            };
+            // Fields with conditions that can't be evaluated can't be encoded either
+            if present(self.protocol, field, fields, find) != Ok(true) {
+                continue;
+            }
+
+            let value: PacketValue = match &field.value {
+                Some(Value::Length(target, _)) => {
+                    let length: Option<usize> = self.length(field, target, fields);
+                    if let Some(length) = length {
+                        lengths.push((target, length));
+                    }
+                    // The actual length is filled in once the target is generated
+                    PacketValue::Integer(length.map_or(-1, |length| length as i128))
+                }
+                Some(value) => self.computed(field, value, &generated),
+                None => {
+                    let length: Option<usize> = lengths
+                        .iter()
+                        .find(|(target, _)| *target == &field.name)
+                        .map(|(_, length)| *length);
+                    self.value(&field.ty, length)
+                }
+            };
+            generated.push((field.name.0.clone(), value));
+        }
+
+        // Lengths are computed from their targets when encoding, and absent targets have length -1
+        for field in &fields.0 {
+            let Some(Value::Length(target, _)) = &field.value else {
+                continue;
+            };
+            let length: i128 = match generated.iter().find(|(name, _)| name == &target.0) {
+                Some((_, PacketValue::List(values))) => values.len() as i128,
+                Some((_, PacketValue::Nbt(tag))) => nbt::write_compressed(tag.as_ref())
+                    .expect("generated NBT can be written")
+                    .len() as i128,
+                _ => -1,
+            };
+            if let Some((_, value)) = generated.iter_mut().find(|(name, _)| name == &field.name.0) {
+                *value = PacketValue::Integer(length);
+            }
+        }
+        PacketValue::Struct(generated)
+    }
+
+    /// Choose the length of a list with a `len(...)` field.
+    /// Returns `None` to leave out the target, if it is conditional and the length can be -1.
+    fn length(&mut self, field: &Field, target: &Identifier, fields: &FieldList) -> Option<usize> {
+        let Type::BaseType(BaseType::Integer(ty)) = &field.ty else {
+            return Some(0);
+        };
+        let range: RangeInclusive<i128> = integer_range(ty);
+        let conditional: bool = fields
+            .0
+            .iter()
+            .any(|field| &field.name == target && !field.conditions.is_empty());
+        if conditional && range.contains(&-1) && self.rng.random_bool(0.5) {
+            return None;
+        }
+        let max: usize = usize::try_from(*range.end())
+            .unwrap_or(usize::MAX)
+            .min(self.list_length_limit());
+        Some(self.count(max))
+    }
+
+    /// The value of a field that is set to a value in the specification.
+    fn computed(
+        &mut self,
+        field: &Field,
+        value: &Value,
+        generated: &[(String, PacketValue)],
+    ) -> PacketValue {
+        let value: Option<PacketValue> = match value {
+            Value::Literal(literal, _) => Some(literal_value(literal)),
+            Value::Identifier(name, _) => generated
+                .iter()
+                .find(|(field, _)| field == &name.0)
+                .map(|(_, value)| value.clone())
+                .or_else(|| {
+                    let e = self.protocol.get_enum(field.ty.referenced()?)?;
+                    let variant = e.variant(name)?;
+                    Some(PacketValue::Integer(
+                        variant.value.unwrap_or_default() as i128
+                    ))
+                })
+                .or_else(|| {
+                    self.protocol
+                        .get_constant(name)
+                        .map(|constant| literal_value(&constant.value))
+                }),
+            Value::Length(..) => None,
+        };
+        match value {
+            Some(value) => self.coerce(&field.ty, value),
+            None => self.value(&field.ty, None),
+        }
+    }
+
+    /// Convert a value from the specification to the value it decodes to.
+    fn coerce(&self, ty: &Type, value: PacketValue) -> PacketValue {
+        match (ty, value) {
+            (Type::CustomType(name), PacketValue::Integer(value)) => {
+                let variant = self.protocol.get_enum(name).and_then(|e| {
+                    e.variants
+                        .iter()
+                        .find(|variant| variant.value.map(|v| v as i128) == Some(value))
+                });
+                match variant {
+                    Some(variant) => PacketValue::Enum {
+                        variant: variant.name.0.clone(),
+                        value,
+                    },
+                    None => PacketValue::Integer(value),
+                }
+            }
+            (Type::BaseType(BaseType::F32 | BaseType::F64), PacketValue::Integer(value)) => {
+                PacketValue::Float(value as f64)
+            }
+            (_, value) => value,
+        }
+    }
+
+    fn list_length_limit(&self) -> usize {
+        if self.depth >= self.options.max_depth {
+            0
+        } else {
+            self.options.max_list_length
+        }
+    }
+
+    /// A number of elements up to `max`, which is either end as an edge case.
+    fn count(&mut self, max: usize) -> usize {
+        if self.edge_case() {
+            if self.rng.random_bool(0.5) {
+                0
+            } else {
+                max
+            }
+        } else {
+            self.rng.random_range(0..=max)
+        }
+    }
+
+    fn value(&mut self, ty: &Type, length: Option<usize>) -> PacketValue {
+        match ty {
+            Type::BaseType(base_type) => self.base_type(base_type, length),
+            Type::CustomType(name) => {
+                if let Some(e) = self.protocol.get_enum(name) {
+                    if e.variants.is_empty() {
+                        return PacketValue::Integer(0);
+                    }
+                    let variant = &e.variants[self.rng.random_range(0..e.variants.len())];
+                    PacketValue::Enum {
+                        variant: variant.name.0.clone(),
+                        value: variant.value.unwrap_or_default() as i128,
+                    }
+                } else if let Some(custom_type) = self.protocol.get_type(name) {
+                    self.depth += 1;
+                    let value: PacketValue = self.fields(&custom_type.fields);
+                    self.depth -= 1;
+                    value
+                } else {
+                    PacketValue::Struct(vec![])
+                }
+            }
+        }
+    }
+
+    fn base_type(&mut self, base_type: &BaseType, length: Option<usize>) -> PacketValue {
+        use BaseType::*;
+        match base_type {
+            Bool => PacketValue::Bool(self.rng.random()),
+            Integer(ty) => PacketValue::Integer(self.integer(ty)),
+            F32 => {
+                let value: f32 = if self.edge_case() {
+                    [0.0, f32::MIN, f32::MAX][self.rng.random_range(0..3)]
+                } else {
+                    self.rng.random_range(-1e6..1e6)
+                };
+                PacketValue::Float(value as f64)
+            }
+            F64 => PacketValue::Float(if self.edge_case() {
+                [0.0, f64::MIN, f64::MAX][self.rng.random_range(0..3)]
+            } else {
+                self.rng.random_range(-1e6..1e6)
+            }),
+            String { length: max } => {
+                let max: usize = max
+                    .unwrap_or(BaseType::MAX_STRING_LENGTH)
+                    .min(self.options.max_string_length);
+                PacketValue::String(self.string(max))
+            }
+            List { ty, length: fixed } => {
+                let count: usize = match fixed.or(length) {
+                    Some(count) => count,
+                    None => {
+                        let max: usize = self.list_length_limit();
+                        self.count(max)
+                    }
+                };
+                self.depth += 1;
+                let values: Vec<PacketValue> = (0..count).map(|_| self.value(ty, None)).collect();
+                self.depth -= 1;
+                PacketValue::List(values)
+            }
+            Nbt => PacketValue::Nbt(if self.edge_case() {
+                None
+            } else {
+                Some(NamedTag {
+                    name: self.string(self.options.max_string_length),
+                    tag: self.compound(),
+                })
+            }),
+        }
+    }
+
+    fn integer(&mut self, ty: &IntegerType) -> i128 {
+        let range: RangeInclusive<i128> = integer_range(ty);
+        if self.edge_case() {
+            let cases: Vec<i128> = [*range.start(), *range.end(), 0, -1, 1]
+                .into_iter()
+                .filter(|value| range.contains(value))
+                .collect();
+            cases[self.rng.random_range(0..cases.len())]
+        } else {
+            self.rng.random_range(range)
+        }
+    }
+
+    /// A string of up to `max` UTF-16 code units, mostly ASCII.
+    fn string(&mut self, max: usize) -> String {
+        let length: usize = self.count(max);
+        let mut string: String = String::new();
+        let mut units: usize = 0;
+        while units < length {
+            let c: char = if self.edge_case() {
+                self.rng.random()
+            } else {
+                self.rng.random_range(' '..='~')
+            };
+            if units + c.len_utf16() > length {
+                break;
+            }
+            units += c.len_utf16();
+            string.push(c);
+        }
+        string
+    }
+
+    fn compound(&mut self) -> Nbt {
+        self.depth += 1;
+        let count: usize = self.count(self.list_length_limit());
+        let tags: Vec<(String, Nbt)> = (0..count)
+            .map(|_| {
+                let kind: u8 = self.rng.random_range(1..=12);
+                (self.string(self.options.max_string_length), self.tag(kind))
+            })
+            .collect();
+        self.depth -= 1;
+        Nbt::Compound(tags)
+    }
+
+    /// A random tag of the type with the given id.
+    fn tag(&mut self, kind: u8) -> Nbt {
+        let count = |generator: &mut Self| {
+            let max: usize = generator.list_length_limit();
+            generator.count(max)
+        };
+        match kind {
+            1 => Nbt::Byte(self.rng.random()),
+            2 => Nbt::Short(self.rng.random()),
+            3 => Nbt::Int(self.rng.random()),
+            4 => Nbt::Long(self.rng.random()),
+            5 => Nbt::Float(self.rng.random_range(-1e6..1e6)),
+            6 => Nbt::Double(self.rng.random_range(-1e6..1e6)),
+            7 => Nbt::ByteArray((0..count(self)).map(|_| self.rng.random()).collect()),
+            8 => Nbt::String(self.string(self.options.max_string_length)),
+            9 => {
+                // All elements of a list have the same type
+                let kind: u8 = self.rng.random_range(1..=12);
+                self.depth += 1;
+                let values: Vec<Nbt> = (0..count(self)).map(|_| self.tag(kind)).collect();
+                self.depth -= 1;
+                Nbt::List(values)
+            }
+            10 => self.compound(),
+            11 => Nbt::IntArray((0..count(self)).map(|_| self.rng.random()).collect()),
+            _ => Nbt::LongArray((0..count(self)).map(|_| self.rng.random()).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        decode::decode,
+        encode::encode,
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+
+        // Every generated packet can be encoded, and decodes to the same value
+        let options: GenerateOptions = GenerateOptions {
+            edge_case_probability: 0.3,
+            ..Default::default()
+        };
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        for packet in &protocol.packets {
+            for _ in 0..50 {
+                let value: PacketValue = generate_with(&protocol, packet, &mut rng, &options);
+                let bytes: Vec<u8> = encode(&protocol, packet, &value)
+                    .unwrap_or_else(|error| panic!("{}: {error} for {value}", packet.name));
+                assert_eq!(
+                    decode(&protocol, packet, &bytes),
+                    Ok(value),
+                    "{}",
+                    packet.name
+                );
+            }
+        }
+
+        // A seed reproduces a value
+        let packet: &Packet = protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == "PlayerBlockPlacement")
+            .unwrap();
+        let value = |seed: u64| generate(&protocol, packet, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(value(42), value(42));
+        assert_ne!(value(42), value(43));
+
+        // Conditional fields and lengths follow each other
+        let mut present: usize = 0;
+        for seed in 0..100 {
+            let value: PacketValue = value(seed);
+            let slot: &PacketValue = value.get("held_item").unwrap();
+            match slot.get("id") {
+                Some(PacketValue::Integer(-1)) => assert_eq!(slot.get("count"), None),
+                _ => {
+                    present += 1;
+                    let length: i128 = slot.get("data_length").unwrap().as_integer().unwrap();
+                    assert_eq!(length == -1, slot.get("data").is_none());
+                }
+            }
+        }
+        assert!(present > 50);
+
+        // Limits are respected
+        let options: GenerateOptions = GenerateOptions {
+            max_list_length: 2,
+            max_string_length: 3,
+            ..Default::default()
+        };
+        let packet: &Packet = protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == "EncryptionResponse")
+            .unwrap();
+        for seed in 0..20 {
+            let value: PacketValue = generate_with(
+                &protocol,
+                packet,
+                &mut StdRng::seed_from_u64(seed),
+                &options,
+            );
+            assert!(value.get("shared_secret").unwrap().as_list().unwrap().len() <= 2);
+        }
+        let packet: &Packet = protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == "ChatMessage")
+            .unwrap();
+        for seed in 0..20 {
+            let value: PacketValue = generate_with(
+                &protocol,
+                packet,
+                &mut StdRng::seed_from_u64(seed),
+                &options,
+            );
+            let message: &str = value.get("message").unwrap().as_str().unwrap();
+            assert!(message.encode_utf16().count() <= 3);
+        }
+    }
+}
@@ -1,63 +1,499 @@
 //! A library for parsing Minecraft protocol specification.
 
+pub mod analysis;
 pub mod base;
+pub mod bounds;
+pub mod codec;
+pub mod condition;
+pub mod connection;
+pub mod constants;
+pub mod decode;
+pub mod diagnostic;
+pub mod encode;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 pub mod enums;
+pub mod error;
+pub mod frame;
+#[cfg(feature = "rand")]
+pub mod generate;
+pub mod lint;
+pub mod nbt;
+#[cfg(feature = "tokio")]
+pub mod packet_codec;
 pub mod packets;
+pub mod parse;
+pub mod plan;
+pub mod resolved;
+#[cfg(feature = "serde")]
+pub mod serde;
 #[cfg(feature = "spec")]
 pub mod spec;
+pub mod trace;
 pub mod types;
+pub mod value;
 
-use specmc_base::parse::{Parse, ParseError};
+use specmc_base::parse::Identifier;
 
+pub(crate) use parse::ensure_tokens;
+#[cfg(test)]
+pub(crate) use parse::tokenize;
+
+use base::{BaseType, Field, Value};
+use constants::Constant;
+use diagnostic::Diagnostic;
 use enums::Enum;
-use packets::Packet;
-use types::CustomType;
+pub use error::Error;
+use packets::{Direction, Packet};
+use parse::{Parse, Span, TokenStream, KEYWORDS};
+use types::{CustomType, Type};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Protocol {
     pub enums: Vec<Enum>,
     pub types: Vec<CustomType>,
     pub packets: Vec<Packet>,
+    pub constants: Vec<Constant>,
+}
+impl Protocol {
+    /// Find an enum by name.
+    pub fn get_enum(&self, name: &Identifier) -> Option<&Enum> {
+        self.enums.iter().find(|e| &e.name == name)
+    }
+
+    /// Find a constant by name.
+    pub fn get_constant(&self, name: &Identifier) -> Option<&Constant> {
+        self.constants
+            .iter()
+            .find(|constant| &constant.name == name)
+    }
+
+    /// Find a custom type by name.
+    pub fn get_type(&self, name: &Identifier) -> Option<&CustomType> {
+        self.types.iter().find(|ty| &ty.name == name)
+    }
+
+    /// Find the packet with the given id in a state and direction.
+    pub fn get_packet(
+        &self,
+        state: &Identifier,
+        direction: &Direction,
+        id: u32,
+    ) -> Option<&Packet> {
+        self.packets.iter().find(|packet| {
+            &packet.state == state && &packet.direction == direction && packet.id == id
+        })
+    }
+
+    /// Find a custom type that always contains itself, directly or through other types.
+    /// Recursion through a conditional field or a list that may be empty ends, so it isn't a cycle.
+    /// Returns the chain of type names, starting and ending with the same type.
+    pub fn find_type_cycle(&self) -> Option<Vec<Identifier>> {
+        /// The type that a value of this type always contains.
+        fn contained(ty: &Type) -> Option<&Identifier> {
+            match ty {
+                Type::CustomType(name) => Some(name),
+                Type::BaseType(BaseType::List {
+                    ty,
+                    length: Some(length),
+                }) if *length > 0 => contained(ty),
+                Type::BaseType(_) => None,
+            }
+        }
+
+        fn visit<'a>(
+            protocol: &'a Protocol,
+            ty: &'a CustomType,
+            path: &mut Vec<&'a Identifier>,
+            done: &mut Vec<&'a Identifier>,
+        ) -> Option<Vec<Identifier>> {
+            if done.contains(&&ty.name) {
+                return None;
+            }
+            if let Some(start) = path.iter().position(|name| *name == &ty.name) {
+                let mut cycle: Vec<Identifier> =
+                    path[start..].iter().map(|name| (*name).clone()).collect();
+                cycle.push(ty.name.clone());
+                return Some(cycle);
+            }
+
+            path.push(&ty.name);
+            for field in ty
+                .fields
+                .0
+                .iter()
+                .filter(|field| field.conditions.is_empty())
+            {
+                let Some(other) = contained(&field.ty).and_then(|name| protocol.get_type(name))
+                else {
+                    continue;
+                };
+                if let Some(cycle) = visit(protocol, other, path, done) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            done.push(&ty.name);
+
+            None
+        }
+
+        let mut done: Vec<&Identifier> = vec![];
+        self.types
+            .iter()
+            .find_map(|ty| visit(self, ty, &mut vec![], &mut done))
+    }
+
+    /// Iterate over the fields of all custom types and packets.
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.types
+            .iter()
+            .map(|ty| &ty.fields)
+            .chain(self.packets.iter().map(|packet| &packet.fields))
+            .flat_map(|fields| &fields.0)
+    }
+
+    /// Parse a protocol, recovering from errors.
+    /// Invalid items and fields are skipped, so the protocol may be incomplete.
+    pub fn parse_recovering(tokens: TokenStream) -> (Protocol, Vec<Diagnostic>) {
+        let mut tokens: TokenStream = tokens.recovering();
+        let protocol: Protocol = Protocol::parse(&mut tokens)
+            .expect("recovering parsers record errors instead of returning them");
+        (protocol, tokens.take_diagnostics())
+    }
+
+    /// Check literal default values assigned to fields of enum or custom types.
+    /// Default values of base types are checked by [`Field::parse`].
+    fn check_defaults(&self, tokens: &mut TokenStream) -> Result<(), Error> {
+        for field in self.fields() {
+            let (Type::CustomType(name), Some(Value::Literal(literal, span))) =
+                (&field.ty, &field.value)
+            else {
+                continue;
+            };
+
+            let accepts: bool = match self.get_enum(name) {
+                Some(e) => e.accepts(literal),
+                None => self.get_type(name).is_none(),
+            };
+            if !accepts {
+                tokens.recover(Error::IncompatibleDefault {
+                    value: literal.clone(),
+                    span: *span,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
 }
 impl Parse for Protocol {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, specmc_base::parse::ParseError> {
+    /// If the stream is recovering, invalid items are recorded and skipped
+    /// up to the next `enum`, `type`, `packet` or `const` keyword, or attribute.
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
         let mut enums: Vec<Enum> = vec![];
         let mut types: Vec<CustomType> = vec![];
         let mut packets: Vec<Packet> = vec![];
+        let mut constants: Vec<Constant> = vec![];
         while !tokens.is_empty() {
-            match tokens.last().unwrap().as_str() {
-                "enum" => {
-                    enums.push(Enum::parse(tokens)?);
-                }
-                "type" => {
-                    types.push(CustomType::parse(tokens)?);
-                }
-                "packet" => {
-                    packets.push(Packet::parse(tokens)?);
+            let start: usize = tokens.len();
+            let result: Result<(), Error> = match tokens.last().unwrap().as_str() {
+                "enum" => Enum::parse(tokens).map(|e| enums.push(e)),
+                "type" => CustomType::parse(tokens).map(|ty| types.push(ty)),
+                "packet" | "@" => Packet::parse(tokens).map(|packet| packets.push(packet)),
+                "const" => Constant::parse(tokens).map(|constant| constants.push(constant)),
+                "}" => Err(Error::UnexpectedClose {
+                    span: tokens.next_span(),
+                }),
+                found => Err(Error::Expected {
+                    expected: "\"enum\", \"type\", \"packet\" or \"const\"".to_string(),
+                    found: found.to_string(),
+                    span: tokens.next_span(),
+                }),
+            };
+            if let Err(error) = result {
+                tokens.recover(error)?;
+                if tokens.len() == start {
+                    tokens.pop();
                 }
-                token => {
-                    return Err(ParseError::InvalidToken {
-                        token: token.to_string(),
-                        error: "Expected \"enum\", \"type\" or \"packet\"".to_string(),
-                    });
+                while tokens
+                    .last()
+                    .is_some_and(|token| !KEYWORDS.contains(&token.as_str()))
+                {
+                    tokens.pop();
                 }
             }
         }
 
-        Ok(Protocol {
+        let protocol: Protocol = Protocol {
             enums,
             types,
             packets,
-        })
+            constants,
+        };
+        protocol.check_defaults(tokens)?;
+        if let Some(cycle) = protocol.find_type_cycle() {
+            let span: Span = protocol.get_type(&cycle[0]).unwrap().span;
+            tokens.recover(Error::RecursiveType { cycle, span })?;
+        }
+
+        Ok(protocol)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    /// Parse a value and compare it to the expected one, ignoring spans.
+    /// Tests that check spans compare them directly.
     #[macro_export]
     macro_rules! test_parse {
-        ($tokens:ident, $ty:ty, $value:expr) => {
-            assert_eq!(<$ty>::parse(&mut $tokens), $value);
-        }
+        ($tokens:ident, $ty:ty, $value:expr) => {{
+            use $crate::parse::spans::ClearSpans;
+            let mut parsed: Result<$ty, $crate::Error> = <$ty>::parse(&mut $tokens);
+            parsed.clear_spans();
+            assert_eq!(parsed, $value);
+        }};
+    }
+
+    use specmc_base::parse::{Identifier, Literal};
+
+    use crate::{
+        diagnostic::Diagnostic,
+        parse::{Parse, Span, TokenStream},
+        tokenize, Error, Protocol,
+    };
+
+    #[test]
+    fn test_protocol_defaults() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum A(u8) {
+                X = 1
+                Y = 3
+            }
+            type T {
+                A a = 3
+            }
+            "
+        );
+        assert!(Protocol::parse(&mut tokens).is_ok());
+
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum A(u8) {
+                X = 1
+                Y = 3
+            }
+            packet P(serverbound, Play, 0x00) {
+                A a = 2
+            }
+            "
+        );
+        test_parse!(
+            tokens,
+            Protocol,
+            Err(Error::IncompatibleDefault {
+                value: Literal::Integer(2),
+                span: Span::default(),
+            })
+        );
+
+        let mut tokens: TokenStream = tokenize!(
+            "
+            type T {
+                bool b
+            }
+            packet P(serverbound, Play, 0x00) {
+                T t = true
+            }
+            "
+        );
+        test_parse!(
+            tokens,
+            Protocol,
+            Err(Error::IncompatibleDefault {
+                value: Literal::Boolean(true),
+                span: Span::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_protocol_cycle() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            type A {
+                List[B; 2] b
+            }
+            type B {
+                C c
+            }
+            type C {
+                A a
+            }
+            "
+        );
+        test_parse!(
+            tokens,
+            Protocol,
+            Err(Error::RecursiveType {
+                cycle: ["A", "B", "C", "A"]
+                    .map(|name| Identifier(name.to_string()))
+                    .to_vec(),
+                span: Span::default(),
+            })
+        );
+
+        let mut tokens: TokenStream = tokenize!(
+            "
+            type A {
+                B first
+                B second
+            }
+            type B {
+                i32 value
+            }
+            "
+        );
+        assert!(Protocol::parse(&mut tokens).is_ok());
+
+        // Recursion through a conditional field or a list that may be empty ends
+        let mut tokens: TokenStream = tokenize!(
+            "
+            type A {
+                bool more
+                if (more) {
+                    B b
+                }
+            }
+            type B {
+                List[C] c
+            }
+            type C {
+                A a
+            }
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        assert_eq!(protocol.find_type_cycle(), None);
+    }
+
+    #[test]
+    fn test_protocol_spec() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        assert!(protocol
+            .get_enum(&Identifier("State".to_string()))
+            .is_some());
+        assert!(protocol.resolve().is_ok());
+    }
+
+    #[test]
+    fn test_protocol_recovering() {
+        let tokens: TokenStream = tokenize!(
+            "
+            enum State(i32) {
+                Play
+            }
+            type T {
+                bool a
+                Unknown<> b
+                if (a {
+                    i32 c
+                }
+                i32 d
+            }
+            typo
+            packet P(sideways, Play, 0x00) {
+                i32 a
+            }
+            packet Q(serverbound, Play, 0x01) {
+                i32 a = 1.5
+                bool b
+            }
+            "
+        );
+        let (protocol, diagnostics): (Protocol, Vec<Diagnostic>) =
+            Protocol::parse_recovering(tokens);
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(Diagnostic::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "error: Identifiers must not start with a number and can only contain letters, numbers, and underscores at 7:24",
+                "error: Expected ) at 8:23",
+                "error: Expected \"enum\", \"type\", \"packet\" or \"const\" at 13:13",
+                "error: Invalid direction at 14:22",
+                "error: Incompatible default value at 18:25",
+            ]
+        );
+        assert_eq!(diagnostics[3].primary.span.line, 14);
+
+        assert_eq!(protocol.enums.len(), 1);
+        let fields: Vec<&str> = protocol.types[0]
+            .fields
+            .0
+            .iter()
+            .map(|field| field.name.0.as_str())
+            .collect();
+        assert_eq!(fields, vec!["a", "d"]);
+        assert_eq!(protocol.packets.len(), 1);
+        assert_eq!(protocol.packets[0].fields.0.len(), 1);
+
+        let mut tokens: TokenStream = tokenize!("typo");
+        assert!(Protocol::parse(&mut tokens).is_err());
+    }
+
+    #[test]
+    fn test_protocol_blocks() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            type A {
+                bool a
+            }
+            }
+            "
+        );
+        let error: Error = Protocol::parse(&mut tokens).unwrap_err();
+        assert_eq!(
+            error,
+            Error::UnexpectedClose {
+                span: Span {
+                    start: 71,
+                    end: 72,
+                    line: 5,
+                    column: 13,
+                }
+            }
+        );
+        assert_eq!(error.to_string(), "Unexpected `}` at 5:13");
+
+        let tokens: TokenStream = tokenize!(
+            "
+            enum E(u8) {
+                X
+            type A {
+                bool a
+            packet P(serverbound, Play, 0x00) {
+                i32 b
+            }
+            "
+        );
+        let (protocol, diagnostics): (Protocol, Vec<Diagnostic>) =
+            Protocol::parse_recovering(tokens);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(Diagnostic::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "error: Unclosed `enum` block at 4:13",
+                "error: Unclosed `type` block at 6:13",
+            ]
+        );
+        assert_eq!(protocol.packets.len(), 1);
+        assert_eq!(diagnostics[0].secondary[0].span.to_string(), "2:24");
     }
 }
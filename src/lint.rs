@@ -0,0 +1,423 @@
+//! Lints for style and suspicious patterns in specifications.
+//!
+//! Unlike parse errors, lints don't make a specification invalid.
+//! Each lint can be configured to be allowed, to warn or to deny.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    str::FromStr,
+};
+
+use specmc_base::parse::Identifier;
+
+use crate::{packets::Direction, parse::Span, Protocol};
+
+/// Names of the base types, which fields should not be named after.
+pub(crate) const BASE_TYPES: &[&str] = &[
+    "bool", "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "VarInt", "VarLong", "f32",
+    "f64", "String", "List", "Nbt",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Lint {
+    /// An enum or custom type that is never used by a field.
+    Unused,
+    /// An enum or custom type with the same name as a packet or state.
+    NameCollision,
+    /// An explicit enum value that is equal to the implicit one.
+    RedundantEnumValue,
+    /// Packet ids of a state and direction that don't form a contiguous range starting at 0.
+    NonContiguousPacketIds,
+    /// A field name that is not snake_case.
+    NonSnakeCaseField,
+    /// An enum, variant, custom type or packet name that is not PascalCase.
+    NonPascalCaseType,
+    /// A field with the same name as a type.
+    FieldShadowsType,
+}
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::Unused,
+        Lint::NameCollision,
+        Lint::RedundantEnumValue,
+        Lint::NonContiguousPacketIds,
+        Lint::NonSnakeCaseField,
+        Lint::NonPascalCaseType,
+        Lint::FieldShadowsType,
+    ];
+
+    /// The code used to refer to the lint.
+    pub fn code(&self) -> &'static str {
+        use Lint::*;
+        match self {
+            Unused => "unused",
+            NameCollision => "name_collision",
+            RedundantEnumValue => "redundant_enum_value",
+            NonContiguousPacketIds => "non_contiguous_packet_ids",
+            NonSnakeCaseField => "non_snake_case_field",
+            NonPascalCaseType => "non_pascal_case_type",
+            FieldShadowsType => "field_shadows_type",
+        }
+    }
+}
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.code() == s)
+            .ok_or_else(|| format!("Unknown lint: {s}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The level of each lint. Lints warn unless configured otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintConfig {
+    levels: HashMap<Lint, Level>,
+}
+impl LintConfig {
+    /// Set the level of a lint.
+    pub fn set(mut self, lint: Lint, level: Level) -> Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    /// Set the level of all lints.
+    pub fn set_all(mut self, level: Level) -> Self {
+        for lint in Lint::ALL {
+            self.levels.insert(lint, level);
+        }
+        self
+    }
+
+    pub fn allow(self, lint: Lint) -> Self {
+        self.set(lint, Level::Allow)
+    }
+
+    pub fn deny(self, lint: Lint) -> Self {
+        self.set(lint, Level::Deny)
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: Lint,
+    pub level: Level,
+    pub message: String,
+    /// Where the linted item is defined.
+    pub span: Span,
+}
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level: &str = match self.level {
+            Level::Deny => "error",
+            _ => "warning",
+        };
+        write!(f, "{level}[{}]: {}", self.lint, self.message)
+    }
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn is_pascal_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_')
+}
+
+impl Protocol {
+    /// Run all lints that are not allowed by the configuration.
+    pub fn lint(&self, config: &LintConfig) -> Vec<Warning> {
+        let mut warnings: Vec<Warning> = vec![];
+        let mut warn = |lint: Lint, span: Span, message: String| {
+            let level: Level = config.level(lint);
+            if level != Level::Allow {
+                warnings.push(Warning {
+                    lint,
+                    level,
+                    message,
+                    span,
+                });
+            }
+        };
+
+        let type_names: Vec<(&str, &Identifier, Span)> = self
+            .enums
+            .iter()
+            .map(|e| ("Enum", &e.name, e.span))
+            .chain(self.types.iter().map(|ty| ("Type", &ty.name, ty.span)))
+            .collect();
+        // Enums whose variants are the states of packets are used by the packets
+        let state_enums: Vec<&Identifier> = self
+            .enums
+            .iter()
+            .filter(|e| {
+                self.packets
+                    .iter()
+                    .any(|packet| e.variant(&packet.state).is_some())
+            })
+            .map(|e| &e.name)
+            .collect();
+        let states: Vec<&Identifier> = self
+            .enums
+            .iter()
+            .filter(|e| state_enums.contains(&&e.name))
+            .flat_map(|e| e.variants.iter().map(|variant| &variant.name))
+            .collect();
+
+        for (kind, name, span) in &type_names {
+            if !state_enums.contains(name)
+                && !self
+                    .fields()
+                    .any(|field| field.ty.referenced() == Some(name))
+            {
+                warn(Lint::Unused, *span, format!("{kind} {name} is never used"));
+            }
+            if self.packets.iter().any(|packet| &packet.name == *name) {
+                warn(
+                    Lint::NameCollision,
+                    *span,
+                    format!("{kind} {name} has the same name as a packet"),
+                );
+            }
+            if states.contains(name) {
+                warn(
+                    Lint::NameCollision,
+                    *span,
+                    format!("{kind} {name} has the same name as a state"),
+                );
+            }
+        }
+
+        for e in &self.enums {
+            let mut implicit: isize = 0;
+            for variant in &e.variants {
+                if variant.explicit && variant.value == Some(implicit) {
+                    warn(
+                        Lint::RedundantEnumValue,
+                        variant.span,
+                        format!(
+                            "Variant {}::{} is explicitly assigned its implicit value {implicit}",
+                            e.name, variant.name
+                        ),
+                    );
+                }
+                implicit = variant.value.unwrap_or(implicit) + 1;
+            }
+        }
+
+        let mut ids: BTreeMap<(&str, bool), Vec<(u32, Span)>> = BTreeMap::new();
+        for packet in &self.packets {
+            ids.entry((&packet.state.0, packet.direction == Direction::Serverbound))
+                .or_default()
+                .push((packet.id, packet.span));
+        }
+        for ((state, serverbound), packets) in ids {
+            let span: Span = packets[0].1;
+            let mut ids: Vec<u32> = packets.into_iter().map(|(id, _)| id).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            let missing: Vec<String> = (0..*ids.last().unwrap())
+                .filter(|id| ids.binary_search(id).is_err())
+                .map(|id| format!("{id:#04X}"))
+                .collect();
+            if !missing.is_empty() {
+                let direction: &str = if serverbound {
+                    "serverbound"
+                } else {
+                    "clientbound"
+                };
+                warn(
+                    Lint::NonContiguousPacketIds,
+                    span,
+                    format!(
+                        "Missing {direction} packet ids in state {state}: {}",
+                        missing.join(", ")
+                    ),
+                );
+            }
+        }
+
+        for (kind, name, span) in type_names.iter().copied().chain(
+            self.packets
+                .iter()
+                .map(|packet| ("Packet", &packet.name, packet.span)),
+        ) {
+            if !is_pascal_case(&name.0) {
+                warn(
+                    Lint::NonPascalCaseType,
+                    span,
+                    format!("{kind} {name} should have a PascalCase name"),
+                );
+            }
+        }
+        for e in &self.enums {
+            for variant in &e.variants {
+                if !is_pascal_case(&variant.name.0) {
+                    warn(
+                        Lint::NonPascalCaseType,
+                        variant.span,
+                        format!(
+                            "Variant {}::{} should have a PascalCase name",
+                            e.name, variant.name
+                        ),
+                    );
+                }
+            }
+        }
+
+        for field in self.fields() {
+            if !is_snake_case(&field.name.0) {
+                warn(
+                    Lint::NonSnakeCaseField,
+                    field.span,
+                    format!("Field {} should have a snake_case name", field.name),
+                );
+            }
+            if BASE_TYPES.contains(&field.name.0.as_str())
+                || type_names.iter().any(|(_, name, _)| **name == field.name)
+            {
+                warn(
+                    Lint::FieldShadowsType,
+                    field.span,
+                    format!("Field {} has the same name as a type", field.name),
+                );
+            }
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum State(i32) {
+                Handshake
+                Status
+                Play
+            }
+            enum Status(i8) {
+                Started = 0
+                Cancelled = 2
+                Finished = 3
+            }
+            enum Animation(i8) {
+                none
+            }
+            type Slot {
+                i16 id
+            }
+            type Unused_Type {
+                i16 id
+            }
+            packet Animation(serverbound, Play, 0x00) {
+                i32 entityId
+                Animation animation
+            }
+            packet Digging(serverbound, Play, 0x02) {
+                Status status
+                Slot Slot
+                i32 u8
+            }
+            packet Kick(clientbound, Play, 0x00) {}
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+
+        let messages: Vec<String> = protocol
+            .lint(&LintConfig::default())
+            .iter()
+            .map(Warning::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "warning[name_collision]: Enum Status has the same name as a state",
+                "warning[name_collision]: Enum Animation has the same name as a packet",
+                "warning[unused]: Type Unused_Type is never used",
+                "warning[redundant_enum_value]: Variant Status::Started is explicitly assigned its implicit value 0",
+                "warning[redundant_enum_value]: Variant Status::Finished is explicitly assigned its implicit value 3",
+                "warning[non_contiguous_packet_ids]: Missing serverbound packet ids in state Play: 0x01",
+                "warning[non_pascal_case_type]: Type Unused_Type should have a PascalCase name",
+                "warning[non_pascal_case_type]: Variant Animation::none should have a PascalCase name",
+                "warning[non_snake_case_field]: Field entityId should have a snake_case name",
+                "warning[non_snake_case_field]: Field Slot should have a snake_case name",
+                "warning[field_shadows_type]: Field Slot has the same name as a type",
+                "warning[field_shadows_type]: Field u8 has the same name as a type",
+            ]
+        );
+
+        let config: LintConfig = LintConfig::default()
+            .set_all(Level::Allow)
+            .deny(Lint::NonSnakeCaseField);
+        let warnings: Vec<Warning> = protocol.lint(&config);
+        assert_eq!(warnings.len(), 2);
+        assert!(
+            warnings
+                .iter()
+                .all(|warning| warning.lint == Lint::NonSnakeCaseField
+                    && warning.level == Level::Deny)
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "error[non_snake_case_field]: Field entityId should have a snake_case name"
+        );
+        assert_eq!(warnings[0].span.to_string(), "22:17");
+
+        // The state enum is used by packets, whatever its name
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum States(i32) {
+                Play
+            }
+            enum Unused(i32) {
+                Other
+            }
+            packet Kick(clientbound, Play, 0x00) {}
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let config: LintConfig = LintConfig::default()
+            .set_all(Level::Allow)
+            .set(Lint::Unused, Level::Warn);
+        let messages: Vec<String> = protocol
+            .lint(&config)
+            .iter()
+            .map(Warning::to_string)
+            .collect();
+        assert_eq!(messages, vec!["warning[unused]: Enum Unused is never used"]);
+
+        assert_eq!("unused".parse(), Ok(Lint::Unused));
+        assert!("unknown".parse::<Lint>().is_err());
+    }
+}
@@ -0,0 +1,698 @@
+//! Named Binary Tag values, and their binary format.
+//!
+//! Strings in NBT are encoded as modified UTF-8, like Java's `DataOutput::writeUTF`:
+//! `\0` takes two bytes and characters outside the BMP are encoded as surrogate pairs.
+
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{
+    base::IntegerType,
+    codec::{CodecError, Reader},
+};
+
+/// The maximum nesting of compounds and lists.
+pub const MAX_DEPTH: usize = 512;
+/// The maximum size of decompressed NBT.
+pub const MAX_DECOMPRESSED_SIZE: usize = 2 * 1024 * 1024;
+
+/// An NBT tag without its name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// A list of tags, which should all have the same type.
+    List(Vec<Nbt>),
+    /// Named tags, in the order they appear.
+    Compound(Vec<(String, Nbt)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+impl Nbt {
+    /// The id of the tag type.
+    pub fn tag_id(&self) -> u8 {
+        use Nbt::*;
+        match self {
+            Byte(_) => 1,
+            Short(_) => 2,
+            Int(_) => 3,
+            Long(_) => 4,
+            Float(_) => 5,
+            Double(_) => 6,
+            ByteArray(_) => 7,
+            String(_) => 8,
+            List(_) => 9,
+            Compound(_) => 10,
+            IntArray(_) => 11,
+            LongArray(_) => 12,
+        }
+    }
+
+    /// Find a tag of a compound by name.
+    pub fn get(&self, name: &str) -> Option<&Nbt> {
+        match self {
+            Nbt::Compound(tags) => tags
+                .iter()
+                .find(|(tag_name, _)| tag_name == name)
+                .map(|(_, tag)| tag),
+            _ => None,
+        }
+    }
+}
+
+/// Strings that don't need to be quoted in SNBT.
+fn is_bare(string: &str) -> bool {
+    !string.is_empty()
+        && string
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.+".contains(c))
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, string: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' | '\\' => write!(f, "\\{c}")?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn write_array<T: Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    prefix: &str,
+    values: &[T],
+    suffix: &str,
+) -> std::fmt::Result {
+    write!(f, "[{prefix};")?;
+    for (i, value) in values.iter().enumerate() {
+        let separator: &str = if i == 0 { " " } else { ", " };
+        write!(f, "{separator}{value}{suffix}")?;
+    }
+    write!(f, "]")
+}
+
+/// Tags are displayed as SNBT, the text format used in commands.
+impl Display for Nbt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Nbt::Byte(value) => write!(f, "{value}b"),
+            Nbt::Short(value) => write!(f, "{value}s"),
+            Nbt::Int(value) => write!(f, "{value}"),
+            Nbt::Long(value) => write!(f, "{value}L"),
+            Nbt::Float(value) => write!(f, "{value:?}f"),
+            Nbt::Double(value) => write!(f, "{value:?}d"),
+            Nbt::ByteArray(values) => write_array(f, "B", values, "b"),
+            Nbt::String(value) => write_string(f, value),
+            Nbt::List(tags) => {
+                write!(f, "[")?;
+                for (i, tag) in tags.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{tag}")?;
+                }
+                write!(f, "]")
+            }
+            Nbt::Compound(tags) => {
+                write!(f, "{{")?;
+                for (i, (name, tag)) in tags.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    if is_bare(name) {
+                        write!(f, "{name}")?;
+                    } else {
+                        write_string(f, name)?;
+                    }
+                    write!(f, ": {tag}")?;
+                }
+                write!(f, "}}")
+            }
+            Nbt::IntArray(values) => write_array(f, "I", values, ""),
+            Nbt::LongArray(values) => write_array(f, "L", values, "L"),
+        }
+    }
+}
+
+/// The root of an NBT structure, which is a named tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedTag {
+    pub name: String,
+    pub tag: Nbt,
+}
+impl Display for NamedTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.name.is_empty() {
+            write_string(f, &self.name)?;
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.tag)
+    }
+}
+
+fn read_length(reader: &mut Reader) -> Result<usize, CodecError> {
+    let length: i128 = reader.read_integer(&IntegerType::I32)?;
+    usize::try_from(length).map_err(|_| CodecError::InvalidLength(length))
+}
+
+/// Decode modified UTF-8, passing each UTF-16 code unit to `unit`.
+fn mutf8_units(bytes: &[u8], mut unit: impl FnMut(u16)) -> Result<(), CodecError> {
+    let mut i: usize = 0;
+    while i < bytes.len() {
+        let continuation = |j: usize| {
+            bytes
+                .get(i + j)
+                .filter(|byte| *byte & 0xc0 == 0x80)
+                .map(|byte| (byte & 0x3f) as u16)
+                .ok_or(CodecError::InvalidUtf8)
+        };
+        let byte: u8 = bytes[i];
+        let (value, size): (u16, usize) = match byte {
+            0x01..=0x7f => (byte as u16, 1),
+            0xc0..=0xdf => (((byte & 0x1f) as u16) << 6 | continuation(1)?, 2),
+            0xe0..=0xef => (
+                ((byte & 0x0f) as u16) << 12 | continuation(1)? << 6 | continuation(2)?,
+                3,
+            ),
+            _ => return Err(CodecError::InvalidUtf8),
+        };
+        unit(value);
+        i += size;
+    }
+    Ok(())
+}
+
+fn read_mutf8(reader: &mut Reader) -> Result<String, CodecError> {
+    let length: usize = reader.read_integer(&IntegerType::U16)? as usize;
+    let mut units: Vec<u16> = vec![];
+    mutf8_units(reader.read_bytes(length)?, |unit| units.push(unit))?;
+    String::from_utf16(&units).map_err(|_| CodecError::InvalidUtf8)
+}
+
+/// Check a modified UTF-8 string like [`read_mutf8`], without allocating it.
+fn skip_mutf8(reader: &mut Reader) -> Result<(), CodecError> {
+    let length: usize = reader.read_integer(&IntegerType::U16)? as usize;
+    // Surrogates must come in pairs
+    let mut high: bool = false;
+    let mut valid: bool = true;
+    mutf8_units(reader.read_bytes(length)?, |unit| match unit {
+        0xd800..=0xdbff => {
+            valid &= !high;
+            high = true;
+        }
+        0xdc00..=0xdfff => {
+            valid &= high;
+            high = false;
+        }
+        _ => valid &= !high,
+    })?;
+    if valid && !high {
+        Ok(())
+    } else {
+        Err(CodecError::InvalidUtf8)
+    }
+}
+
+/// Check the id of a named tag before its name is read.
+fn check_id(id: u8) -> Result<(), CodecError> {
+    match id {
+        1..=12 => Ok(()),
+        id => Err(CodecError::InvalidNbtTag(id)),
+    }
+}
+
+fn read_payload(reader: &mut Reader, id: u8, depth: usize) -> Result<Nbt, CodecError> {
+    fn array<T>(
+        reader: &mut Reader,
+        mut read: impl FnMut(&mut Reader) -> Result<T, CodecError>,
+    ) -> Result<Vec<T>, CodecError> {
+        let length: usize = read_length(reader)?;
+        // The length comes from the input, so it isn't used to preallocate
+        let mut values: Vec<T> = vec![];
+        for _ in 0..length {
+            values.push(read(reader)?);
+        }
+        Ok(values)
+    }
+
+    let integer = |reader: &mut Reader, ty: IntegerType| reader.read_integer(&ty);
+    Ok(match id {
+        1 => Nbt::Byte(integer(reader, IntegerType::I8)? as i8),
+        2 => Nbt::Short(integer(reader, IntegerType::I16)? as i16),
+        3 => Nbt::Int(integer(reader, IntegerType::I32)? as i32),
+        4 => Nbt::Long(integer(reader, IntegerType::I64)? as i64),
+        5 => Nbt::Float(reader.read_f32()?),
+        6 => Nbt::Double(reader.read_f64()?),
+        7 => {
+            let length: usize = read_length(reader)?;
+            Nbt::ByteArray(
+                reader
+                    .read_bytes(length)?
+                    .iter()
+                    .map(|byte| *byte as i8)
+                    .collect(),
+            )
+        }
+        8 => Nbt::String(read_mutf8(reader)?),
+        9 | 10 if depth >= MAX_DEPTH => return Err(CodecError::NbtTooDeep),
+        9 => {
+            let element: u8 = integer(reader, IntegerType::U8)? as u8;
+            Nbt::List(array(reader, |reader| {
+                read_payload(reader, element, depth + 1)
+            })?)
+        }
+        10 => {
+            let mut tags: Vec<(String, Nbt)> = vec![];
+            loop {
+                let id: u8 = integer(reader, IntegerType::U8)? as u8;
+                if id == 0 {
+                    break;
+                }
+                check_id(id)?;
+                let name: String = read_mutf8(reader)?;
+                tags.push((name, read_payload(reader, id, depth + 1)?));
+            }
+            Nbt::Compound(tags)
+        }
+        11 => Nbt::IntArray(array(reader, |reader| {
+            Ok(integer(reader, IntegerType::I32)? as i32)
+        })?),
+        12 => Nbt::LongArray(array(reader, |reader| {
+            Ok(integer(reader, IntegerType::I64)? as i64)
+        })?),
+        id => return Err(CodecError::InvalidNbtTag(id)),
+    })
+}
+
+/// Check a payload like [`read_payload`], without allocating it.
+fn skip_payload(reader: &mut Reader, id: u8, depth: usize) -> Result<(), CodecError> {
+    let integer = |reader: &mut Reader, ty: IntegerType| reader.read_integer(&ty).map(|_| ());
+    match id {
+        1 => integer(reader, IntegerType::I8)?,
+        2 => integer(reader, IntegerType::I16)?,
+        3 | 5 => integer(reader, IntegerType::I32)?,
+        4 | 6 => integer(reader, IntegerType::I64)?,
+        7 => {
+            let length: usize = read_length(reader)?;
+            reader.read_bytes(length)?;
+        }
+        8 => skip_mutf8(reader)?,
+        9 | 10 if depth >= MAX_DEPTH => return Err(CodecError::NbtTooDeep),
+        9 => {
+            let element: u8 = reader.read_integer(&IntegerType::U8)? as u8;
+            for _ in 0..read_length(reader)? {
+                skip_payload(reader, element, depth + 1)?;
+            }
+        }
+        10 => loop {
+            let id: u8 = reader.read_integer(&IntegerType::U8)? as u8;
+            if id == 0 {
+                break;
+            }
+            check_id(id)?;
+            skip_mutf8(reader)?;
+            skip_payload(reader, id, depth + 1)?;
+        },
+        11 => {
+            for _ in 0..read_length(reader)? {
+                integer(reader, IntegerType::I32)?;
+            }
+        }
+        12 => {
+            for _ in 0..read_length(reader)? {
+                integer(reader, IntegerType::I64)?;
+            }
+        }
+        id => return Err(CodecError::InvalidNbtTag(id)),
+    }
+    Ok(())
+}
+
+/// Read a named tag.
+/// An empty NBT, which is a single end tag, is `None`.
+pub fn read_nbt(reader: &mut Reader) -> Result<Option<NamedTag>, CodecError> {
+    let start: Reader = reader.clone();
+    let result: Result<Option<NamedTag>, CodecError> = (|| {
+        let id: u8 = reader.read_integer(&IntegerType::U8)? as u8;
+        if id == 0 {
+            return Ok(None);
+        }
+        check_id(id)?;
+        let name: String = read_mutf8(reader)?;
+        let tag: Nbt = read_payload(reader, id, 0)?;
+        Ok(Some(NamedTag { name, tag }))
+    })();
+    if result.is_err() {
+        *reader = start;
+    }
+    result
+}
+
+/// Read the bytes of a named tag without decoding it.
+/// The tag is checked like in [`read_nbt`], so decoding the bytes later succeeds.
+pub fn read_raw_nbt<'a>(reader: &mut Reader<'a>) -> Result<&'a [u8], CodecError> {
+    let mut start: Reader = reader.clone();
+    let result: Result<(), CodecError> = (|| {
+        let id: u8 = reader.read_integer(&IntegerType::U8)? as u8;
+        if id == 0 {
+            return Ok(());
+        }
+        check_id(id)?;
+        skip_mutf8(reader)?;
+        skip_payload(reader, id, 0)
+    })();
+    match result {
+        Ok(()) => start.read_bytes(reader.offset() - start.offset()),
+        Err(error) => {
+            *reader = start;
+            Err(error)
+        }
+    }
+}
+
+fn write_mutf8(bytes: &mut Vec<u8>, string: &str) -> Result<(), CodecError> {
+    let mut encoded: Vec<u8> = vec![];
+    for unit in string.encode_utf16() {
+        match unit {
+            0x01..=0x7f => encoded.push(unit as u8),
+            0x00 | 0x80..=0x7ff => {
+                encoded.extend([0xc0 | (unit >> 6) as u8, 0x80 | (unit & 0x3f) as u8])
+            }
+            _ => encoded.extend([
+                0xe0 | (unit >> 12) as u8,
+                0x80 | ((unit >> 6) & 0x3f) as u8,
+                0x80 | (unit & 0x3f) as u8,
+            ]),
+        }
+    }
+    if encoded.len() > u16::MAX as usize {
+        return Err(CodecError::StringTooLong {
+            length: encoded.len(),
+            max: u16::MAX as usize,
+        });
+    }
+    bytes.extend((encoded.len() as u16).to_be_bytes());
+    bytes.extend(encoded);
+    Ok(())
+}
+
+fn write_length(bytes: &mut Vec<u8>, length: usize) -> Result<(), CodecError> {
+    let length: i32 =
+        i32::try_from(length).map_err(|_| CodecError::InvalidLength(length as i128))?;
+    bytes.extend(length.to_be_bytes());
+    Ok(())
+}
+
+fn write_payload(bytes: &mut Vec<u8>, tag: &Nbt, depth: usize) -> Result<(), CodecError> {
+    if matches!(tag, Nbt::List(_) | Nbt::Compound(_)) && depth >= MAX_DEPTH {
+        return Err(CodecError::NbtTooDeep);
+    }
+
+    match tag {
+        Nbt::Byte(value) => bytes.extend(value.to_be_bytes()),
+        Nbt::Short(value) => bytes.extend(value.to_be_bytes()),
+        Nbt::Int(value) => bytes.extend(value.to_be_bytes()),
+        Nbt::Long(value) => bytes.extend(value.to_be_bytes()),
+        Nbt::Float(value) => bytes.extend(value.to_be_bytes()),
+        Nbt::Double(value) => bytes.extend(value.to_be_bytes()),
+        Nbt::ByteArray(values) => {
+            write_length(bytes, values.len())?;
+            bytes.extend(values.iter().map(|value| *value as u8));
+        }
+        Nbt::String(value) => write_mutf8(bytes, value)?,
+        Nbt::List(tags) => {
+            // Empty lists have the end tag as their element type
+            let id: u8 = tags.first().map_or(0, Nbt::tag_id);
+            if tags.iter().any(|tag| tag.tag_id() != id) {
+                return Err(CodecError::MixedNbtList);
+            }
+            bytes.push(id);
+            write_length(bytes, tags.len())?;
+            for tag in tags {
+                write_payload(bytes, tag, depth + 1)?;
+            }
+        }
+        Nbt::Compound(tags) => {
+            for (name, tag) in tags {
+                bytes.push(tag.tag_id());
+                write_mutf8(bytes, name)?;
+                write_payload(bytes, tag, depth + 1)?;
+            }
+            bytes.push(0);
+        }
+        Nbt::IntArray(values) => {
+            write_length(bytes, values.len())?;
+            values
+                .iter()
+                .for_each(|value| bytes.extend(value.to_be_bytes()));
+        }
+        Nbt::LongArray(values) => {
+            write_length(bytes, values.len())?;
+            values
+                .iter()
+                .for_each(|value| bytes.extend(value.to_be_bytes()));
+        }
+    }
+    Ok(())
+}
+
+/// Write a named tag, or a single end tag for `None`.
+pub fn write_nbt(bytes: &mut Vec<u8>, tag: Option<&NamedTag>) -> Result<(), CodecError> {
+    let Some(tag) = tag else {
+        bytes.push(0);
+        return Ok(());
+    };
+    bytes.push(tag.tag.tag_id());
+    write_mutf8(bytes, &tag.name)?;
+    write_payload(bytes, &tag.tag, 0)
+}
+
+/// Read gzip compressed NBT, as used for item data in 1.7.
+/// No bytes at all are an empty NBT.
+pub fn read_compressed(bytes: &[u8]) -> Result<Option<NamedTag>, CodecError> {
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+    let mut decompressed: Vec<u8> = vec![];
+    GzDecoder::new(bytes)
+        .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|_| CodecError::InvalidCompression)?;
+    if decompressed.len() > MAX_DECOMPRESSED_SIZE {
+        return Err(CodecError::InvalidCompression);
+    }
+
+    let mut reader: Reader = Reader::new(&decompressed);
+    let tag: Option<NamedTag> = read_nbt(&mut reader)?;
+    if !reader.remaining().is_empty() {
+        return Err(CodecError::InvalidCompression);
+    }
+    Ok(tag)
+}
+
+/// Write gzip compressed NBT, as used for item data in 1.7.
+/// An empty NBT is written as no bytes at all.
+pub fn write_compressed(tag: Option<&NamedTag>) -> Result<Vec<u8>, CodecError> {
+    let Some(tag) = tag else {
+        return Ok(vec![]);
+    };
+    let mut bytes: Vec<u8> = vec![];
+    write_nbt(&mut bytes, Some(tag))?;
+    let mut encoder: GzEncoder<Vec<u8>> = GzEncoder::new(vec![], Compression::default());
+    encoder
+        .write_all(&bytes)
+        .and_then(|_| encoder.finish())
+        .map_err(|_| CodecError::InvalidCompression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nbt() {
+        let nbt: Nbt = Nbt::Compound(vec![
+            ("id".to_string(), Nbt::Short(1)),
+            (
+                "display".to_string(),
+                Nbt::Compound(vec![(
+                    "Name".to_string(),
+                    Nbt::String("A \"good\" sword".to_string()),
+                )]),
+            ),
+            (
+                "ench list".to_string(),
+                Nbt::List(vec![Nbt::Int(1), Nbt::Int(2)]),
+            ),
+            ("bytes".to_string(), Nbt::ByteArray(vec![1, -1])),
+            ("longs".to_string(), Nbt::LongArray(vec![])),
+            ("f".to_string(), Nbt::Float(1.0)),
+        ]);
+        assert_eq!(nbt.tag_id(), 10);
+        assert_eq!(nbt.get("id"), Some(&Nbt::Short(1)));
+        assert_eq!(nbt.get("missing"), None);
+        assert_eq!(
+            nbt.to_string(),
+            "{id: 1s, display: {Name: \"A \\\"good\\\" sword\"}, \"ench list\": [1, 2], bytes: [B; 1b, -1b], longs: [L;], f: 1.0f}"
+        );
+
+        let root: NamedTag = NamedTag {
+            name: "".to_string(),
+            tag: Nbt::Compound(vec![]),
+        };
+        assert_eq!(root.to_string(), "{}");
+    }
+
+    #[test]
+    fn test_nbt_binary() {
+        // hello_world.nbt from the NBT specification
+        let mut bytes: Vec<u8> = vec![0x0a, 0x00, 0x0b];
+        bytes.extend(b"hello world");
+        bytes.extend([0x08, 0x00, 0x04]);
+        bytes.extend(b"name");
+        bytes.extend([0x00, 0x09]);
+        bytes.extend(b"Bananrama");
+        bytes.push(0x00);
+
+        let tag: NamedTag = NamedTag {
+            name: "hello world".to_string(),
+            tag: Nbt::Compound(vec![(
+                "name".to_string(),
+                Nbt::String("Bananrama".to_string()),
+            )]),
+        };
+        assert_eq!(read_nbt(&mut Reader::new(&bytes)), Ok(Some(tag.clone())));
+        let mut written: Vec<u8> = vec![];
+        write_nbt(&mut written, Some(&tag)).unwrap();
+        assert_eq!(written, bytes);
+
+        let tag: NamedTag = NamedTag {
+            name: "\0😀".to_string(),
+            tag: Nbt::Compound(vec![
+                ("byte".to_string(), Nbt::Byte(-1)),
+                ("short".to_string(), Nbt::Short(-2)),
+                ("int".to_string(), Nbt::Int(3)),
+                ("long".to_string(), Nbt::Long(i64::MIN)),
+                ("float".to_string(), Nbt::Float(0.5)),
+                ("double".to_string(), Nbt::Double(-0.25)),
+                ("bytes".to_string(), Nbt::ByteArray(vec![1, -1])),
+                ("string".to_string(), Nbt::String("é".to_string())),
+                (
+                    "list".to_string(),
+                    Nbt::List(vec![Nbt::Int(1), Nbt::Int(2)]),
+                ),
+                ("empty".to_string(), Nbt::List(vec![])),
+                ("ints".to_string(), Nbt::IntArray(vec![i32::MAX])),
+                ("longs".to_string(), Nbt::LongArray(vec![1, 2])),
+            ]),
+        };
+        let mut bytes: Vec<u8> = vec![];
+        write_nbt(&mut bytes, Some(&tag)).unwrap();
+        // Modified UTF-8 encodes `\0` as 2 bytes and each half of the surrogate pair as 3
+        assert_eq!(
+            &bytes[..11],
+            [0x0a, 0x00, 0x08, 0xc0, 0x80, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]
+        );
+        let mut reader: Reader = Reader::new(&bytes);
+        assert_eq!(read_nbt(&mut reader), Ok(Some(tag.clone())));
+        assert!(reader.remaining().is_empty());
+        bytes.push(0xff);
+        let mut reader: Reader = Reader::new(&bytes);
+        assert_eq!(read_raw_nbt(&mut reader), Ok(&bytes[..bytes.len() - 1]));
+        assert_eq!(reader.remaining(), [0xff]);
+
+        assert_eq!(read_nbt(&mut Reader::new(&[0x00])), Ok(None));
+        assert_eq!(read_raw_nbt(&mut Reader::new(&[0x00])), Ok(&[0x00][..]));
+        let compressed: Vec<u8> = write_compressed(Some(&tag)).unwrap();
+        assert_eq!(compressed[..2], [0x1f, 0x8b]);
+        assert_eq!(read_compressed(&compressed), Ok(Some(tag)));
+        assert_eq!(write_compressed(None), Ok(vec![]));
+        assert_eq!(read_compressed(&[]), Ok(None));
+    }
+
+    #[test]
+    fn test_nbt_binary_errors() {
+        let mut reader: Reader = Reader::new(&[0x0a, 0x00, 0x00, 0x01, 0x00]);
+        assert_eq!(read_nbt(&mut reader), Err(CodecError::UnexpectedEnd));
+        assert_eq!(reader.offset(), 0);
+        assert_eq!(
+            read_nbt(&mut Reader::new(&[0x0a, 0x00, 0x00, 0x0f])),
+            Err(CodecError::InvalidNbtTag(15))
+        );
+        assert_eq!(
+            read_nbt(&mut Reader::new(&[0x08, 0x00, 0x00, 0x00, 0x01, 0x80])),
+            Err(CodecError::InvalidUtf8)
+        );
+        assert_eq!(
+            read_nbt(&mut Reader::new(&[
+                0x0b, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff
+            ])),
+            Err(CodecError::InvalidLength(-1))
+        );
+
+        let mut deep: Nbt = Nbt::Compound(vec![]);
+        for _ in 0..MAX_DEPTH {
+            deep = Nbt::List(vec![deep]);
+        }
+        let deep: NamedTag = NamedTag {
+            name: "".to_string(),
+            tag: deep,
+        };
+        assert_eq!(
+            write_nbt(&mut vec![], Some(&deep)),
+            Err(CodecError::NbtTooDeep)
+        );
+        let mut bytes: Vec<u8> = vec![0x09, 0x00, 0x00];
+        for _ in 0..MAX_DEPTH {
+            bytes.extend([0x09, 0x00, 0x00, 0x00, 0x01]);
+        }
+        assert_eq!(
+            read_nbt(&mut Reader::new(&bytes)),
+            Err(CodecError::NbtTooDeep)
+        );
+
+        // Raw NBT is checked the same way
+        let invalid: [&[u8]; 6] = [
+            &[0x0a, 0x00, 0x00, 0x01, 0x00],
+            &[0x0a, 0x00, 0x00, 0x0f],
+            &[0x08, 0x00, 0x00, 0x00, 0x01, 0x80],
+            &[0x0b, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff],
+            // An unpaired surrogate
+            &[0x08, 0x00, 0x00, 0x00, 0x03, 0xed, 0xa0, 0x80],
+            &bytes,
+        ];
+        for bytes in invalid {
+            let mut reader: Reader = Reader::new(bytes);
+            assert_eq!(
+                read_raw_nbt(&mut reader).unwrap_err(),
+                read_nbt(&mut Reader::new(bytes)).unwrap_err()
+            );
+            assert_eq!(reader.offset(), 0);
+        }
+
+        let mixed: NamedTag = NamedTag {
+            name: "".to_string(),
+            tag: Nbt::List(vec![Nbt::Int(1), Nbt::Byte(1)]),
+        };
+        assert_eq!(
+            write_nbt(&mut vec![], Some(&mixed)),
+            Err(CodecError::MixedNbtList)
+        );
+        assert_eq!(
+            read_compressed(&[0x1f, 0x8b, 0x00]),
+            Err(CodecError::InvalidCompression)
+        );
+    }
+}
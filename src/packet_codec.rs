@@ -0,0 +1,375 @@
+//! A [`tokio_util::codec`] codec for packets, behind the `tokio` feature.
+//!
+//! [`PacketCodec`] combines a [`Framer`], a [`Connection`] and the interpreted codec,
+//! so a `Framed<TcpStream, PacketCodec>` reads and writes packets as their name and value.
+//! Frames with ids that the specification doesn't know are passed through as they are.
+
+use std::fmt::Display;
+
+use bytes::BytesMut;
+use specmc_base::parse::Identifier;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    connection::Connection,
+    decode::DecodeError,
+    encode::EncodeError,
+    frame::{Frame, FrameError, Framer},
+    packets::Direction,
+    value::PacketValue,
+    Protocol,
+};
+
+#[derive(Debug)]
+pub enum PacketCodecError {
+    Io(std::io::Error),
+    Frame(FrameError),
+    Decode(DecodeError),
+    Encode(EncodeError),
+    /// A packet to send that isn't part of the current state.
+    UnknownPacketName {
+        state: Identifier,
+        name: Identifier,
+    },
+}
+impl Display for PacketCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketCodecError::Io(error) => write!(f, "{error}"),
+            PacketCodecError::Frame(error) => write!(f, "{error}"),
+            PacketCodecError::Decode(error) => write!(f, "{error}"),
+            PacketCodecError::Encode(error) => write!(f, "{error}"),
+            PacketCodecError::UnknownPacketName { state, name } => {
+                write!(f, "Unknown packet {name} in state {state}")
+            }
+        }
+    }
+}
+impl std::error::Error for PacketCodecError {}
+impl From<std::io::Error> for PacketCodecError {
+    fn from(error: std::io::Error) -> Self {
+        PacketCodecError::Io(error)
+    }
+}
+impl From<FrameError> for PacketCodecError {
+    fn from(error: FrameError) -> Self {
+        PacketCodecError::Frame(error)
+    }
+}
+impl From<DecodeError> for PacketCodecError {
+    fn from(error: DecodeError) -> Self {
+        PacketCodecError::Decode(error)
+    }
+}
+impl From<EncodeError> for PacketCodecError {
+    fn from(error: EncodeError) -> Self {
+        PacketCodecError::Encode(error)
+    }
+}
+
+/// A frame received by [`PacketCodec`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Received {
+    /// A packet of the current state, by name.
+    Packet(Identifier, PacketValue),
+    /// A frame with an id that isn't part of the current state.
+    Unknown(Frame),
+}
+
+/// Decodes received packets and encodes packets to send, following the state of the connection.
+/// Packets are identified by name, and sent packets are looked up in the opposite direction.
+#[derive(Debug, Clone)]
+pub struct PacketCodec<'a> {
+    framer: Framer,
+    connection: Connection<'a>,
+    /// The direction of received packets.
+    direction: Direction,
+}
+impl<'a> PacketCodec<'a> {
+    /// A codec starting in `state`, receiving packets in `direction`.
+    /// A server receives serverbound packets and a client clientbound ones.
+    pub fn new(protocol: &'a Protocol, state: Identifier, direction: Direction) -> Self {
+        PacketCodec {
+            framer: Framer::default(),
+            connection: Connection::new(protocol, state),
+            direction,
+        }
+    }
+
+    /// The framer, to switch on encryption.
+    /// Compression is switched by packets marked with `@compression(...)`.
+    pub fn framer_mut(&mut self) -> &mut Framer {
+        &mut self.framer
+    }
+
+    pub fn connection(&self) -> &Connection<'a> {
+        &self.connection
+    }
+
+    pub fn connection_mut(&mut self) -> &mut Connection<'a> {
+        &mut self.connection
+    }
+
+    /// Switch the framer to the compression threshold of the connection, if a packet changed it.
+    fn follow_compression(&mut self, before: Option<usize>) {
+        if self.connection.compression() != before {
+            self.framer.set_compression(self.connection.compression());
+        }
+    }
+}
+impl Decoder for PacketCodec<'_> {
+    type Item = Received;
+    type Error = PacketCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // The framer keeps incomplete frames, so it can decrypt bytes as they arrive
+        self.framer.feed(src);
+        src.clear();
+        let Some(frame) = self.framer.next_frame()? else {
+            return Ok(None);
+        };
+        let compression: Option<usize> = self.connection.compression();
+        let received: Received = match self.connection.decode(&self.direction, &frame)? {
+            Some((packet, value)) => Received::Packet(packet.name.clone(), value),
+            None => Received::Unknown(frame),
+        };
+        self.follow_compression(compression);
+        Ok(Some(received))
+    }
+
+    /// The input is moved into the framer, so a frame cut off by the end of the stream
+    /// is only visible there. It is reported like other codecs do.
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(item) => Ok(Some(item)),
+            None if self.framer.buffered() == 0 => Ok(None),
+            None => Err(std::io::Error::other("bytes remaining on stream").into()),
+        }
+    }
+}
+impl Encoder<(Identifier, PacketValue)> for PacketCodec<'_> {
+    type Error = PacketCodecError;
+
+    fn encode(
+        &mut self,
+        (name, value): (Identifier, PacketValue),
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let state: &Identifier = self.connection.state();
+        let direction: Direction = self.direction.opposite();
+        let Some(packet) = self.connection.protocol().packets.iter().find(|packet| {
+            packet.name == name && &packet.state == state && packet.direction == direction
+        }) else {
+            return Err(PacketCodecError::UnknownPacketName {
+                state: state.clone(),
+                name,
+            });
+        };
+
+        let compression: Option<usize> = self.connection.compression();
+        let frame: Frame = self.connection.encode(packet, &value)?;
+        self.encode(frame, dst)?;
+        self.follow_compression(compression);
+        Ok(())
+    }
+}
+/// Frames are sent as they are, like unknown frames that are passed on.
+impl Encoder<Frame> for PacketCodec<'_> {
+    type Error = PacketCodecError;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut bytes: Vec<u8> = vec![];
+        self.framer.write(&frame, &mut bytes)?;
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{BytesCodec, Framed, FramedWrite};
+
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    fn received(item: Option<Result<Received, PacketCodecError>>) -> (Identifier, PacketValue) {
+        match item.unwrap().unwrap() {
+            Received::Packet(name, value) => (name, value),
+            Received::Unknown(frame) => panic!("unknown packet {:#04x}", frame.id),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_packet_codec() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let name = |name: &str| Identifier(name.to_string());
+
+        let (client, server) = tokio::io::duplex(64);
+        let mut client = Framed::new(
+            client,
+            PacketCodec::new(&protocol, name("Handshake"), Direction::Clientbound),
+        );
+        let mut server = Framed::new(
+            server,
+            PacketCodec::new(&protocol, name("Handshake"), Direction::Serverbound),
+        );
+
+        let handshake: PacketValue = PacketValue::Struct(vec![
+            ("server_address".to_string(), "localhost".into()),
+            ("server_port".to_string(), 25565u16.into()),
+            ("next_state".to_string(), 2.into()),
+        ]);
+        client.send((name("Handshake"), handshake)).await.unwrap();
+        client
+            .send((
+                name("LoginStart"),
+                PacketValue::Struct(vec![("name".to_string(), "Steve".into())]),
+            ))
+            .await
+            .unwrap();
+
+        let (packet, value) = received(server.next().await);
+        assert_eq!(packet, name("Handshake"));
+        assert_eq!(
+            value.get("protocol_version"),
+            Some(&PacketValue::Integer(4))
+        );
+        let (packet, value) = received(server.next().await);
+        assert_eq!(packet, name("LoginStart"));
+        assert_eq!(value.get("name").unwrap().as_str(), Some("Steve"));
+
+        server
+            .send((
+                name("LoginSuccess"),
+                PacketValue::Struct(vec![
+                    ("uuid".to_string(), "".into()),
+                    ("username".to_string(), "Steve".into()),
+                ]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(server.codec().connection().state(), &name("Play"));
+        let (packet, _) = received(client.next().await);
+        assert_eq!(packet, name("LoginSuccess"));
+        assert_eq!(client.codec().connection().state(), &name("Play"));
+
+        // Packets of other states can't be sent
+        let error: PacketCodecError = client
+            .send((name("LoginStart"), PacketValue::Struct(vec![])))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Unknown packet LoginStart in state Play");
+
+        client
+            .send((
+                name("KeepAlive"),
+                PacketValue::Struct(vec![("keep_alive_id".to_string(), 42.into())]),
+            ))
+            .await
+            .unwrap();
+        let (packet, value) = received(server.next().await);
+        assert_eq!(packet, name("KeepAlive"));
+        assert_eq!(value.get("keep_alive_id"), Some(&PacketValue::Integer(42)));
+
+        // Ids that aren't part of the state are received as frames, and can be sent on
+        let unknown: Frame = Frame {
+            id: 0x7f,
+            body: vec![0x01, 0x02],
+        };
+        client.send(unknown.clone()).await.unwrap();
+        client
+            .send((
+                name("KeepAlive"),
+                PacketValue::Struct(vec![("keep_alive_id".to_string(), 43.into())]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Received::Unknown(unknown)
+        );
+        let (packet, _) = received(server.next().await);
+        assert_eq!(packet, name("KeepAlive"));
+
+        // A stream that ends in the middle of a frame is an error
+        let (client, server) = tokio::io::duplex(64);
+        let mut server = Framed::new(
+            server,
+            PacketCodec::new(&protocol, name("Status"), Direction::Serverbound),
+        );
+        let mut client = FramedWrite::new(client, BytesCodec::new());
+        client
+            .send(bytes::Bytes::from_static(&[0x01, 0x00, 0x05, 0x00]))
+            .await
+            .unwrap();
+        drop(client);
+        let (packet, _) = received(server.next().await);
+        assert_eq!(packet, name("Request"));
+        assert_eq!(
+            server.next().await.unwrap().unwrap_err().to_string(),
+            "bytes remaining on stream"
+        );
+        assert!(server.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_packet_codec_compression() {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum State(VarInt) {
+                Login
+            }
+            @compression(threshold)
+            packet SetCompression(clientbound, Login, 0x03) {
+                VarInt threshold
+            }
+            packet Message(clientbound, Login, 0x04) {
+                String text
+            }
+            "
+        );
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let name = |name: &str| Identifier(name.to_string());
+
+        let (client, server) = tokio::io::duplex(64);
+        let mut client = Framed::new(
+            client,
+            PacketCodec::new(&protocol, name("Login"), Direction::Clientbound),
+        );
+        let mut server = Framed::new(
+            server,
+            PacketCodec::new(&protocol, name("Login"), Direction::Serverbound),
+        );
+
+        // Both sides switch to compressed frames after Set Compression
+        server
+            .send((
+                name("SetCompression"),
+                PacketValue::Struct(vec![("threshold".to_string(), 0.into())]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(server.codec_mut().framer_mut().compression(), Some(0));
+        let text: String = "a".repeat(100);
+        server
+            .send((
+                name("Message"),
+                PacketValue::Struct(vec![("text".to_string(), text.as_str().into())]),
+            ))
+            .await
+            .unwrap();
+
+        let (packet, _) = received(client.next().await);
+        assert_eq!(packet, name("SetCompression"));
+        assert_eq!(client.codec_mut().framer_mut().compression(), Some(0));
+        let (packet, value) = received(client.next().await);
+        assert_eq!(packet, name("Message"));
+        assert_eq!(value.get("text").unwrap().as_str(), Some(text.as_str()));
+    }
+}
@@ -1,25 +1,38 @@
-use specmc_base::{
-    ensure_tokens,
-    parse::{Identifier, Parse, ParseError},
-};
+use specmc_base::parse::Identifier;
 use strtoint::strtoint;
 
-use crate::base::FieldList;
+use crate::{
+    base::FieldList,
+    bounds::{Analyzer, SizeBounds},
+    ensure_tokens,
+    parse::{Parse, Span, Token, TokenStream},
+    Error, Protocol,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Direction {
     Serverbound,
     Clientbound,
 }
+impl Direction {
+    /// The direction of packets sent the other way.
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Serverbound => Direction::Clientbound,
+            Direction::Clientbound => Direction::Serverbound,
+        }
+    }
+}
 impl Parse for Direction {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
         use Direction::*;
-        match tokens.pop().ok_or(ParseError::EndOfFile)?.as_str() {
+        let token: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+        match token.as_str() {
             "serverbound" => Ok(Serverbound),
             "clientbound" => Ok(Clientbound),
-            token => Err(ParseError::InvalidToken {
-                token: token.to_string(),
-                error: "Invalid direction".to_string(),
+            _ => Err(Error::InvalidDirection {
+                token: token.text,
+                span: token.span,
             }),
         }
     }
@@ -32,9 +45,47 @@ pub struct Packet {
     pub state: Identifier,
     pub id: u32,
     pub fields: FieldList,
+    /// The state the connection switches to after this packet, from `@transition(...)`.
+    /// This is either the name of a state, or an enum field whose variant names the state.
+    pub transition: Option<Identifier>,
+    /// The integer field with the compression threshold that frames switch to after this packet,
+    /// from `@compression(...)`. A negative threshold switches compression off.
+    pub compression: Option<Identifier>,
+    pub span: Span,
+}
+impl Packet {
+    /// The minimum and maximum length of the encoded packet body.
+    pub fn size_bounds(&self, protocol: &Protocol) -> SizeBounds {
+        Analyzer::new(protocol).fields(&self.fields)
+    }
 }
 impl Parse for Packet {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let start: Span = tokens.next_span();
+        let mut transition: Option<Identifier> = None;
+        let mut compression: Option<Identifier> = None;
+        while tokens.last().is_some_and(|token| token == "@") {
+            tokens.pop();
+            let attribute: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+            match attribute.as_str() {
+                "transition" => {
+                    ensure_tokens!(tokens, "(");
+                    transition = Some(Identifier::parse(tokens)?);
+                    ensure_tokens!(tokens, ")");
+                }
+                "compression" => {
+                    ensure_tokens!(tokens, "(");
+                    compression = Some(Identifier::parse(tokens)?);
+                    ensure_tokens!(tokens, ")");
+                }
+                _ => {
+                    return Err(Error::UnknownAttribute {
+                        name: attribute.text,
+                        span: attribute.span,
+                    })
+                }
+            }
+        }
         ensure_tokens!(tokens, "packet");
         let name: Identifier = Identifier::parse(tokens)?;
         ensure_tokens!(tokens, "(");
@@ -42,15 +93,16 @@ impl Parse for Packet {
         ensure_tokens!(tokens, ",");
         let state: Identifier = Identifier::parse(tokens)?;
         ensure_tokens!(tokens, ",");
-        let id: String = tokens.pop().ok_or(ParseError::EndOfFile)?;
-        let id: u32 = strtoint(&id).map_err(|_| ParseError::InvalidToken {
-            token: id,
-            error: "Invalid packet id".to_string(),
+        let id: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+        let id: u32 = strtoint(id.as_str()).map_err(|_| Error::InvalidPacketId {
+            token: id.text.clone(),
+            span: id.span,
         })?;
         ensure_tokens!(tokens, ")");
+        let open: Span = tokens.next_span();
         ensure_tokens!(tokens, "{");
         let fields: FieldList = FieldList::parse(tokens)?;
-        ensure_tokens!(tokens, "}");
+        tokens.close_block("packet", open)?;
 
         Ok(Packet {
             name,
@@ -58,6 +110,9 @@ impl Parse for Packet {
             state,
             id,
             fields,
+            transition,
+            compression,
+            span: tokens.span_from(start),
         })
     }
 }
@@ -66,7 +121,7 @@ impl Parse for Packet {
 mod tests {
     use std::collections::HashSet;
 
-    use specmc_base::tokenize;
+    use crate::tokenize;
 
     use crate::{
         base::{BaseType, Field, IntegerType, Value},
@@ -78,7 +133,7 @@ mod tests {
 
     #[test]
     fn test_direction() {
-        let mut tokens: Vec<String> = tokenize!("serverbound clientbound unknown");
+        let mut tokens: TokenStream = tokenize!("serverbound clientbound unknown");
 
         test_parse!(tokens, Direction, Ok(Direction::Serverbound));
         test_parse!(tokens, Direction, Ok(Direction::Clientbound));
@@ -86,18 +141,18 @@ mod tests {
         test_parse!(
             tokens,
             Direction,
-            Err(ParseError::InvalidToken {
+            Err(Error::InvalidDirection {
                 token: "unknown".to_string(),
-                error: "Invalid direction".to_string()
+                span: Span::default(),
             })
         );
         assert!(tokens.is_empty());
-        test_parse!(tokens, Direction, Err(ParseError::EndOfFile));
+        test_parse!(tokens, Direction, Err(Error::EndOfFile));
     }
 
     #[test]
     fn test_packet() {
-        let mut tokens: Vec<String> = tokenize!(
+        let mut tokens: TokenStream = tokenize!(
             "
             packet TestPacket(serverbound, Play, 0x42) {
                 i32 number
@@ -129,30 +184,38 @@ mod tests {
                         name: Identifier("number".to_string()),
                         value: None,
                         conditions: HashSet::new(),
+                        span: Span::default(),
                     },
                     Field {
                         ty: Type::BaseType(BaseType::String { length: None }),
                         name: Identifier("message".to_string()),
                         value: None,
                         conditions: HashSet::new(),
+                        span: Span::default(),
                     },
                     Field {
                         ty: Type::BaseType(BaseType::Bool),
                         name: Identifier("flag".to_string()),
                         value: None,
                         conditions: HashSet::new(),
+                        span: Span::default(),
                     },
                     Field {
                         ty: Type::BaseType(BaseType::Integer(IntegerType::I32)),
                         name: Identifier("other".to_string()),
                         value: None,
                         conditions: HashSet::from_iter(vec!["flag".to_string()]),
+                        span: Span::default(),
                     },
                     Field {
                         ty: Type::BaseType(BaseType::Integer(IntegerType::VarInt)),
                         name: Identifier("length".to_string()),
-                        value: Some(Value::Length(Identifier("data".to_string()))),
+                        value: Some(Value::Length(
+                            Identifier("data".to_string()),
+                            Span::default()
+                        )),
                         conditions: HashSet::new(),
+                        span: Span::default(),
                     },
                     Field {
                         ty: Type::BaseType(BaseType::List {
@@ -162,12 +225,45 @@ mod tests {
                         name: Identifier("data".to_string()),
                         value: None,
                         conditions: HashSet::new(),
+                        span: Span::default(),
                     },
-                ])
+                ]),
+                transition: None,
+                compression: None,
+                span: Span::default(),
             })
         );
 
-        test_parse!(tokens, Packet, Err(ParseError::EndOfFile));
+        let error: Error = Packet::parse(&mut tokens).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unclosed `packet` block at 14:24 (opened at 12:61)"
+        );
         assert!(tokens.is_empty());
+
+        let mut tokens: TokenStream = tokenize!(
+            "
+            @transition(Play)
+            packet LoginSuccess(clientbound, Login, 0x02) {}
+            @compression(threshold)
+            packet SetCompression(clientbound, Login, 0x03) {
+                VarInt threshold
+            }
+            @deprecated packet Old(clientbound, Login, 0x04) {}
+            "
+        );
+        let packet: Packet = Packet::parse(&mut tokens).unwrap();
+        assert_eq!(packet.transition, Some(Identifier("Play".to_string())));
+        assert_eq!(packet.span.line, 2);
+        let packet: Packet = Packet::parse(&mut tokens).unwrap();
+        assert_eq!(packet.transition, None);
+        assert_eq!(
+            packet.compression,
+            Some(Identifier("threshold".to_string()))
+        );
+        assert_eq!(
+            Packet::parse(&mut tokens).unwrap_err().to_string(),
+            "Unknown attribute: deprecated at 8:14"
+        );
     }
 }
@@ -0,0 +1,742 @@
+//! Tokenizing and parsing with source positions.
+//!
+//! This mirrors `specmc_base::{tokenize, parse}`, but every token remembers where it
+//! came from, so that parsed nodes and errors can point into the specification.
+
+use std::fmt::Display;
+
+use specmc_base::{
+    ensure,
+    parse::{Identifier, Literal, ParseError},
+};
+
+use crate::{
+    base::{BaseType, Field, FieldList, IntegerType, Value},
+    constants::Constant,
+    diagnostic::Diagnostic,
+    enums::{Enum, Variant},
+    packets::{Direction, Packet},
+    types::{CustomType, Type},
+    Error, Protocol,
+};
+
+/// Ensure that the next tokens in the stream match the given tokens.
+/// This will remove the tokens from the stream.
+macro_rules! ensure_tokens {
+    ($tokens:ident, $($token:expr),+) => {
+        $(
+            let token: &$crate::parse::Token = $tokens
+                .last()
+                .ok_or($crate::Error::EndOfFile)?;
+            specmc_base::ensure!(
+                token == $token,
+                $crate::Error::Expected {
+                    expected: $token.to_string(),
+                    found: token.text.clone(),
+                    span: token.span,
+                }
+            );
+            $tokens.pop();
+        )+
+    };
+}
+
+pub(crate) use ensure_tokens;
+
+/// Tokenize a string into a [`TokenStream`], for tests.
+/// Like `ensure_tokens!`, this isn't exported, so it doesn't collide with `specmc_base::tokenize!`.
+#[cfg(test)]
+macro_rules! tokenize_stream {
+    ($input:expr) => {
+        $crate::parse::tokenize($input)
+    };
+}
+#[cfg(test)]
+pub(crate) use tokenize_stream as tokenize;
+
+/// A range of bytes in the specification, with the line and column it starts at.
+/// Lines and columns start at 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+impl Span {
+    /// The span from the start of this span to the end of the other one.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token {
+    pub text: String,
+    pub span: Span,
+}
+impl Token {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+impl PartialEq<str> for Token {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+impl PartialEq<&str> for Token {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Tokens waiting to be parsed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenStream {
+    /// Remaining tokens in reverse order.
+    tokens: Vec<Token>,
+    /// The span of the last token that was consumed.
+    previous: Option<Span>,
+    /// Problems that were recovered from, if recovery is enabled.
+    diagnostics: Option<Vec<Diagnostic>>,
+}
+impl TokenStream {
+    /// Create a stream from tokens in source order.
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        tokens.reverse();
+        TokenStream {
+            tokens,
+            previous: None,
+            diagnostics: None,
+        }
+    }
+
+    /// Enable error recovery.
+    /// Parsers that can recover record errors with [`TokenStream::recover`] and continue.
+    pub fn recovering(mut self) -> Self {
+        self.diagnostics.get_or_insert_with(Vec::new);
+        self
+    }
+
+    pub fn is_recovering(&self) -> bool {
+        self.diagnostics.is_some()
+    }
+
+    /// Record an error if recovery is enabled, otherwise return it.
+    /// Errors without a span are reported at the last consumed token.
+    pub fn recover(&mut self, error: Error) -> Result<(), Error> {
+        let span: Span = error
+            .span()
+            .or(self.previous)
+            .unwrap_or_else(|| self.next_span());
+        match &mut self.diagnostics {
+            Some(diagnostics) => {
+                diagnostics.push(Diagnostic::new(error, span));
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    /// Take the recorded diagnostics, leaving recovery enabled.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.diagnostics
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// The next token.
+    pub fn last(&self) -> Option<&Token> {
+        self.tokens.last()
+    }
+
+    /// Consume the next token.
+    pub fn pop(&mut self) -> Option<Token> {
+        let token: Token = self.tokens.pop()?;
+        self.previous = Some(token.span);
+        Some(token)
+    }
+
+    /// Put a token back in front of the stream.
+    pub fn push(&mut self, token: Token) {
+        self.tokens.push(token);
+    }
+
+    /// The span of the next token, or an empty span after the last consumed one.
+    pub fn next_span(&self) -> Span {
+        match (self.tokens.last(), self.previous) {
+            (Some(token), _) => token.span,
+            (None, Some(previous)) => Span {
+                start: previous.end,
+                ..previous
+            },
+            (None, None) => Span::default(),
+        }
+    }
+
+    /// Consume the `}` closing a block that was opened at `open`.
+    /// Reaching the end of the input or the next item means the block was never closed.
+    pub fn close_block(&mut self, kind: &str, open: Span) -> Result<(), Error> {
+        match self.last() {
+            Some(token) if token == "}" => {
+                self.pop();
+                Ok(())
+            }
+            Some(token) if !KEYWORDS.contains(&token.as_str()) => Err(Error::Expected {
+                expected: "}".to_string(),
+                found: token.text.clone(),
+                span: token.span,
+            }),
+            _ => Err(Error::UnclosedBlock {
+                kind: kind.to_string(),
+                open,
+                span: self.next_span(),
+            }),
+        }
+    }
+
+    /// The span of the last consumed token.
+    pub fn previous_span(&self) -> Option<Span> {
+        self.previous
+    }
+
+    /// The span from `start` to the end of the last consumed token.
+    pub fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous.unwrap_or(start))
+    }
+}
+
+impl From<Vec<String>> for TokenStream {
+    /// Tokens as returned by `specmc_base::tokenize!`, in reverse order and without positions.
+    fn from(tokens: Vec<String>) -> Self {
+        TokenStream {
+            tokens: tokens
+                .into_iter()
+                .map(|text| Token {
+                    text,
+                    span: Span::default(),
+                })
+                .collect(),
+            previous: None,
+            diagnostics: None,
+        }
+    }
+}
+impl From<TokenStream> for Vec<String> {
+    /// The remaining tokens in reverse order, like `specmc_base::tokenize!` returns them.
+    fn from(tokens: TokenStream) -> Self {
+        tokens.tokens.into_iter().map(|token| token.text).collect()
+    }
+}
+
+pub trait Parse
+where
+    Self: Sized,
+{
+    /// Parse a stream of tokens into an object, consuming the tokens as needed.
+    /// If this fails, it is **not** guaranteed that no tokens have been consumed.
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error>;
+}
+
+/// Implement [`specmc_base::parse::Parse`] in terms of [`Parse`],
+/// so that nodes can still be parsed from the tokens of `specmc_base::tokenize!`.
+macro_rules! impl_base_parse {
+    ($($ty:ty),+) => {
+        $(
+            impl specmc_base::parse::Parse for $ty {
+                fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+                    let mut stream: TokenStream = TokenStream::from(std::mem::take(tokens));
+                    let result: Result<Self, Error> = <$ty as Parse>::parse(&mut stream);
+                    *tokens = stream.into();
+                    Ok(result?)
+                }
+            }
+        )+
+    };
+}
+impl_base_parse!(
+    Protocol,
+    Enum,
+    Variant,
+    CustomType,
+    Type,
+    BaseType,
+    IntegerType,
+    Packet,
+    Direction,
+    FieldList,
+    Field,
+    Value,
+    Constant
+);
+
+/// Keywords that start a top-level item.
+/// Packets can be preceded by attributes, which start with `@`.
+pub const KEYWORDS: [&str; 5] = ["enum", "type", "packet", "const", "@"];
+
+const SPECIAL_CHARS: &[&str] = &[
+    " ", "\t", "\n", "\r", "==", "!=", "<=", ">=", "||", "&&", "**", "(", ")", "{", "}", "[", "]",
+    ",", "=", "<", ">", "-", "+", ";", "\"", "@",
+];
+
+/// Split a string into tokens.
+/// Whitespace and `//` comments are skipped, special characters are separate tokens
+/// and string literals keep their whitespace.
+pub fn tokenize(input: &str) -> TokenStream {
+    let mut tokens: Vec<Token> = vec![];
+    let mut current: Option<Token> = None;
+    let (mut line, mut column): (usize, usize) = (1, 1);
+    let mut in_string: bool = false;
+
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        let span: Span = Span {
+            start: i,
+            end: i + ch.len_utf8(),
+            line,
+            column,
+        };
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+
+        let special: Option<&&str> = if in_string {
+            (ch == '"').then_some(&"\"")
+        } else {
+            SPECIAL_CHARS
+                .iter()
+                .find(|special| input[i..].starts_with(**special))
+        };
+        let comment: bool = !in_string && input[i..].starts_with("//");
+
+        if special.is_none() && !comment {
+            match &mut current {
+                Some(token) => {
+                    token.text.push(ch);
+                    token.span.end = span.end;
+                }
+                None => {
+                    current = Some(Token {
+                        text: ch.to_string(),
+                        span,
+                    })
+                }
+            }
+            continue;
+        }
+
+        tokens.extend(current.take());
+        if comment {
+            while chars.next_if(|(_, ch)| *ch != '\n').is_some() {}
+            continue;
+        }
+
+        let special: &str = special.unwrap();
+        if special == "\"" {
+            in_string = !in_string;
+        }
+        for _ in 1..special.len() {
+            chars.next();
+            column += 1;
+        }
+        if !special.trim().is_empty() {
+            tokens.push(Token {
+                text: special.to_string(),
+                span: Span {
+                    end: i + special.len(),
+                    ..span
+                },
+            });
+        }
+    }
+    tokens.extend(current);
+
+    TokenStream::new(tokens)
+}
+
+impl Parse for Identifier {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let token: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+
+        let mut chars: std::str::Chars = token.text.chars();
+        ensure!(
+            chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            Error::InvalidIdentifier {
+                name: token.text,
+                span: token.span,
+            }
+        );
+
+        Ok(Identifier(token.text))
+    }
+}
+
+impl Parse for Literal {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let token: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+        match token.as_str() {
+            "true" => Ok(Literal::Boolean(true)),
+            "false" => Ok(Literal::Boolean(false)),
+            "\"" => {
+                let mut string: String = String::new();
+                while tokens.last().ok_or(Error::EndOfFile)? != "\"" {
+                    string += tokens.pop().unwrap().as_str();
+                }
+                ensure_tokens!(tokens, "\"");
+                Ok(Literal::String(string))
+            }
+            _ => {
+                let mut text: String = token.text.clone();
+                let mut span: Span = token.span;
+                if let "+" | "-" = text.as_str() {
+                    let number: Token = tokens.pop().ok_or(Error::EndOfFile)?;
+                    text += number.as_str();
+                    span = span.to(number.span);
+                }
+
+                if let Ok(int) = strtoint::strtoint(&text) {
+                    Ok(Literal::Integer(int))
+                } else if let Ok(float) = text.parse::<f64>() {
+                    Ok(Literal::Float(float))
+                } else {
+                    let error: Error = Error::InvalidLiteral {
+                        token: text.clone(),
+                        span,
+                    };
+                    tokens.push(Token { text, span });
+                    Err(error)
+                }
+            }
+        }
+    }
+}
+
+/// Clearing spans, for tests that compare parsed nodes regardless of where they are defined.
+#[cfg(test)]
+pub(crate) mod spans {
+    use specmc_base::parse::{Identifier, Literal};
+
+    use crate::{
+        base::{BaseType, Field, FieldList, IntegerType, Value},
+        constants::Constant,
+        enums::{Enum, Variant},
+        packets::{Direction, Packet},
+        types::{CustomType, Type},
+        Error, Protocol,
+    };
+
+    use super::{Span, Token};
+
+    pub(crate) trait ClearSpans {
+        /// Reset all spans to [`Span::default`].
+        fn clear_spans(&mut self);
+    }
+
+    macro_rules! without_spans {
+        ($($ty:ty),+) => {
+            $(
+                impl ClearSpans for $ty {
+                    fn clear_spans(&mut self) {}
+                }
+            )+
+        };
+    }
+    without_spans!(Identifier, Literal, IntegerType, BaseType, Type, Direction);
+
+    impl ClearSpans for Span {
+        fn clear_spans(&mut self) {
+            *self = Span::default();
+        }
+    }
+    impl<T: ClearSpans> ClearSpans for Option<T> {
+        fn clear_spans(&mut self) {
+            self.iter_mut().for_each(T::clear_spans);
+        }
+    }
+    impl<T: ClearSpans> ClearSpans for Vec<T> {
+        fn clear_spans(&mut self) {
+            self.iter_mut().for_each(T::clear_spans);
+        }
+    }
+    impl<T: ClearSpans, E: ClearSpans> ClearSpans for Result<T, E> {
+        fn clear_spans(&mut self) {
+            match self {
+                Ok(value) => value.clear_spans(),
+                Err(error) => error.clear_spans(),
+            }
+        }
+    }
+    impl ClearSpans for Error {
+        fn clear_spans(&mut self) {
+            *self = std::mem::replace(self, Error::EndOfFile).at(Span::default());
+            if let Error::UnclosedBlock { open, .. } = self {
+                open.clear_spans();
+            }
+        }
+    }
+    impl ClearSpans for Token {
+        fn clear_spans(&mut self) {
+            self.span.clear_spans();
+        }
+    }
+    impl ClearSpans for Value {
+        fn clear_spans(&mut self) {
+            match self {
+                Value::Length(_, span) | Value::Literal(_, span) | Value::Identifier(_, span) => {
+                    span.clear_spans()
+                }
+            }
+        }
+    }
+    impl ClearSpans for Field {
+        fn clear_spans(&mut self) {
+            self.value.clear_spans();
+            self.span.clear_spans();
+        }
+    }
+    impl ClearSpans for FieldList {
+        fn clear_spans(&mut self) {
+            self.0.clear_spans();
+        }
+    }
+    impl ClearSpans for Variant {
+        fn clear_spans(&mut self) {
+            self.span.clear_spans();
+        }
+    }
+    impl ClearSpans for Enum {
+        fn clear_spans(&mut self) {
+            self.variants.clear_spans();
+            self.span.clear_spans();
+        }
+    }
+    impl ClearSpans for CustomType {
+        fn clear_spans(&mut self) {
+            self.fields.clear_spans();
+            self.span.clear_spans();
+        }
+    }
+    impl ClearSpans for Packet {
+        fn clear_spans(&mut self) {
+            self.fields.clear_spans();
+            self.span.clear_spans();
+        }
+    }
+    impl ClearSpans for Constant {
+        fn clear_spans(&mut self) {
+            self.span.clear_spans();
+        }
+    }
+    impl ClearSpans for Protocol {
+        fn clear_spans(&mut self) {
+            self.enums.clear_spans();
+            self.types.clear_spans();
+            self.packets.clear_spans();
+            self.constants.clear_spans();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::test_parse;
+
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let mut tokens: TokenStream = tokenize!(
+            "packet A(serverbound, Play, 0x00) { // comment
+    String s = \" a  b \"
+    if (x >= -1) {}
+}"
+        );
+
+        let expected: [(&str, usize, usize, usize); 25] = [
+            ("packet", 0, 1, 1),
+            ("A", 7, 1, 8),
+            ("(", 8, 1, 9),
+            ("serverbound", 9, 1, 10),
+            (",", 20, 1, 21),
+            ("Play", 22, 1, 23),
+            (",", 26, 1, 27),
+            ("0x00", 28, 1, 29),
+            (")", 32, 1, 33),
+            ("{", 34, 1, 35),
+            ("String", 51, 2, 5),
+            ("s", 58, 2, 12),
+            ("=", 60, 2, 14),
+            ("\"", 62, 2, 16),
+            (" a  b ", 63, 2, 17),
+            ("\"", 69, 2, 23),
+            ("if", 75, 3, 5),
+            ("(", 78, 3, 8),
+            ("x", 79, 3, 9),
+            (">=", 81, 3, 11),
+            ("-", 84, 3, 14),
+            ("1", 85, 3, 15),
+            (")", 86, 3, 16),
+            ("{", 88, 3, 18),
+            ("}", 89, 3, 19),
+        ];
+        for (text, start, line, column) in expected {
+            let token: Token = tokens.pop().unwrap();
+            assert_eq!(token, text);
+            assert_eq!(
+                (token.span.start, token.span.line, token.span.column),
+                (start, line, column),
+                "{text}"
+            );
+            assert_eq!(token.span.end, start + text.len());
+        }
+        assert_eq!(tokens.pop().unwrap(), "}");
+        assert!(tokens.is_empty());
+    }
+
+    #[test]
+    fn test_identifier() {
+        let mut tokens: TokenStream = tokenize!("cool_identifier\n 1abc");
+
+        test_parse!(
+            tokens,
+            Identifier,
+            Ok(Identifier("cool_identifier".to_string()))
+        );
+        let error: Error = Identifier::parse(&mut tokens).unwrap_err();
+        assert_eq!(
+            error,
+            Error::InvalidIdentifier {
+                name: "1abc".to_string(),
+                span: Span {
+                    start: 17,
+                    end: 21,
+                    line: 2,
+                    column: 2,
+                },
+            }
+        );
+        assert_eq!(error.span().unwrap().to_string(), "2:2");
+
+        assert!(tokens.is_empty());
+        test_parse!(tokens, Identifier, Err(Error::EndOfFile));
+    }
+
+    #[test]
+    fn test_literal() {
+        let mut tokens: TokenStream =
+            tokenize!("true false 0 +42 -5 123.0 +8.5 -11.4 \"a string\" abc");
+
+        test_parse!(tokens, Literal, Ok(Literal::Boolean(true)));
+        test_parse!(tokens, Literal, Ok(Literal::Boolean(false)));
+        test_parse!(tokens, Literal, Ok(Literal::Integer(0)));
+        test_parse!(tokens, Literal, Ok(Literal::Integer(42)));
+        test_parse!(tokens, Literal, Ok(Literal::Integer(-5)));
+        test_parse!(tokens, Literal, Ok(Literal::Float(123.0)));
+        test_parse!(tokens, Literal, Ok(Literal::Float(8.5)));
+        test_parse!(tokens, Literal, Ok(Literal::Float(-11.4)));
+        test_parse!(tokens, Literal, Ok(Literal::String("a string".to_string())));
+        test_parse!(
+            tokens,
+            Literal,
+            Err(Error::InvalidLiteral {
+                token: "abc".to_string(),
+                span: Span::default(),
+            })
+        );
+
+        assert_eq!(tokens.pop().unwrap(), "abc");
+        assert!(tokens.is_empty());
+        test_parse!(tokens, Literal, Err(Error::EndOfFile));
+    }
+
+    #[test]
+    fn test_spans() {
+        let span = |start: usize, end: usize, line: usize, column: usize| Span {
+            start,
+            end,
+            line,
+            column,
+        };
+
+        let mut tokens: TokenStream = tokenize!("enum A(u8) {\n    X = 1\n}");
+        assert_eq!(
+            Enum::parse(&mut tokens),
+            Ok(Enum {
+                name: Identifier("A".to_string()),
+                ty: IntegerType::U8,
+                variants: vec![Variant {
+                    name: Identifier("X".to_string()),
+                    value: Some(1),
+                    explicit: true,
+                    span: span(17, 22, 2, 5),
+                }],
+                span: span(0, 24, 1, 1),
+            })
+        );
+
+        let mut tokens: TokenStream = tokenize!("i32 length = len(data)");
+        assert_eq!(
+            Field::parse(&mut tokens),
+            Ok(Field {
+                ty: Type::BaseType(BaseType::Integer(IntegerType::I32)),
+                name: Identifier("length".to_string()),
+                value: Some(Value::Length(
+                    Identifier("data".to_string()),
+                    span(13, 22, 1, 14)
+                )),
+                conditions: HashSet::new(),
+                span: span(0, 22, 1, 1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_base_parse() {
+        use specmc_base::parse::Parse as BaseParse;
+
+        let mut tokens: Vec<String> = specmc_base::tokenize!("enum A(u8) { X Y = 3 } packet");
+        let e: Enum = <Enum as BaseParse>::parse(&mut tokens).unwrap();
+        assert_eq!(e.variants[1].value, Some(3));
+        assert_eq!(tokens, vec!["packet"]);
+
+        let mut tokens: Vec<String> = specmc_base::tokenize!("enum A(u7) {}");
+        assert_eq!(
+            <Enum as BaseParse>::parse(&mut tokens),
+            Err(ParseError::InvalidToken {
+                token: "u7".to_string(),
+                error: "Invalid integer type".to_string(),
+            })
+        );
+    }
+}
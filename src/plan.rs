@@ -0,0 +1,653 @@
+//! Decoding packets with compiled plans.
+//!
+//! [`ResolvedProtocol::compile`] lowers every packet and custom type into a [`Program`],
+//! a linear list of instructions: read a field, or jump past the fields of an `if` block
+//! whose condition doesn't hold. Lists loop over their count, and custom types call the
+//! program of the type. Names, conditions and lengths are all resolved up front,
+//! and the fields they refer to are kept in numbered registers,
+//! so [`Plan::decode`] does no lookups while it runs.
+//!
+//! The result is the same as [`decode_ref`](crate::decode::decode_ref), including errors,
+//! except that invalid conditions are reported in their resolved form.
+
+use specmc_base::parse::Identifier;
+
+use crate::{
+    base::IntegerType,
+    codec::{CodecError, Reader},
+    condition::{Condition, Operand},
+    decode::{join_path, DecodeError, DecodeErrorKind, PathPart, MAX_DEPTH},
+    nbt,
+    resolved::{
+        EnumId, ListLength, PacketId, ResolvedEnum, ResolvedField, ResolvedProtocol, ResolvedType,
+        TypeId,
+    },
+    value::PacketValueRef,
+};
+
+/// How many elements a list has.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Count {
+    Fixed(usize),
+    /// The value in the register with the given index.
+    Register(usize),
+    /// A VarInt before the elements.
+    Prefixed,
+}
+
+/// How a single value is read.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Read {
+    Bool,
+    Integer(IntegerType),
+    F32,
+    F64,
+    String {
+        max_length: usize,
+    },
+    /// A `List[u8]`, read as a single slice.
+    Bytes(Count),
+    List {
+        count: Count,
+        element: Box<Read>,
+    },
+    /// NBT, gzip compressed if its length in bytes is in the register with the given index.
+    Nbt {
+        length: Option<usize>,
+    },
+    Enum(EnumId),
+    /// Run the program of a custom type.
+    Call(TypeId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Read the value of a field into its slot,
+    /// and into a register if later instructions refer to it.
+    Read {
+        slot: usize,
+        read: Read,
+        register: Option<usize>,
+    },
+    /// Continue at `target` if the condition on registers doesn't hold.
+    JumpUnless {
+        condition: Condition<usize>,
+        target: usize,
+    },
+}
+
+/// The instructions to decode the fields of a packet or custom type.
+/// Each field has a slot, which holds its value once it is read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The names of the slots.
+    pub names: Vec<Identifier>,
+    /// The slot of each register, in increasing order.
+    pub registers: Vec<usize>,
+    pub ops: Vec<Op>,
+}
+impl Program {
+    fn compile(fields: &[ResolvedField]) -> Self {
+        // Fields that conditions and lengths refer to are kept in registers
+        let mut registers: Vec<usize> = vec![];
+        for field in fields {
+            registers.extend(field.conditions.iter().flat_map(Condition::fields));
+            referenced(&field.ty, &mut registers);
+        }
+        registers.sort_unstable();
+        registers.dedup();
+        let register = |slot: &usize| -> usize {
+            registers
+                .binary_search(slot)
+                .expect("referenced slots have registers")
+        };
+
+        let mut ops: Vec<Op> = vec![];
+        // The conditions of the enclosing `if` blocks, with the index of their jump
+        let mut open: Vec<(&Condition<usize>, usize)> = vec![];
+        for (slot, field) in fields.iter().enumerate() {
+            let shared: usize = open
+                .iter()
+                .zip(&field.conditions)
+                .take_while(|((open, _), condition)| open == condition)
+                .count();
+            for (_, jump) in open.drain(shared..) {
+                patch(&mut ops, jump);
+            }
+            for condition in &field.conditions[shared..] {
+                open.push((condition, ops.len()));
+                ops.push(Op::JumpUnless {
+                    condition: condition
+                        .try_map(&mut |slot: &usize| Ok::<_, ()>(Operand::Field(register(slot))))
+                        .expect("slots are mapped to registers"),
+                    target: 0,
+                });
+            }
+            ops.push(Op::Read {
+                slot,
+                read: read(&field.ty, &register),
+                register: registers.binary_search(&slot).ok(),
+            });
+        }
+        for (_, jump) in open {
+            patch(&mut ops, jump);
+        }
+
+        Program {
+            names: fields.iter().map(|field| field.name.clone()).collect(),
+            registers,
+            ops,
+        }
+    }
+}
+
+/// Add the slots that the length of a value refers to.
+fn referenced(ty: &ResolvedType, slots: &mut Vec<usize>) {
+    match ty {
+        ResolvedType::List { ty, length } => {
+            if let ListLength::Field(slot) = length {
+                slots.push(*slot);
+            }
+            referenced(ty, slots);
+        }
+        ResolvedType::Nbt { length: Some(slot) } => slots.push(*slot),
+        _ => {}
+    }
+}
+
+/// How to read a value of a type, given the register of each slot.
+fn read(ty: &ResolvedType, register: &impl Fn(&usize) -> usize) -> Read {
+    let count = |length: &ListLength| match length {
+        ListLength::Fixed(count) => Count::Fixed(*count),
+        ListLength::Field(slot) => Count::Register(register(slot)),
+        ListLength::Prefixed => Count::Prefixed,
+    };
+    match ty {
+        ResolvedType::Bool => Read::Bool,
+        ResolvedType::Integer(integer_type) => Read::Integer(integer_type.clone()),
+        ResolvedType::F32 => Read::F32,
+        ResolvedType::F64 => Read::F64,
+        ResolvedType::String { max_length } => Read::String {
+            max_length: *max_length,
+        },
+        ResolvedType::List { ty, length } => match **ty {
+            ResolvedType::Integer(IntegerType::U8) => Read::Bytes(count(length)),
+            _ => Read::List {
+                count: count(length),
+                element: Box::new(read(ty, register)),
+            },
+        },
+        ResolvedType::Nbt { length } => Read::Nbt {
+            length: length.as_ref().map(register),
+        },
+        ResolvedType::Enum(id) => Read::Enum(*id),
+        ResolvedType::Custom(id) => Read::Call(*id),
+    }
+}
+
+/// Point a jump at the end of the instructions so far.
+fn patch(ops: &mut [Op], jump: usize) {
+    let end: usize = ops.len();
+    if let Op::JumpUnless { target, .. } = &mut ops[jump] {
+        *target = end;
+    }
+}
+
+/// The compiled programs of all packets and custom types of a protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub enums: Vec<ResolvedEnum>,
+    /// Programs by [`TypeId`].
+    pub types: Vec<Program>,
+    /// Programs by [`PacketId`].
+    pub packets: Vec<Program>,
+}
+impl Plan {
+    /// Decode the body of a packet, without its id.
+    /// All bytes must belong to the packet.
+    pub fn decode<'a>(
+        &'a self,
+        packet: PacketId,
+        bytes: &'a [u8],
+    ) -> Result<PacketValueRef<'a>, DecodeError> {
+        let mut runner: Runner = Runner {
+            plan: self,
+            reader: Reader::new(bytes),
+            path: vec![],
+            depth: 0,
+        };
+        let value: PacketValueRef = runner.run(&self.packets[packet.0])?;
+        let remaining: usize = runner.reader.remaining().len();
+        if remaining != 0 {
+            return Err(runner.error(DecodeErrorKind::TrailingBytes(remaining)));
+        }
+        Ok(value)
+    }
+}
+
+impl ResolvedProtocol {
+    /// Compile all packets and custom types into decode programs.
+    pub fn compile(&self) -> Plan {
+        Plan {
+            enums: self.enums.clone(),
+            types: self
+                .types
+                .iter()
+                .map(|ty| Program::compile(&ty.fields))
+                .collect(),
+            packets: self
+                .packets
+                .iter()
+                .map(|packet| Program::compile(&packet.fields))
+                .collect(),
+        }
+    }
+}
+
+/// The fields of a program that have been read, in the order of their slots.
+struct Slots<'a> {
+    program: &'a Program,
+    fields: Vec<(&'a str, PacketValueRef<'a>)>,
+    /// The index in `fields` of the value of each register, once it is read.
+    registers: Vec<Option<usize>>,
+}
+impl<'a> Slots<'a> {
+    fn get(&self, register: usize) -> Option<&PacketValueRef<'a>> {
+        self.registers[register].map(|index| &self.fields[index].1)
+    }
+}
+
+struct Runner<'a> {
+    plan: &'a Plan,
+    reader: Reader<'a>,
+    path: Vec<PathPart<'a>>,
+    /// The number of custom types being decoded.
+    depth: usize,
+}
+impl<'a> Runner<'a> {
+    fn error(&self, kind: DecodeErrorKind) -> DecodeError {
+        DecodeError {
+            kind,
+            path: join_path(&self.path),
+            offset: self.reader.offset(),
+        }
+    }
+
+    fn read<T>(
+        &mut self,
+        read: impl FnOnce(&mut Reader<'a>) -> Result<T, CodecError>,
+    ) -> Result<T, DecodeError> {
+        read(&mut self.reader).map_err(|error| self.error(DecodeErrorKind::Codec(error)))
+    }
+
+    fn run(&mut self, program: &'a Program) -> Result<PacketValueRef<'a>, DecodeError> {
+        let mut slots: Slots = Slots {
+            program,
+            fields: Vec::with_capacity(program.names.len()),
+            registers: vec![None; program.registers.len()],
+        };
+        let mut pc: usize = 0;
+        while let Some(op) = program.ops.get(pc) {
+            pc += 1;
+            match op {
+                Op::Read {
+                    slot,
+                    read,
+                    register,
+                } => {
+                    self.path.push(PathPart::Field(&program.names[*slot].0));
+                    let value: PacketValueRef = self.value(read, &slots)?;
+                    if let Some(register) = register {
+                        slots.registers[*register] = Some(slots.fields.len());
+                    }
+                    slots.fields.push((&program.names[*slot].0, value));
+                    self.path.pop();
+                }
+                Op::JumpUnless { condition, target } => {
+                    let holds: Option<bool> = condition.evaluate(&mut |register: &usize| {
+                        slots.get(*register).and_then(PacketValueRef::literal)
+                    });
+                    match holds {
+                        Some(true) => {}
+                        Some(false) => pc = *target,
+                        None => {
+                            // Report the error at the first field of the block, like `decode`
+                            if let Some(slot) = program.ops[pc..].iter().find_map(|op| match op {
+                                Op::Read { slot, .. } => Some(*slot),
+                                Op::JumpUnless { .. } => None,
+                            }) {
+                                self.path.push(PathPart::Field(&program.names[slot].0));
+                            }
+                            let condition: Condition = condition
+                                .try_map(&mut |register: &usize| {
+                                    let slot: usize = program.registers[*register];
+                                    Ok::<_, ()>(Operand::Field(program.names[slot].clone()))
+                                })
+                                .expect("registers are mapped to names");
+                            return Err(self
+                                .error(DecodeErrorKind::InvalidCondition(condition.to_string())));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(PacketValueRef::Struct(slots.fields))
+    }
+
+    fn count(&mut self, count: &Count, slots: &Slots) -> Result<usize, DecodeError> {
+        match count {
+            Count::Fixed(count) => Ok(*count),
+            Count::Register(register) => self.length(*register, slots),
+            Count::Prefixed => self.read(Reader::read_length),
+        }
+    }
+
+    /// The length in the register of a `len(...)` field.
+    fn length(&self, register: usize, slots: &Slots) -> Result<usize, DecodeError> {
+        let length: i128 = slots
+            .get(register)
+            .and_then(PacketValueRef::as_integer)
+            .ok_or_else(|| {
+                let slot: usize = slots.program.registers[register];
+                self.error(DecodeErrorKind::MissingLength(
+                    slots.program.names[slot].clone(),
+                ))
+            })?;
+        usize::try_from(length)
+            .map_err(|_| self.error(DecodeErrorKind::Codec(CodecError::InvalidLength(length))))
+    }
+
+    fn value(&mut self, read: &'a Read, slots: &Slots) -> Result<PacketValueRef<'a>, DecodeError> {
+        Ok(match read {
+            Read::Bool => PacketValueRef::Bool(self.read(Reader::read_bool)?),
+            Read::Integer(integer_type) => {
+                PacketValueRef::Integer(self.read(|reader| reader.read_integer(integer_type))?)
+            }
+            Read::F32 => PacketValueRef::Float(self.read(Reader::read_f32)? as f64),
+            Read::F64 => PacketValueRef::Float(self.read(Reader::read_f64)?),
+            Read::String { max_length } => {
+                PacketValueRef::String(self.read(|reader| reader.read_str(*max_length))?)
+            }
+            Read::Bytes(count) => {
+                let count: usize = self.count(count, slots)?;
+                PacketValueRef::Bytes(self.read(|reader| reader.read_bytes(count))?)
+            }
+            Read::List { count, element } => {
+                let count: usize = self.count(count, slots)?;
+                // The count comes from the input, so it isn't used to preallocate
+                let mut values: Vec<PacketValueRef> = vec![];
+                for i in 0..count {
+                    self.path.push(PathPart::Index(i));
+                    values.push(self.value(element, slots)?);
+                    self.path.pop();
+                }
+                PacketValueRef::List(values)
+            }
+            Read::Nbt {
+                length: Some(register),
+            } => {
+                let length: usize = self.length(*register, slots)?;
+                let start: Reader = self.reader.clone();
+                let bytes: &[u8] = self.read(|reader| reader.read_bytes(length))?;
+                PacketValueRef::Nbt(nbt::read_compressed(bytes).map_err(|error| {
+                    self.reader = start;
+                    self.error(DecodeErrorKind::Codec(error))
+                })?)
+            }
+            Read::Nbt { length: None } => PacketValueRef::RawNbt(self.read(nbt::read_raw_nbt)?),
+            Read::Enum(id) => {
+                let e: &ResolvedEnum = &self.plan.enums[id.0];
+                let start: Reader = self.reader.clone();
+                let value: i128 = self.read(|reader| reader.read_integer(&e.ty))?;
+                let Some(variant) = isize::try_from(value)
+                    .ok()
+                    .and_then(|value| e.variant(value))
+                else {
+                    self.reader = start;
+                    return Err(self.error(DecodeErrorKind::UnknownVariant {
+                        name: e.name.clone(),
+                        value,
+                    }));
+                };
+                PacketValueRef::Enum {
+                    variant: &variant.0,
+                    value,
+                }
+            }
+            Read::Call(id) => {
+                if self.depth >= MAX_DEPTH {
+                    return Err(self.error(DecodeErrorKind::TooDeep));
+                }
+                self.depth += 1;
+                let value: PacketValueRef = self.run(&self.plan.types[id.0])?;
+                self.depth -= 1;
+                value
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode::decode_ref,
+        packets::Packet,
+        parse::{Parse, TokenStream},
+        tokenize, Protocol,
+    };
+
+    use super::*;
+
+    fn protocol() -> Protocol {
+        let mut tokens: TokenStream = tokenize!(
+            "
+            enum State(VarInt) {
+                Play
+            }
+            enum Mode(u8) {
+                Survival
+                Creative
+            }
+            type Slot {
+                i16 id
+                if (id != -1) {
+                    i8 count
+                    if (count > 1) {
+                        String name
+                    }
+                    i16 damage
+                }
+            }
+            type Node {
+                bool more
+                if (more) {
+                    Node next
+                }
+            }
+            packet Window(clientbound, Play, 0x30) {
+                Mode mode
+                if (mode == Creative) {
+                    f32 speed
+                }
+                List[Slot] slots
+                i8 length = len(data)
+                List[u8] data
+                List[bool; 2] flags
+            }
+            packet Text(clientbound, Play, 0x31) {
+                String text
+                if (text) {
+                    bool flag
+                }
+            }
+            packet Chain(clientbound, Play, 0x32) {
+                Node node
+            }
+            "
+        );
+        Protocol::parse(&mut tokens).unwrap()
+    }
+
+    #[test]
+    fn test_compile() {
+        let protocol: ResolvedProtocol = protocol().resolve().unwrap();
+        let plan: Plan = protocol.compile();
+        let program: &Program = &plan.types[protocol.find_type("Slot").unwrap().0];
+        let jumps: Vec<(String, usize)> = program
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::JumpUnless { condition, target } => Some((
+                    condition
+                        .try_map(&mut |register: &usize| {
+                            let slot: usize = program.registers[*register];
+                            Ok::<_, ()>(Operand::Field(program.names[slot].clone()))
+                        })
+                        .unwrap()
+                        .to_string(),
+                    *target,
+                )),
+                Op::Read { .. } => None,
+            })
+            .collect();
+        // id, jump, count, jump, name, damage
+        assert_eq!(program.ops.len(), 6);
+        assert_eq!(
+            jumps,
+            vec![("id != -1".to_string(), 6), ("count > 1".to_string(), 5)]
+        );
+
+        // The mode and length are kept in registers
+        let window: &Program = &plan.packets[protocol.find_packet("Window").unwrap().0];
+        assert_eq!(window.registers, vec![0, 3]);
+        assert_eq!(
+            window.ops[4],
+            Op::Read {
+                slot: 3,
+                read: Read::Integer(IntegerType::I8),
+                register: Some(1),
+            }
+        );
+        assert_eq!(
+            window.ops[5],
+            Op::Read {
+                slot: 4,
+                read: Read::Bytes(Count::Register(1)),
+                register: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_plan_decode() {
+        let parsed: Protocol = protocol();
+        let protocol: ResolvedProtocol = parsed.resolve().unwrap();
+        let plan: Plan = protocol.compile();
+        let packet = |name: &str| -> (&Packet, PacketId) {
+            (
+                parsed
+                    .packets
+                    .iter()
+                    .find(|packet| packet.name.0 == name)
+                    .unwrap(),
+                protocol.find_packet(name).unwrap(),
+            )
+        };
+        let same = |name: &str, bytes: &[u8]| {
+            let (packet, id) = packet(name);
+            assert_eq!(
+                plan.decode(id, bytes),
+                decode_ref(&parsed, packet, bytes),
+                "{name} {bytes:?}"
+            );
+        };
+
+        let bytes: Vec<u8> = vec![
+            0x01, 0x3f, 0x80, 0x00, 0x00, // Creative, 1.0
+            0x03, 0xff, 0xff, // empty slot
+            0x00, 0x01, 0x01, 0x00, 0x00, // one item
+            0x00, 0x02, 0x02, 0x01, b'a', 0x00, 0x07, // two named items
+            0x02, 0xfe, 0x01, // data
+            0x00, 0x01, // flags
+        ];
+        let value: PacketValueRef = plan.decode(packet("Window").1, &bytes).unwrap();
+        assert_eq!(value.path("slots.0.count"), None);
+        assert_eq!(value.path("slots.1.name"), None);
+        assert_eq!(
+            value.path("slots.1.damage"),
+            Some(&PacketValueRef::Integer(0))
+        );
+        assert_eq!(value.path("slots.2.name").unwrap().as_str(), Some("a"));
+        assert_eq!(
+            value.get("data").unwrap().as_bytes(),
+            Some(&[0xfe, 0x01][..])
+        );
+        same("Window", &bytes);
+        same("Window", &[0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        // Errors are the same as well
+        same("Window", &bytes[..bytes.len() - 1]);
+        same("Window", &[0x02]);
+        same("Window", &[0x00, 0x01, 0x00, 0x01, 0x02, 0x01]);
+        same("Window", &[0x00, 0x00, 0xff]);
+        same("Window", &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            plan.decode(packet("Text").1, &[0x01, b'a']).unwrap_err(),
+            DecodeError {
+                kind: DecodeErrorKind::InvalidCondition("text".to_string()),
+                path: "flag".to_string(),
+                offset: 2,
+            }
+        );
+        same("Text", &[0x01, b'a']);
+        same("Chain", &[0x01, 0x01, 0x00]);
+        assert_eq!(
+            plan.decode(packet("Chain").1, &vec![0x01; 2_000_000])
+                .unwrap_err()
+                .kind,
+            DecodeErrorKind::TooDeep
+        );
+        same("Chain", &vec![0x01; 2_000_000]);
+    }
+
+    #[test]
+    fn test_plan_spec() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let parsed: Protocol = Protocol::parse(&mut tokens).unwrap();
+        let protocol: ResolvedProtocol = parsed.resolve().unwrap();
+        let plan: Plan = protocol.compile();
+        let same = |name: &str, bytes: &[u8]| {
+            let packet: &Packet = parsed
+                .packets
+                .iter()
+                .find(|packet| packet.name.0 == name)
+                .unwrap();
+            assert_eq!(
+                plan.decode(protocol.find_packet(name).unwrap(), bytes),
+                decode_ref(&parsed, packet, bytes),
+                "{name}"
+            );
+        };
+
+        let mut handshake: Vec<u8> = vec![0x04, 0x09];
+        handshake.extend(b"localhost");
+        handshake.extend([0x63, 0xdd, 0x02]);
+        same("Handshake", &handshake);
+        same("Handshake", &handshake[..5]);
+        same(
+            "PlayerBlockPlacement",
+            &[
+                0x00, 0x00, 0x00, 0x01, 0x40, 0xff, 0xff, 0xff, 0xfe,
+                0x01, // position and face
+                0xff, 0xff, // empty slot
+                0x08, 0x00, 0x0f,
+            ],
+        );
+        same(
+            "EncryptionResponse",
+            &[0x00, 0x02, 0xaa, 0xbb, 0x00, 0x01, 0xcc],
+        );
+        same("EncryptionResponse", &[0x00, 0x02, 0xaa]);
+    }
+}
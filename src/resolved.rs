@@ -0,0 +1,804 @@
+//! A resolved representation of a [`Protocol`].
+//!
+//! All names are replaced by typed indices into the arenas of [`ResolvedProtocol`],
+//! conditions are parsed and length fields are linked to the fields they describe.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Index,
+};
+
+use specmc_base::{
+    ensure,
+    parse::{Identifier, Literal},
+};
+
+use crate::{
+    base::{BaseType, FieldList, IntegerType, Value},
+    condition::{Condition, Operand},
+    packets::Direction,
+    parse::Span,
+    types::Type,
+    Error, Protocol,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EnumId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PacketId(pub usize);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedEnum {
+    pub name: Identifier,
+    pub ty: IntegerType,
+    pub variants: Vec<(Identifier, isize)>,
+}
+impl ResolvedEnum {
+    /// Find the name of the variant with the given value.
+    pub fn variant(&self, value: isize) -> Option<&Identifier> {
+        self.variants
+            .iter()
+            .find(|(_, v)| *v == value)
+            .map(|(name, _)| name)
+    }
+
+    /// Find the value of the variant with the given name.
+    pub fn value(&self, name: &str) -> Option<isize> {
+        self.variants
+            .iter()
+            .find(|(variant, _)| variant.0 == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// How the number of elements of a list is determined.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListLength {
+    /// A fixed number of elements.
+    Fixed(usize),
+    /// The value of the field with the given index.
+    Field(usize),
+    /// A VarInt before the elements.
+    Prefixed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResolvedType {
+    Bool,
+    Integer(IntegerType),
+    F32,
+    F64,
+    String {
+        max_length: usize,
+    },
+    List {
+        ty: Box<ResolvedType>,
+        length: ListLength,
+    },
+    /// NBT data, optionally with its length in bytes given by the field with the given index.
+    Nbt {
+        length: Option<usize>,
+    },
+    Enum(EnumId),
+    Custom(TypeId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedValue {
+    Literal(Literal),
+    /// The length of the field with the given index.
+    Length(usize),
+    /// The value of the field with the given index.
+    Field(usize),
+    /// A constant of the protocol, like `PVN`.
+    Constant(Identifier),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedField {
+    pub name: Identifier,
+    pub ty: ResolvedType,
+    pub value: Option<ResolvedValue>,
+    /// Conditions that must all hold for the field to be present.
+    /// Fields are referred to by their index.
+    pub conditions: Vec<Condition<usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedCustomType {
+    pub name: Identifier,
+    pub fields: Vec<ResolvedField>,
+}
+
+/// The state a connection switches to after a packet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Transition {
+    State(Identifier),
+    /// The state named by the variant of the enum field with the given index.
+    Field(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedPacket {
+    pub name: Identifier,
+    pub direction: Direction,
+    pub state: Identifier,
+    pub id: u32,
+    pub fields: Vec<ResolvedField>,
+    pub transition: Option<Transition>,
+    /// The index of the field with the compression threshold, from `@compression(...)`.
+    pub compression: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedProtocol {
+    pub enums: Vec<ResolvedEnum>,
+    pub types: Vec<ResolvedCustomType>,
+    pub packets: Vec<ResolvedPacket>,
+    /// Packets by state and direction, ordered by id.
+    pub packet_tables: HashMap<(Identifier, Direction), BTreeMap<u32, PacketId>>,
+}
+impl ResolvedProtocol {
+    pub fn find_enum(&self, name: &str) -> Option<EnumId> {
+        self.enums.iter().position(|e| e.name.0 == name).map(EnumId)
+    }
+
+    pub fn find_type(&self, name: &str) -> Option<TypeId> {
+        self.types
+            .iter()
+            .position(|ty| ty.name.0 == name)
+            .map(TypeId)
+    }
+
+    pub fn find_packet(&self, name: &str) -> Option<PacketId> {
+        self.packets
+            .iter()
+            .position(|packet| packet.name.0 == name)
+            .map(PacketId)
+    }
+
+    /// Find the packet with the given id in a state and direction.
+    pub fn packet_id(
+        &self,
+        state: &Identifier,
+        direction: &Direction,
+        id: u32,
+    ) -> Option<PacketId> {
+        self.packet_tables
+            .get(&(state.clone(), direction.clone()))?
+            .get(&id)
+            .copied()
+    }
+}
+impl Index<EnumId> for ResolvedProtocol {
+    type Output = ResolvedEnum;
+
+    fn index(&self, index: EnumId) -> &Self::Output {
+        &self.enums[index.0]
+    }
+}
+impl Index<TypeId> for ResolvedProtocol {
+    type Output = ResolvedCustomType;
+
+    fn index(&self, index: TypeId) -> &Self::Output {
+        &self.types[index.0]
+    }
+}
+impl Index<PacketId> for ResolvedProtocol {
+    type Output = ResolvedPacket;
+
+    fn index(&self, index: PacketId) -> &Self::Output {
+        &self.packets[index.0]
+    }
+}
+
+impl Protocol {
+    /// Resolve all names in the protocol.
+    pub fn resolve(&self) -> Result<ResolvedProtocol, Error> {
+        let mut names: Vec<&Identifier> = vec![];
+        for (name, span) in self
+            .enums
+            .iter()
+            .map(|e| (&e.name, e.span))
+            .chain(self.types.iter().map(|ty| (&ty.name, ty.span)))
+        {
+            ensure!(
+                !names.contains(&name),
+                Error::DuplicateTypeName {
+                    name: name.clone(),
+                    span,
+                }
+            );
+            names.push(name);
+        }
+
+        let enums: Vec<ResolvedEnum> = self
+            .enums
+            .iter()
+            .map(|e| ResolvedEnum {
+                name: e.name.clone(),
+                ty: e.ty.clone(),
+                variants: e
+                    .variants
+                    .iter()
+                    .map(|variant| (variant.name.clone(), variant.value.unwrap_or_default()))
+                    .collect(),
+            })
+            .collect();
+
+        let resolver: Resolver = Resolver {
+            protocol: self,
+            enums: &enums,
+        };
+        let types: Vec<ResolvedCustomType> = self
+            .types
+            .iter()
+            .map(|ty| {
+                Ok(ResolvedCustomType {
+                    name: ty.name.clone(),
+                    fields: resolver.fields(&ty.fields)?,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        // The states are the variants of the enums that the states of packets belong to,
+        // and of the enum `State`
+        let state_enums: Vec<&ResolvedEnum> = enums
+            .iter()
+            .filter(|e| {
+                e.name.0 == "State"
+                    || self
+                        .packets
+                        .iter()
+                        .any(|packet| e.value(&packet.state.0).is_some())
+            })
+            .collect();
+        ensure!(
+            self.packets.is_empty() || !state_enums.is_empty(),
+            Error::MissingStateEnum
+        );
+        let is_state = |name: &str| state_enums.iter().any(|e| e.value(name).is_some());
+        let mut packets: Vec<ResolvedPacket> = vec![];
+        let mut packet_tables: HashMap<(Identifier, Direction), BTreeMap<u32, PacketId>> =
+            HashMap::new();
+        for packet in &self.packets {
+            ensure!(
+                is_state(&packet.state.0),
+                Error::UnknownState {
+                    name: packet.state.clone(),
+                    span: packet.span,
+                }
+            );
+            ensure!(
+                !packets.iter().any(|other| other.name == packet.name),
+                Error::DuplicatePacketName {
+                    name: packet.name.clone(),
+                    span: packet.span,
+                }
+            );
+            let table: &mut BTreeMap<u32, PacketId> = packet_tables
+                .entry((packet.state.clone(), packet.direction.clone()))
+                .or_default();
+            ensure!(
+                !table.contains_key(&packet.id),
+                Error::DuplicatePacketId {
+                    id: packet.id,
+                    span: packet.span,
+                }
+            );
+            table.insert(packet.id, PacketId(packets.len()));
+
+            let fields: Vec<ResolvedField> = resolver.fields(&packet.fields)?;
+            let transition: Option<Transition> = match &packet.transition {
+                None => None,
+                Some(name) => Some(match fields.iter().position(|field| &field.name == name) {
+                    Some(index) => {
+                        let ResolvedType::Enum(id) = fields[index].ty else {
+                            return Err(Error::InvalidTransitionField {
+                                name: name.clone(),
+                                span: packet.span,
+                            });
+                        };
+                        // Every variant of the field must name a state
+                        if let Some((variant, _)) = enums[id.0]
+                            .variants
+                            .iter()
+                            .find(|(variant, _)| !is_state(&variant.0))
+                        {
+                            return Err(Error::UnknownState {
+                                name: variant.clone(),
+                                span: packet.span,
+                            });
+                        }
+                        Transition::Field(index)
+                    }
+                    None => {
+                        ensure!(
+                            is_state(&name.0),
+                            Error::UnknownState {
+                                name: name.clone(),
+                                span: packet.span,
+                            }
+                        );
+                        Transition::State(name.clone())
+                    }
+                }),
+            };
+
+            let compression: Option<usize> = match &packet.compression {
+                None => None,
+                Some(name) => match fields.iter().position(|field| &field.name == name) {
+                    Some(index) if matches!(fields[index].ty, ResolvedType::Integer(_)) => {
+                        Some(index)
+                    }
+                    _ => {
+                        return Err(Error::InvalidCompressionField {
+                            name: name.clone(),
+                            span: packet.span,
+                        })
+                    }
+                },
+            };
+
+            packets.push(ResolvedPacket {
+                name: packet.name.clone(),
+                direction: packet.direction.clone(),
+                state: packet.state.clone(),
+                id: packet.id,
+                fields,
+                transition,
+                compression,
+            });
+        }
+
+        Ok(ResolvedProtocol {
+            enums,
+            types,
+            packets,
+            packet_tables,
+        })
+    }
+}
+
+struct Resolver<'a> {
+    protocol: &'a Protocol,
+    enums: &'a [ResolvedEnum],
+}
+impl Resolver<'_> {
+    fn ty(&self, ty: &Type, span: Span) -> Result<ResolvedType, Error> {
+        Ok(match ty {
+            Type::BaseType(base_type) => match base_type {
+                BaseType::Bool => ResolvedType::Bool,
+                BaseType::Integer(integer_type) => ResolvedType::Integer(integer_type.clone()),
+                BaseType::F32 => ResolvedType::F32,
+                BaseType::F64 => ResolvedType::F64,
+                BaseType::String { length } => ResolvedType::String {
+                    max_length: length.unwrap_or(BaseType::MAX_STRING_LENGTH),
+                },
+                BaseType::List { ty, length } => ResolvedType::List {
+                    ty: Box::new(self.ty(ty, span)?),
+                    length: length.map_or(ListLength::Prefixed, ListLength::Fixed),
+                },
+                BaseType::Nbt => ResolvedType::Nbt { length: None },
+            },
+            Type::CustomType(name) => {
+                if let Some(index) = self.protocol.enums.iter().position(|e| &e.name == name) {
+                    ResolvedType::Enum(EnumId(index))
+                } else if let Some(index) =
+                    self.protocol.types.iter().position(|ty| &ty.name == name)
+                {
+                    ResolvedType::Custom(TypeId(index))
+                } else {
+                    return Err(Error::UnknownType {
+                        name: name.clone(),
+                        span,
+                    });
+                }
+            }
+        })
+    }
+
+    fn fields(&self, fields: &FieldList) -> Result<Vec<ResolvedField>, Error> {
+        let fields: &Vec<crate::base::Field> = &fields.0;
+        let find = |name: &Identifier| fields.iter().position(|field| &field.name == name);
+
+        let mut resolved: Vec<ResolvedField> = vec![];
+        for (index, field) in fields.iter().enumerate() {
+            ensure!(
+                find(&field.name) == Some(index),
+                Error::DuplicateField {
+                    name: field.name.clone(),
+                    span: field.span,
+                }
+            );
+            let ty: ResolvedType = self.ty(&field.ty, field.span)?;
+
+            let value: Option<ResolvedValue> = match &field.value {
+                None => None,
+                Some(Value::Literal(literal, _)) => Some(ResolvedValue::Literal(literal.clone())),
+                Some(Value::Length(name, span)) => {
+                    Some(ResolvedValue::Length(find(name).ok_or_else(|| {
+                        Error::UnknownLengthField {
+                            name: name.clone(),
+                            span: *span,
+                        }
+                    })?))
+                }
+                Some(Value::Identifier(name, span)) => Some(match (find(name), &ty) {
+                    (Some(other), _) => ResolvedValue::Field(other),
+                    (None, ResolvedType::Enum(id)) if self.enums[id.0].value(&name.0).is_some() => {
+                        ResolvedValue::Literal(Literal::Integer(
+                            self.enums[id.0].value(&name.0).unwrap(),
+                        ))
+                    }
+                    (None, _) => {
+                        ensure!(
+                            self.protocol.get_constant(name).is_some(),
+                            Error::UnknownConstant {
+                                name: name.clone(),
+                                span: *span,
+                            }
+                        );
+                        ResolvedValue::Constant(name.clone())
+                    }
+                }),
+            };
+
+            // Order conditions by the fields they refer to, so outer conditions come first
+            let mut conditions: Vec<&String> = field.conditions.iter().collect();
+            conditions.sort();
+            let mut conditions: Vec<Condition<usize>> = conditions
+                .into_iter()
+                .map(|condition| self.condition(condition, field.span, fields, &resolved))
+                .collect::<Result<_, Error>>()?;
+            conditions.sort_by_key(|condition| condition.fields().into_iter().max().copied());
+
+            resolved.push(ResolvedField {
+                name: field.name.clone(),
+                ty,
+                value,
+                conditions,
+            });
+        }
+
+        // Link lists and NBT to the fields that contain their length
+        for index in 0..resolved.len() {
+            let Some(ResolvedValue::Length(target)) = resolved[index].value else {
+                continue;
+            };
+            match &mut resolved[target].ty {
+                ResolvedType::List {
+                    length: length @ ListLength::Prefixed,
+                    ..
+                } => *length = ListLength::Field(index),
+                ResolvedType::Nbt {
+                    length: length @ None,
+                } => *length = Some(index),
+                _ => {
+                    return Err(Error::InvalidLengthField {
+                        name: resolved[target].name.clone(),
+                        span: fields[index].span,
+                    })
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Parse a condition and resolve the fields it refers to.
+    /// Names that are not fields may be variants of an enum field in the same condition.
+    fn condition(
+        &self,
+        condition: &str,
+        span: Span,
+        fields: &[crate::base::Field],
+        previous: &[ResolvedField],
+    ) -> Result<Condition<usize>, Error> {
+        let condition: Condition = condition.parse().map_err(|error: Error| error.at(span))?;
+        let find = |name: &Identifier| previous.iter().position(|field| &field.name == name);
+
+        let enums: Vec<&ResolvedEnum> = condition
+            .fields()
+            .into_iter()
+            .filter_map(|name| match &previous[find(name)?].ty {
+                ResolvedType::Enum(id) => Some(&self.enums[id.0]),
+                _ => None,
+            })
+            .collect();
+
+        condition.try_map(&mut |name: &Identifier| {
+            if let Some(index) = find(name) {
+                return Ok(Operand::Field(index));
+            }
+            if let Some(value) = enums.iter().find_map(|e| e.value(&name.0)) {
+                return Ok(Operand::Literal(Literal::Integer(value)));
+            }
+            if fields.iter().any(|field| &field.name == name) {
+                return Err(Error::LaterFieldInCondition {
+                    name: name.clone(),
+                    span,
+                });
+            }
+            Err(Error::UnknownFieldInCondition {
+                name: name.clone(),
+                span,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use crate::condition::Comparison;
+
+    use super::*;
+
+    fn resolve(input: &str) -> Result<ResolvedProtocol, Error> {
+        let mut tokens: TokenStream = tokenize!(input);
+        Protocol::parse(&mut tokens).unwrap().resolve()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let protocol: ResolvedProtocol = resolve(
+            "
+            const PVN = 4
+            enum State(i32) {
+                Handshake
+                Status
+                Login
+                Play
+            }
+            enum NextState(VarInt) {
+                Status = 1
+                Login
+            }
+            type Slot {
+                i16 id
+                if (id != -1) {
+                    i8 count
+                    i16 data_length
+                    if (data_length != -1) {
+                        List[u8; 4] data
+                    }
+                }
+            }
+            @transition(next_state)
+            packet Handshake(serverbound, Handshake, 0x00) {
+                VarInt protocol_version = PVN
+                NextState next_state
+                if (next_state == Login) {
+                    Slot slot
+                }
+            }
+            @transition(Play)
+            packet EncryptionResponse(serverbound, Play, 0x01) {
+                u16 shared_secret_length = len(shared_secret)
+                List[u8] shared_secret
+                List[String] names
+            }
+            ",
+        )
+        .unwrap();
+
+        let slot: TypeId = protocol.find_type("Slot").unwrap();
+        let next_state: EnumId = protocol.find_enum("NextState").unwrap();
+        assert_eq!(protocol[next_state].value("Login"), Some(2));
+        assert_eq!(
+            protocol[next_state].variant(1),
+            Some(&Identifier("Status".to_string()))
+        );
+        assert_eq!(
+            protocol[slot].fields[3].conditions,
+            vec![
+                Condition::Compare(
+                    Operand::Field(0),
+                    Comparison::Ne,
+                    Operand::Literal(Literal::Integer(-1))
+                ),
+                Condition::Compare(
+                    Operand::Field(2),
+                    Comparison::Ne,
+                    Operand::Literal(Literal::Integer(-1))
+                ),
+            ]
+        );
+
+        let handshake: &ResolvedPacket = &protocol[protocol
+            .packet_id(
+                &Identifier("Handshake".to_string()),
+                &Direction::Serverbound,
+                0,
+            )
+            .unwrap()];
+        assert_eq!(handshake.name.0, "Handshake");
+        assert_eq!(
+            handshake.fields[0].value,
+            Some(ResolvedValue::Constant(Identifier("PVN".to_string())))
+        );
+        assert_eq!(handshake.fields[1].ty, ResolvedType::Enum(next_state));
+        assert_eq!(handshake.fields[2].ty, ResolvedType::Custom(slot));
+        assert_eq!(handshake.transition, Some(Transition::Field(1)));
+        assert_eq!(
+            handshake.fields[2].conditions,
+            vec![Condition::Compare(
+                Operand::Field(1),
+                Comparison::Eq,
+                Operand::Literal(Literal::Integer(2))
+            )]
+        );
+
+        let encryption_response: &ResolvedPacket =
+            &protocol[protocol.find_packet("EncryptionResponse").unwrap()];
+        assert_eq!(
+            encryption_response.fields[0].value,
+            Some(ResolvedValue::Length(1))
+        );
+        assert_eq!(
+            encryption_response.transition,
+            Some(Transition::State(Identifier("Play".to_string())))
+        );
+        assert_eq!(
+            encryption_response.fields[1].ty,
+            ResolvedType::List {
+                ty: Box::new(ResolvedType::Integer(IntegerType::U8)),
+                length: ListLength::Field(0),
+            }
+        );
+        assert_eq!(
+            encryption_response.fields[2].ty,
+            ResolvedType::List {
+                ty: Box::new(ResolvedType::String { max_length: 32767 }),
+                length: ListLength::Prefixed,
+            }
+        );
+        assert!(protocol
+            .packet_id(&Identifier("Play".to_string()), &Direction::Clientbound, 1)
+            .is_none());
+
+        // The states don't have to be in an enum called `State`
+        let protocol: ResolvedProtocol = resolve(
+            "enum States(i32) { Login Play } @transition(Play) packet P(clientbound, Login, 0) {}",
+        )
+        .unwrap();
+        assert_eq!(
+            protocol.packets[0].transition,
+            Some(Transition::State(Identifier("Play".to_string())))
+        );
+
+        let protocol: ResolvedProtocol = resolve(
+            "enum State(i32) { Login } @compression(threshold) packet P(clientbound, Login, 3) { VarInt threshold }",
+        )
+        .unwrap();
+        assert_eq!(protocol.packets[0].compression, Some(0));
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let state: &str = "enum State(i32) { Play }";
+        let name = |name: &str| Identifier(name.to_string());
+        let span: Span = Span::default();
+        let cases: [(&str, Error, &str); 13] = [
+            (
+                "packet P(serverbound, Play, 0) { Unknown a }",
+                Error::UnknownType {
+                    name: name("Unknown"),
+                    span,
+                },
+                "1:59",
+            ),
+            (
+                "packet P(serverbound, Login, 0) {}",
+                Error::UnknownState {
+                    name: name("Login"),
+                    span,
+                },
+                "1:26",
+            ),
+            (
+                "packet P(serverbound, Play, 0) {} packet Q(serverbound, Play, 0) {}",
+                Error::DuplicatePacketId { id: 0, span },
+                "1:60",
+            ),
+            (
+                "packet P(serverbound, Play, 0) { i32 a i32 a }",
+                Error::DuplicateField {
+                    name: name("a"),
+                    span,
+                },
+                "1:65",
+            ),
+            (
+                "packet P(serverbound, Play, 0) { i32 a = len(b) }",
+                Error::UnknownLengthField {
+                    name: name("b"),
+                    span,
+                },
+                "1:67",
+            ),
+            (
+                "packet P(serverbound, Play, 0) { if (b) { i32 a } bool b }",
+                Error::LaterFieldInCondition {
+                    name: name("b"),
+                    span,
+                },
+                "1:68",
+            ),
+            (
+                "packet P(serverbound, Play, 0) { i32 a = len(b) String b }",
+                Error::InvalidLengthField {
+                    name: name("b"),
+                    span,
+                },
+                "1:59",
+            ),
+            (
+                "packet P(serverbound, Play, 0) { VarInt version = PVM }",
+                Error::UnknownConstant {
+                    name: name("PVM"),
+                    span,
+                },
+                "1:76",
+            ),
+            (
+                "@transition(Login) packet P(serverbound, Play, 0) {}",
+                Error::UnknownState {
+                    name: name("Login"),
+                    span,
+                },
+                "1:26",
+            ),
+            (
+                "@transition(a) packet P(serverbound, Play, 0) { i32 a }",
+                Error::InvalidTransitionField {
+                    name: name("a"),
+                    span,
+                },
+                "1:26",
+            ),
+            (
+                "enum E(u8) { Login } @transition(e) packet P(serverbound, Play, 0) { E e }",
+                Error::UnknownState {
+                    name: name("Login"),
+                    span,
+                },
+                "1:47",
+            ),
+            (
+                "@compression(a) packet P(serverbound, Play, 0) { String a }",
+                Error::InvalidCompressionField {
+                    name: name("a"),
+                    span,
+                },
+                "1:26",
+            ),
+            (
+                "@compression(b) packet P(serverbound, Play, 0) { VarInt a }",
+                Error::InvalidCompressionField {
+                    name: name("b"),
+                    span,
+                },
+                "1:26",
+            ),
+        ];
+        for (input, expected, position) in cases {
+            let error: Error = resolve(&format!("{state} {input}")).unwrap_err();
+            assert_eq!(error.clone().at(span), expected, "{input}");
+            assert_eq!(error.span().unwrap().to_string(), position, "{input}");
+        }
+        assert_eq!(
+            resolve("packet P(serverbound, Play, 0) {}"),
+            Err(Error::MissingStateEnum)
+        );
+    }
+}
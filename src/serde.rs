@@ -0,0 +1,742 @@
+//! Serde support, behind the `serde` feature.
+//!
+//! [`from_bytes`] decodes a packet and deserializes it into any type implementing
+//! [`Deserialize`], and [`to_bytes`] serializes a value and encodes it as a packet:
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct ChatMessage<'a> {
+//!     message: &'a str,
+//! }
+//! let chat: ChatMessage = from_bytes(&protocol, packet, &bytes)?;
+//! ```
+//!
+//! Fields are matched by name, conditional fields that are absent deserialize as `None`,
+//! and enums are matched by the names of their variants.
+//! Strings and bytes are borrowed from the input where possible.
+//! NBT fields deserialize as their uncompressed bytes, and serialize from them,
+//! as a `Vec<u8>` or with `serde_bytes`. NBT with a length field is compressed again.
+
+use std::fmt::Display;
+
+use serde::{
+    de::{
+        self,
+        value::{MapDeserializer, SeqDeserializer},
+        IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any,
+    ser::{self, Impossible},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    decode::{decode_ref, DecodeError},
+    encode::{encode, EncodeError},
+    nbt,
+    packets::Packet,
+    value::{PacketValue, PacketValueRef},
+    Protocol,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerdeError {
+    Decode(DecodeError),
+    Encode(EncodeError),
+    /// An error from a [`Serialize`] or [`Deserialize`] implementation,
+    /// or a value that can't be part of a packet.
+    Message(String),
+}
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerdeError::Decode(error) => write!(f, "{error}"),
+            SerdeError::Encode(error) => write!(f, "{error}"),
+            SerdeError::Message(message) => write!(f, "{message}"),
+        }
+    }
+}
+impl std::error::Error for SerdeError {}
+impl From<DecodeError> for SerdeError {
+    fn from(error: DecodeError) -> Self {
+        SerdeError::Decode(error)
+    }
+}
+impl From<EncodeError> for SerdeError {
+    fn from(error: EncodeError) -> Self {
+        SerdeError::Encode(error)
+    }
+}
+impl de::Error for SerdeError {
+    fn custom<T: Display>(message: T) -> Self {
+        SerdeError::Message(message.to_string())
+    }
+}
+impl ser::Error for SerdeError {
+    fn custom<T: Display>(message: T) -> Self {
+        SerdeError::Message(message.to_string())
+    }
+}
+
+/// Decode the body of a packet into `T`.
+pub fn from_bytes<'a, T: Deserialize<'a>>(
+    protocol: &'a Protocol,
+    packet: &'a Packet,
+    bytes: &'a [u8],
+) -> Result<T, SerdeError> {
+    T::deserialize(decode_ref(protocol, packet, bytes)?)
+}
+
+/// Encode `value` as the body of a packet.
+/// Fields that are computed by the specification, like `len(...)`, can be left out.
+pub fn to_bytes<T: Serialize + ?Sized>(
+    protocol: &Protocol,
+    packet: &Packet,
+    value: &T,
+) -> Result<Vec<u8>, SerdeError> {
+    Ok(encode(protocol, packet, &to_value(value)?)?)
+}
+
+/// Serialize `value` into a [`PacketValue`].
+/// Enum variants are serialized as their name, since their values are only known to the specification.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<PacketValue, SerdeError> {
+    value
+        .serialize(ValueSerializer)?
+        .ok_or_else(|| SerdeError::Message("Expected a value".to_string()))
+}
+
+fn visit_integer<'de, V: Visitor<'de>>(value: i128, visitor: V) -> Result<V::Value, SerdeError> {
+    if let Ok(value) = i64::try_from(value) {
+        visitor.visit_i64(value)
+    } else if let Ok(value) = u64::try_from(value) {
+        visitor.visit_u64(value)
+    } else {
+        visitor.visit_i128(value)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for PacketValueRef<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+impl<'de> Deserializer<'de> for PacketValueRef<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            PacketValueRef::Bool(value) => visitor.visit_bool(value),
+            PacketValueRef::Integer(value) => visit_integer(value, visitor),
+            PacketValueRef::Float(value) => visitor.visit_f64(value),
+            PacketValueRef::String(value) => visitor.visit_borrowed_str(value),
+            PacketValueRef::Bytes(value) | PacketValueRef::RawNbt(value) => {
+                visitor.visit_seq(SeqDeserializer::new(value.iter().copied()))
+            }
+            PacketValueRef::List(values) => {
+                visitor.visit_seq(SeqDeserializer::new(values.into_iter()))
+            }
+            PacketValueRef::Nbt(tag) => {
+                let mut bytes: Vec<u8> = vec![];
+                nbt::write_nbt(&mut bytes, tag.as_ref())
+                    .map_err(|error| SerdeError::Message(error.to_string()))?;
+                visitor.visit_seq(SeqDeserializer::new(bytes.into_iter()))
+            }
+            PacketValueRef::Struct(fields) => {
+                visitor.visit_map(MapDeserializer::new(fields.into_iter()))
+            }
+            PacketValueRef::Enum { variant, .. } => visitor.visit_borrowed_str(variant),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            PacketValueRef::Bytes(value) | PacketValueRef::RawNbt(value) => {
+                visitor.visit_borrowed_bytes(value)
+            }
+            PacketValueRef::Nbt(tag) => {
+                let mut bytes: Vec<u8> = vec![];
+                nbt::write_nbt(&mut bytes, tag.as_ref())
+                    .map_err(|error| SerdeError::Message(error.to_string()))?;
+                visitor.visit_byte_buf(bytes)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    // Absent fields are left out of the struct, so values that are present are always `Some`
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            PacketValueRef::Enum { variant, .. } | PacketValueRef::String(variant) => {
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // Enums can also be deserialized as their value
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            PacketValueRef::Enum { value, .. } => visit_integer(value, visitor),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_i64(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char str string unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Serializes into a [`PacketValue`], or `None` for values that leave a field out.
+struct ValueSerializer;
+impl ValueSerializer {
+    fn integer<T: TryInto<i128>>(value: T) -> Result<Option<PacketValue>, SerdeError> {
+        value
+            .try_into()
+            .map(|value| Some(PacketValue::Integer(value)))
+            .map_err(|_| SerdeError::Message("Integer out of range".to_string()))
+    }
+}
+impl Serializer for ValueSerializer {
+    type Ok = Option<PacketValue>;
+    type Error = SerdeError;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = StructSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = Impossible<Self::Ok, Self::Error>;
+
+    fn serialize_bool(self, value: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::Bool(value)))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<Self::Ok, Self::Error> {
+        Self::integer(value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::Float(value as f64)))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::Float(value)))
+    }
+
+    fn serialize_char(self, value: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::String(value.to_string())))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::String(value.to_string())))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::Bytes(value.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::Struct(vec![])))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::String(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(variant_error(name, variant))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(ListSerializer(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(variant_error(name, variant))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(StructSerializer {
+            fields: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(variant_error(name, variant))
+    }
+}
+
+/// Enums of the specification have no data, so only unit variants can be serialized.
+fn variant_error(name: &str, variant: &str) -> SerdeError {
+    SerdeError::Message(format!("Variant `{name}::{variant}` has data"))
+}
+
+struct ListSerializer(Vec<PacketValue>);
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+}
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Option<PacketValue>;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::List(self.0)))
+    }
+}
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Option<PacketValue>;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::List(self.0)))
+    }
+}
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Option<PacketValue>;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::List(self.0)))
+    }
+}
+
+/// Serializes structs and maps with string keys.
+/// Fields without a value are left out, like absent conditional fields.
+struct StructSerializer {
+    fields: Vec<(String, PacketValue)>,
+    key: Option<String>,
+}
+impl StructSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, name: String, value: &T) -> Result<(), SerdeError> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.fields.push((name, value));
+        }
+        Ok(())
+    }
+}
+impl ser::SerializeMap for StructSerializer {
+    type Ok = Option<PacketValue>;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        match to_value(key)? {
+            PacketValue::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(SerdeError::Message("Map keys must be strings".to_string())),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key: String = self.key.take().expect("serialize_key is called first");
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::Struct(self.fields)))
+    }
+}
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Option<PacketValue>;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.push(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(PacketValue::Struct(self.fields)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        nbt::{NamedTag, Nbt},
+        parse::{Parse, TokenStream},
+        tokenize,
+    };
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum NextState {
+        Status,
+        Login,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Handshake<'a> {
+        protocol_version: i32,
+        server_address: &'a str,
+        server_port: u16,
+        next_state: NextState,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Slot {
+        id: i16,
+        count: Option<i8>,
+        damage: Option<i16>,
+        #[serde(default, skip_serializing)]
+        data_length: i16,
+        data: Option<Vec<u8>>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct PlayerBlockPlacement {
+        x: i32,
+        y: u8,
+        z: i32,
+        direction: i8,
+        held_item: Slot,
+        cursor_x: i8,
+        cursor_y: i8,
+        cursor_z: i8,
+    }
+
+    fn packet<'a>(protocol: &'a Protocol, name: &str) -> &'a Packet {
+        protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_serde() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+
+        let mut bytes: Vec<u8> = vec![0x04, 0x09];
+        bytes.extend(b"localhost");
+        bytes.extend([0x63, 0xdd, 0x02]);
+        let handshake: Handshake =
+            from_bytes(&protocol, packet(&protocol, "Handshake"), &bytes).unwrap();
+        assert_eq!(
+            handshake,
+            Handshake {
+                protocol_version: 4,
+                server_address: "localhost",
+                server_port: 25565,
+                next_state: NextState::Login,
+            }
+        );
+        assert_eq!(
+            to_bytes(&protocol, packet(&protocol, "Handshake"), &handshake).unwrap(),
+            bytes
+        );
+
+        assert_eq!(
+            to_value(&NextState::Login),
+            Ok(PacketValue::String("Login".to_string()))
+        );
+
+        // Enums can be read as their value, and absent fields as `None`
+        #[derive(Debug, Deserialize)]
+        struct Raw {
+            next_state: u8,
+            missing: Option<i32>,
+        }
+        let raw: Raw = from_bytes(&protocol, packet(&protocol, "Handshake"), &bytes).unwrap();
+        assert_eq!(raw.next_state, 2);
+        assert_eq!(raw.missing, None);
+
+        let bytes: Vec<u8> = vec![
+            0x00, 0x00, 0x00, 0x01, 0x40, 0xff, 0xff, 0xff, 0xfe, 0x01, // position and face
+            0xff, 0xff, // empty slot
+            0x08, 0x00, 0x0f,
+        ];
+        let placement: PlayerBlockPlacement =
+            from_bytes(&protocol, packet(&protocol, "PlayerBlockPlacement"), &bytes).unwrap();
+        assert_eq!(placement.held_item.id, -1);
+        assert_eq!(placement.held_item.count, None);
+        assert_eq!(placement.cursor_z, 15);
+        assert_eq!(
+            to_bytes(
+                &protocol,
+                packet(&protocol, "PlayerBlockPlacement"),
+                &placement
+            )
+            .unwrap(),
+            bytes
+        );
+
+        // Item data is compressed NBT, which round-trips through its uncompressed bytes
+        let tag: NamedTag = NamedTag {
+            name: String::new(),
+            tag: Nbt::Compound(vec![("id".to_string(), Nbt::Short(1))]),
+        };
+        let compressed: Vec<u8> = nbt::write_compressed(Some(&tag)).unwrap();
+        let mut bytes: Vec<u8> = vec![
+            0x00, 0x00, 0x00, 0x01, 0x40, 0xff, 0xff, 0xff, 0xfe, 0x01, // position and face
+            0x00, 0x01, 0x40, 0x00, 0x00, // id, count and damage
+        ];
+        bytes.extend((compressed.len() as i16).to_be_bytes());
+        bytes.extend(&compressed);
+        bytes.extend([0x08, 0x00, 0x0f]);
+        let placement: PlayerBlockPlacement =
+            from_bytes(&protocol, packet(&protocol, "PlayerBlockPlacement"), &bytes).unwrap();
+        let mut uncompressed: Vec<u8> = vec![];
+        nbt::write_nbt(&mut uncompressed, Some(&tag)).unwrap();
+        assert_eq!(placement.held_item.data, Some(uncompressed));
+        assert_eq!(
+            to_bytes(
+                &protocol,
+                packet(&protocol, "PlayerBlockPlacement"),
+                &placement
+            )
+            .unwrap(),
+            bytes
+        );
+
+        // Byte lists can be serialized as bytes
+        struct Bytes<'a>(&'a [u8]);
+        impl Serialize for Bytes<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+        #[derive(Serialize)]
+        struct Secret<'a> {
+            shared_secret: Bytes<'a>,
+            verify_token: Bytes<'a>,
+        }
+        let secret: Secret = Secret {
+            shared_secret: Bytes(&[0xaa, 0xbb]),
+            verify_token: Bytes(&[0xcc]),
+        };
+        assert_eq!(
+            to_bytes(&protocol, packet(&protocol, "EncryptionResponse"), &secret).unwrap(),
+            [0x00, 0x02, 0xaa, 0xbb, 0x00, 0x01, 0xcc]
+        );
+
+        // Borrowed byte slices point into the input
+        #[derive(Deserialize)]
+        struct EncryptionResponse<'a> {
+            shared_secret: &'a [u8],
+            verify_token: Vec<u8>,
+        }
+        let bytes: Vec<u8> = vec![0x00, 0x02, 0xaa, 0xbb, 0x00, 0x01, 0xcc];
+        let response: EncryptionResponse =
+            from_bytes(&protocol, packet(&protocol, "EncryptionResponse"), &bytes).unwrap();
+        assert_eq!(response.shared_secret, [0xaa, 0xbb]);
+        assert!(bytes
+            .as_ptr_range()
+            .contains(&response.shared_secret.as_ptr()));
+        assert_eq!(response.verify_token, [0xcc]);
+
+        // Errors
+        assert_eq!(
+            from_bytes::<Handshake>(&protocol, packet(&protocol, "Handshake"), &bytes[..2])
+                .unwrap_err()
+                .to_string(),
+            "Unexpected end of input in `server_address` at byte 1"
+        );
+        assert_eq!(
+            from_bytes::<Raw>(&protocol, packet(&protocol, "LoginStart"), &[0x01, b'a'])
+                .unwrap_err(),
+            SerdeError::Message("missing field `next_state`".to_string())
+        );
+        #[derive(Serialize)]
+        enum Data {
+            Value(i32),
+        }
+        assert_eq!(
+            to_value(&Data::Value(1)).unwrap_err().to_string(),
+            "Variant `Data::Value` has data"
+        );
+        assert_eq!(
+            to_bytes(
+                &protocol,
+                packet(&protocol, "LoginStart"),
+                &std::collections::BTreeMap::from([("name", 1)])
+            )
+            .unwrap_err()
+            .to_string(),
+            "Expected a string in `name`"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Traces of decoded packets.
+//!
+//! [`decode_traced`](crate::decode::decode_traced) records the byte range of every value it reads.
+//! [`Trace::render`] lines the bytes up with the fields of the specification,
+//! with nested values indented and the point where decoding failed highlighted:
+//!
+//! ```text
+//! 0000..0001  04                       protocol_version: VarInt = 4
+//! 0001..                               server_address: String
+//! 0001..0003  09 6c                      error: Unexpected end of input
+//! ```
+
+use crate::decode::DecodeError;
+
+/// The number of bytes on a line of a rendered trace.
+const BYTES_PER_LINE: usize = 8;
+/// The maximum number of lines of bytes of a single value.
+const MAX_LINES: usize = 8;
+/// The maximum number of characters of a displayed value.
+const MAX_VALUE_LENGTH: usize = 48;
+
+/// A value that was read while decoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// How deeply the value is nested in custom types and lists.
+    pub depth: usize,
+    /// The name of the field, or the index of a list element like `[0]`.
+    pub name: String,
+    /// The type as written in the specification.
+    pub ty: String,
+    /// The offset of the first byte of the value.
+    pub start: usize,
+    /// The offset after the last byte of the value, or `None` if it couldn't be decoded.
+    pub end: Option<usize>,
+    /// The displayed value, except for structs and lists.
+    pub value: Option<String>,
+}
+
+/// The values read while decoding a packet, in the order they were read.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Trace {
+    pub entries: Vec<TraceEntry>,
+}
+impl Trace {
+    /// Render an annotated hexdump of the decoded `bytes`, ending with `error` if decoding failed.
+    pub fn render(&self, bytes: &[u8], error: Option<&DecodeError>, colour: bool) -> String {
+        let paint = |code: &'static str, text: &str| -> String {
+            if colour {
+                format!("{code}{text}\x1b[0m")
+            } else {
+                text.to_string()
+            }
+        };
+        const DIM: &str = "\x1b[2m";
+        const RED: &str = "\x1b[1;31m";
+
+        let mut output: String = String::new();
+        let mut line = |range: &str, lines: &[String], text: &str, code: Option<&'static str>| {
+            let first: &str = lines.first().map_or("", String::as_str);
+            let width: usize = BYTES_PER_LINE * 3 - 1;
+            let line: String = format!("{range}  {first:<width$}  {text}");
+            output += &match code {
+                Some(code) => paint(code, &line),
+                None => line,
+            };
+            output += "\n";
+            for rest in lines.iter().skip(1) {
+                output += &format!("{:10}  {}\n", "", paint(DIM, rest));
+            }
+        };
+
+        for entry in &self.entries {
+            let mut text: String =
+                format!("{}{}: {}", "  ".repeat(entry.depth), entry.name, entry.ty);
+            let range: String = match entry.end {
+                Some(end) => format!("{:04x}..{end:04x}", entry.start),
+                None => format!("{:04x}..    ", entry.start),
+            };
+            match (entry.end, &entry.value) {
+                (Some(end), Some(value)) => {
+                    text += &format!(" = {}", truncate(value));
+                    line(
+                        &range,
+                        &hex_lines(bytes.get(entry.start..end).unwrap_or_default()),
+                        &text,
+                        None,
+                    );
+                }
+                _ => line(&range, &[], &text, None),
+            }
+        }
+
+        if let Some(error) = error {
+            // The error is shown inside the innermost value that failed
+            let depth: usize = self
+                .entries
+                .iter()
+                .filter(|entry| entry.end.is_none())
+                .map(|entry| entry.depth + 1)
+                .max()
+                .unwrap_or_default();
+            let rest: &[u8] = bytes.get(error.offset..).unwrap_or_default();
+            line(
+                &format!("{:04x}..{:04x}", error.offset, bytes.len()),
+                &hex_lines(rest),
+                &format!("{}error: {}", "  ".repeat(depth), error.kind),
+                Some(RED),
+            );
+        }
+        output
+    }
+}
+
+/// The bytes in hex, split into lines.
+fn hex_lines(bytes: &[u8]) -> Vec<String> {
+    let mut lines: Vec<String> = bytes
+        .chunks(BYTES_PER_LINE)
+        .take(MAX_LINES)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect();
+    let shown: usize = BYTES_PER_LINE * MAX_LINES;
+    if bytes.len() > shown {
+        lines.push(format!("... {} more bytes", bytes.len() - shown));
+    }
+    lines
+}
+
+fn truncate(value: &str) -> String {
+    if value.chars().count() > MAX_VALUE_LENGTH {
+        let truncated: String = value.chars().take(MAX_VALUE_LENGTH - 3).collect();
+        format!("{truncated}...")
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode::{decode_ref, decode_traced},
+        packets::Packet,
+        parse::{Parse, TokenStream},
+        tokenize, Protocol,
+    };
+
+    use super::*;
+
+    fn packet<'a>(protocol: &'a Protocol, name: &str) -> &'a Packet {
+        protocol
+            .packets
+            .iter()
+            .find(|packet| packet.name.0 == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut tokens: TokenStream = tokenize!(include_str!("../spec/1_7_2.specmc"));
+        let protocol: Protocol = Protocol::parse(&mut tokens).unwrap();
+
+        let mut bytes: Vec<u8> = vec![0x04, 0x09];
+        bytes.extend(b"localhost");
+        bytes.extend([0x63, 0xdd, 0x02]);
+        let (result, trace) = decode_traced(&protocol, packet(&protocol, "Handshake"), &bytes);
+        assert_eq!(
+            result,
+            decode_ref(&protocol, packet(&protocol, "Handshake"), &bytes)
+        );
+        assert_eq!(
+            trace.entries[1],
+            TraceEntry {
+                depth: 0,
+                name: "server_address".to_string(),
+                ty: "String".to_string(),
+                start: 1,
+                end: Some(11),
+                value: Some("\"localhost\"".to_string()),
+            }
+        );
+        assert_eq!(
+            trace.render(&bytes, None, false),
+            "\
+0000..0001  04                       protocol_version: VarInt = 4
+0001..000b  09 6c 6f 63 61 6c 68 6f  server_address: String = \"localhost\"
+            73 74
+000b..000d  63 dd                    server_port: u16 = 25565
+000d..000e  02                       next_state: NextState = Login(2)
+"
+        );
+
+        // Nested values are indented, and the error is shown where decoding stopped
+        let bytes: Vec<u8> = vec![
+            0x00, 0x00, 0x00, 0x01, 0x40, 0xff, 0xff, 0xff, 0xfe, 0x01, // position and face
+            0x00, 0x01, 0x40, 0x00, 0x00, 0x00, 0x05, 0x1f, 0x8b,
+        ];
+        let (result, trace) =
+            decode_traced(&protocol, packet(&protocol, "PlayerBlockPlacement"), &bytes);
+        let error: DecodeError = result.unwrap_err();
+        assert_eq!(error.path, "held_item.data");
+        assert_eq!(
+            trace.render(&bytes, Some(&error), false),
+            "\
+0000..0004  00 00 00 01              x: i32 = 1
+0004..0005  40                       y: u8 = 64
+0005..0009  ff ff ff fe              z: i32 = -2
+0009..000a  01                       direction: i8 = 1
+000a..                               held_item: Slot
+000a..000c  00 01                      id: i16 = 1
+000c..000d  40                         count: i8 = 64
+000d..000f  00 00                      damage: i16 = 0
+000f..0011  00 05                      data_length: i16 = 5
+0011..                                 data: Nbt
+0011..0013  1f 8b                        error: Unexpected end of input
+"
+        );
+
+        // Long values are cut short, and colours mark the error
+        let mut bytes: Vec<u8> = vec![0x00, 0x80];
+        bytes.extend([0xaa; 128]);
+        let (result, trace) =
+            decode_traced(&protocol, packet(&protocol, "EncryptionResponse"), &bytes);
+        let rendered: String = trace.render(&bytes, result.as_ref().err(), true);
+        assert!(rendered.contains("... 64 more bytes"));
+        assert!(rendered.contains("shared_secret: List[u8] = [170, 170, 170,"));
+        assert!(rendered.contains("...\n"));
+        assert!(rendered.contains("\x1b[1;31m0082..0082"));
+    }
+}
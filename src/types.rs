@@ -1,17 +1,40 @@
-use specmc_base::{
+use std::fmt::Display;
+
+use specmc_base::parse::Identifier;
+
+use crate::{
+    base::{BaseType, FieldList},
+    bounds::{Analyzer, SizeBounds},
     ensure_tokens,
-    parse::{Identifier, Parse, ParseError},
+    parse::{Parse, Span, TokenStream},
+    Error, Protocol,
 };
 
-use crate::base::{BaseType, FieldList};
-
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
     BaseType(BaseType),
     CustomType(Identifier),
 }
+impl Type {
+    /// The name of the custom type or enum this type refers to, looking through lists.
+    pub fn referenced(&self) -> Option<&Identifier> {
+        match self {
+            Type::CustomType(name) => Some(name),
+            Type::BaseType(BaseType::List { ty, .. }) => ty.referenced(),
+            Type::BaseType(_) => None,
+        }
+    }
+}
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::BaseType(base_type) => write!(f, "{base_type}"),
+            Type::CustomType(name) => write!(f, "{name}"),
+        }
+    }
+}
 impl Parse for Type {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
         if let Ok(base_type) = BaseType::parse(tokens) {
             Ok(Type::BaseType(base_type))
         } else {
@@ -24,16 +47,29 @@ impl Parse for Type {
 pub struct CustomType {
     pub name: Identifier,
     pub fields: FieldList,
+    pub span: Span,
+}
+impl CustomType {
+    /// The minimum and maximum length of the encoded type.
+    pub fn size_bounds(&self, protocol: &Protocol) -> SizeBounds {
+        Analyzer::new(protocol).fields(&self.fields)
+    }
 }
 impl Parse for CustomType {
-    fn parse(tokens: &mut Vec<String>) -> Result<Self, ParseError> {
+    fn parse(tokens: &mut TokenStream) -> Result<Self, Error> {
+        let start: Span = tokens.next_span();
         ensure_tokens!(tokens, "type");
         let name: Identifier = Identifier::parse(tokens)?;
+        let open: Span = tokens.next_span();
         ensure_tokens!(tokens, "{");
         let fields: FieldList = FieldList::parse(tokens)?;
-        ensure_tokens!(tokens, "}");
+        tokens.close_block("type", open)?;
 
-        Ok(CustomType { name, fields })
+        Ok(CustomType {
+            name,
+            fields,
+            span: tokens.span_from(start),
+        })
     }
 }
 
@@ -41,7 +77,7 @@ impl Parse for CustomType {
 mod tests {
     use std::collections::HashSet;
 
-    use specmc_base::tokenize;
+    use crate::tokenize;
 
     use crate::{
         base::{Field, IntegerType},
@@ -52,7 +88,7 @@ mod tests {
 
     #[test]
     fn test_type() {
-        let mut tokens: Vec<String> = tokenize!("bool i32 TestType");
+        let mut tokens: TokenStream = tokenize!("bool i32 TestType");
 
         test_parse!(tokens, Type, Ok(Type::BaseType(BaseType::Bool)));
         test_parse!(
@@ -67,12 +103,12 @@ mod tests {
         );
 
         assert!(tokens.is_empty());
-        test_parse!(tokens, Type, Err(ParseError::EndOfFile));
+        test_parse!(tokens, Type, Err(Error::EndOfFile));
     }
 
     #[test]
     fn test_custom_type() {
-        let mut tokens: Vec<String> = tokenize!(
+        let mut tokens: TokenStream = tokenize!(
             "
             type TestType {
                 i32 a
@@ -95,24 +131,28 @@ mod tests {
                         name: Identifier("a".to_string()),
                         value: None,
                         conditions: HashSet::new(),
+                        span: Span::default(),
                     },
                     Field {
                         ty: Type::BaseType(BaseType::Bool),
                         name: Identifier("b".to_string()),
                         value: None,
                         conditions: HashSet::new(),
+                        span: Span::default(),
                     },
                     Field {
                         ty: Type::BaseType(BaseType::Integer(IntegerType::I32)),
                         name: Identifier("c".to_string()),
                         value: None,
                         conditions: HashSet::from_iter(vec!["b".to_string()]),
+                        span: Span::default(),
                     },
-                ])
+                ]),
+                span: Span::default(),
             })
         );
 
         assert!(tokens.is_empty());
-        test_parse!(tokens, CustomType, Err(ParseError::EndOfFile));
+        test_parse!(tokens, CustomType, Err(Error::EndOfFile));
     }
 }
@@ -0,0 +1,453 @@
+//! Dynamic values of packets and their fields.
+//!
+//! A [`PacketValue`] can hold any packet described by a specification,
+//! so packets can be inspected and built without generated code.
+//! A [`PacketValueRef`] borrows strings and bytes from the input it was decoded from.
+
+use std::fmt::Display;
+
+use specmc_base::parse::Literal;
+
+use crate::{
+    codec::Reader,
+    nbt::{self, NamedTag},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketValue {
+    Bool(bool),
+    /// Any integer type, including `VarInt` and `VarLong`.
+    Integer(i128),
+    Float(f64),
+    String(String),
+    /// Bytes to encode as a `List[u8]`, or as NBT that is already encoded.
+    /// Decoding never produces them: byte lists are decoded as a `List` of integers.
+    Bytes(Vec<u8>),
+    List(Vec<PacketValue>),
+    /// `None` is an empty NBT, which is a single end tag.
+    Nbt(Option<NamedTag>),
+    /// The fields of a packet or custom type, in order.
+    /// Conditional fields that are not present are left out.
+    Struct(Vec<(String, PacketValue)>),
+    /// A value of an enum, with the name of its variant.
+    Enum {
+        variant: String,
+        value: i128,
+    },
+}
+impl PacketValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PacketValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer or an enum.
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            PacketValue::Integer(value) | PacketValue::Enum { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            PacketValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PacketValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            PacketValue::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[PacketValue]> {
+        match self {
+            PacketValue::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_nbt(&self) -> Option<&NamedTag> {
+        match self {
+            PacketValue::Nbt(value) => value.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&[(String, PacketValue)]> {
+        match self {
+            PacketValue::Struct(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// The name of the variant of an enum value.
+    pub fn as_variant(&self) -> Option<&str> {
+        match self {
+            PacketValue::Enum { variant, .. } => Some(variant),
+            _ => None,
+        }
+    }
+
+    /// Find a field of a struct by name.
+    pub fn get(&self, name: &str) -> Option<&PacketValue> {
+        self.as_struct()?
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Find a field of a struct by name, to modify it.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut PacketValue> {
+        match self {
+            PacketValue::Struct(fields) => fields
+                .iter_mut()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Look up a nested value by a dot separated path.
+    /// Each part is the name of a struct field or the index of a list element,
+    /// like `slots.0.count`.
+    pub fn path(&self, path: &str) -> Option<&PacketValue> {
+        path.split('.')
+            .filter(|part| !part.is_empty())
+            .try_fold(self, |value, part| match value {
+                PacketValue::List(values) => values.get(part.parse::<usize>().ok()?),
+                _ => value.get(part),
+            })
+    }
+
+    /// The value as it is compared in conditions.
+    pub(crate) fn literal(&self) -> Option<Literal> {
+        match self {
+            PacketValue::Bool(value) => Some(Literal::Boolean(*value)),
+            PacketValue::Integer(value) | PacketValue::Enum { value, .. } => {
+                isize::try_from(*value).ok().map(Literal::Integer)
+            }
+            PacketValue::Float(value) => Some(Literal::Float(*value)),
+            PacketValue::String(value) => Some(Literal::String(value.clone())),
+            _ => None,
+        }
+    }
+}
+impl Display for PacketValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketValue::Bool(value) => write!(f, "{value}"),
+            PacketValue::Integer(value) => write!(f, "{value}"),
+            PacketValue::Float(value) => write!(f, "{value:?}"),
+            PacketValue::String(value) => write!(f, "{value:?}"),
+            PacketValue::Bytes(value) => {
+                write!(f, "<")?;
+                for (i, byte) in value.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{byte:02x}")?;
+                }
+                write!(f, ">")
+            }
+            PacketValue::List(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            PacketValue::Nbt(Some(value)) => write!(f, "{value}"),
+            PacketValue::Nbt(None) => write!(f, "none"),
+            PacketValue::Struct(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {name}: {value}")?;
+                }
+                if !fields.is_empty() {
+                    write!(f, " ")?;
+                }
+                write!(f, "}}")
+            }
+            PacketValue::Enum { variant, value } => write!(f, "{variant}({value})"),
+        }
+    }
+}
+
+/// A decoded value that borrows from the input and the specification.
+/// Only compressed NBT is owned, because it has to be decompressed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketValueRef<'a> {
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    String(&'a str),
+    /// A `List[u8]`, as the bytes of the input.
+    Bytes(&'a [u8]),
+    List(Vec<PacketValueRef<'a>>),
+    /// Uncompressed NBT, as the bytes of the input.
+    /// It is checked while decoding, so it can always be read with [`nbt::read_nbt`].
+    RawNbt(&'a [u8]),
+    /// Compressed NBT, decompressed into an owned tag.
+    Nbt(Option<NamedTag>),
+    Struct(Vec<(&'a str, PacketValueRef<'a>)>),
+    Enum {
+        variant: &'a str,
+        value: i128,
+    },
+}
+impl<'a> PacketValueRef<'a> {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PacketValueRef::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer or an enum.
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            PacketValueRef::Integer(value) | PacketValueRef::Enum { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            PacketValueRef::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            PacketValueRef::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The bytes of a `List[u8]` or of uncompressed NBT.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            PacketValueRef::Bytes(value) | PacketValueRef::RawNbt(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[PacketValueRef<'a>]> {
+        match self {
+            PacketValueRef::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&[(&'a str, PacketValueRef<'a>)]> {
+        match self {
+            PacketValueRef::Struct(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// The name of the variant of an enum value.
+    pub fn as_variant(&self) -> Option<&'a str> {
+        match self {
+            PacketValueRef::Enum { variant, .. } => Some(variant),
+            _ => None,
+        }
+    }
+
+    /// Find a field of a struct by name.
+    pub fn get(&self, name: &str) -> Option<&PacketValueRef<'a>> {
+        self.as_struct()?
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Look up a nested value by a dot separated path, like [`PacketValue::path`].
+    pub fn path(&self, path: &str) -> Option<&PacketValueRef<'a>> {
+        path.split('.')
+            .filter(|part| !part.is_empty())
+            .try_fold(self, |value, part| match value {
+                PacketValueRef::List(values) => values.get(part.parse::<usize>().ok()?),
+                _ => value.get(part),
+            })
+    }
+
+    /// Copy the value out of the input.
+    /// The result is the same as decoding into a [`PacketValue`] directly.
+    pub fn into_owned(self) -> PacketValue {
+        match self {
+            PacketValueRef::Bool(value) => PacketValue::Bool(value),
+            PacketValueRef::Integer(value) => PacketValue::Integer(value),
+            PacketValueRef::Float(value) => PacketValue::Float(value),
+            PacketValueRef::String(value) => PacketValue::String(value.to_string()),
+            PacketValueRef::Bytes(value) => PacketValue::List(
+                value
+                    .iter()
+                    .map(|byte| PacketValue::Integer(*byte as i128))
+                    .collect(),
+            ),
+            PacketValueRef::List(values) => {
+                PacketValue::List(values.into_iter().map(PacketValueRef::into_owned).collect())
+            }
+            PacketValueRef::RawNbt(value) => PacketValue::Nbt(
+                nbt::read_nbt(&mut Reader::new(value)).expect("raw NBT is checked when decoded"),
+            ),
+            PacketValueRef::Nbt(value) => PacketValue::Nbt(value),
+            PacketValueRef::Struct(fields) => PacketValue::Struct(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.into_owned()))
+                    .collect(),
+            ),
+            PacketValueRef::Enum { variant, value } => PacketValue::Enum {
+                variant: variant.to_string(),
+                value,
+            },
+        }
+    }
+
+    /// The value as it is compared in conditions.
+    pub(crate) fn literal(&self) -> Option<Literal> {
+        match self {
+            PacketValueRef::Bool(value) => Some(Literal::Boolean(*value)),
+            PacketValueRef::Integer(value) | PacketValueRef::Enum { value, .. } => {
+                isize::try_from(*value).ok().map(Literal::Integer)
+            }
+            PacketValueRef::Float(value) => Some(Literal::Float(*value)),
+            PacketValueRef::String(value) => Some(Literal::String(value.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for PacketValue {
+    fn from(value: bool) -> Self {
+        PacketValue::Bool(value)
+    }
+}
+macro_rules! from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for PacketValue {
+                fn from(value: $ty) -> Self {
+                    PacketValue::Integer(value as i128)
+                }
+            }
+        )*
+    };
+}
+from_integer!(u8, u16, u32, u64, i8, i16, i32, i64, isize, usize, i128);
+impl From<f32> for PacketValue {
+    fn from(value: f32) -> Self {
+        PacketValue::Float(value as f64)
+    }
+}
+impl From<f64> for PacketValue {
+    fn from(value: f64) -> Self {
+        PacketValue::Float(value)
+    }
+}
+impl From<&str> for PacketValue {
+    fn from(value: &str) -> Self {
+        PacketValue::String(value.to_string())
+    }
+}
+impl From<String> for PacketValue {
+    fn from(value: String) -> Self {
+        PacketValue::String(value)
+    }
+}
+impl From<Vec<PacketValue>> for PacketValue {
+    fn from(values: Vec<PacketValue>) -> Self {
+        PacketValue::List(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbt::Nbt;
+
+    use super::*;
+
+    #[test]
+    fn test_packet_value() {
+        let slot = |id: i16, count: i8| {
+            PacketValue::Struct(vec![
+                ("id".to_string(), id.into()),
+                ("count".to_string(), count.into()),
+            ])
+        };
+        let mut value: PacketValue = PacketValue::Struct(vec![
+            (
+                "mode".to_string(),
+                PacketValue::Enum {
+                    variant: "Creative".to_string(),
+                    value: 1,
+                },
+            ),
+            ("name".to_string(), "Steve".into()),
+            ("health".to_string(), 20.0f32.into()),
+            ("flying".to_string(), false.into()),
+            ("slots".to_string(), vec![slot(1, 64), slot(-1, 0)].into()),
+            ("data".to_string(), PacketValue::Bytes(vec![0x0a, 0xff])),
+            (
+                "tag".to_string(),
+                PacketValue::Nbt(Some(NamedTag {
+                    name: "".to_string(),
+                    tag: Nbt::Compound(vec![("a".to_string(), Nbt::Byte(1))]),
+                })),
+            ),
+        ]);
+
+        assert_eq!(value.get("mode").unwrap().as_variant(), Some("Creative"));
+        assert_eq!(value.get("mode").unwrap().as_integer(), Some(1));
+        assert_eq!(value.get("name").unwrap().as_str(), Some("Steve"));
+        assert_eq!(value.get("health").unwrap().as_float(), Some(20.0));
+        assert_eq!(value.get("flying").unwrap().as_bool(), Some(false));
+        assert_eq!(value.get("slots").unwrap().as_list().unwrap().len(), 2);
+        assert_eq!(
+            value.get("data").unwrap().as_bytes(),
+            Some(&[0x0a, 0xff][..])
+        );
+        assert!(value.get("tag").unwrap().as_nbt().is_some());
+        assert!(PacketValue::Nbt(None).as_nbt().is_none());
+        assert_eq!(value.get("name").unwrap().as_integer(), None);
+        assert_eq!(value.get("missing"), None);
+
+        assert_eq!(value.path("slots.0.count"), Some(&PacketValue::Integer(64)));
+        assert_eq!(value.path("slots.1.id").unwrap().as_integer(), Some(-1));
+        assert_eq!(value.path("slots.2.id"), None);
+        assert_eq!(value.path("slots.first"), None);
+        assert_eq!(value.path(""), Some(&value));
+
+        *value.get_mut("flying").unwrap() = true.into();
+        assert_eq!(value.get("flying"), Some(&PacketValue::Bool(true)));
+
+        assert_eq!(
+            value.to_string(),
+            "{ mode: Creative(1), name: \"Steve\", health: 20.0, flying: true, slots: [{ id: 1, count: 64 }, { id: -1, count: 0 }], data: <0a ff>, tag: {a: 1b} }"
+        );
+        assert_eq!(PacketValue::Struct(vec![]).to_string(), "{}");
+        assert_eq!(PacketValue::Nbt(None).to_string(), "none");
+    }
+}